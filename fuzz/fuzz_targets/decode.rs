@@ -0,0 +1,36 @@
+//! Decodes and formats arbitrary instructions.
+//!
+//! Every instruction the decoder rejects must be rejected by the virtual machine as a
+//! [VmError::BadInsn], and every instruction it accepts must format without panicking.
+
+#![no_main]
+
+use chip8::{
+  insn::{visit::InsnVisit, Insn},
+  vm::{error::VmError, symbols::Symbols, Vm},
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+  let mut symbols = Symbols::new();
+  let mut vm = Vm::new();
+
+  for (i, pair) in data.chunks_exact(2).enumerate() {
+    let [hi, lo] = [pair[0], pair[1]];
+
+    // Name the address of every other instruction so labels get formatted too.
+    if i % 2 == 1 {
+      let addr = (hi as u16) << 8 & 0xf00 | lo as u16;
+      symbols.add_label(&format!("l{addr:x}"), addr);
+    }
+
+    let insn = Insn::from_bytes(hi, lo);
+    let bad = matches!(vm.visit_insn(hi, lo), Err(VmError::BadInsn(_)));
+    assert_eq!(insn.is_none(), bad, "{hi:02x}{lo:02x}");
+
+    if let Some(insn) = insn {
+      let _ = insn.to_string();
+      let _ = symbols.format_insn(&insn);
+    }
+  }
+});
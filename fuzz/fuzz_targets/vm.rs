@@ -0,0 +1,45 @@
+//! Runs arbitrary programs under arbitrary quirks and key presses.
+//!
+//! The first byte selects the quirks, the second the keys held down, rotated every
+//! frame, and the rest is the program. Faults must surface as a [VmError], never as a
+//! panic.
+
+#![no_main]
+
+use chip8::vm::{error::VmError, flags::VmKey, quirks::Quirks, Vm};
+use libfuzzer_sys::fuzz_target;
+
+/// Number of frames each program runs for.
+const FRAMES: u32 = 64;
+
+fuzz_target!(|data: &[u8]| {
+  let [quirks, keys, program @ ..] = data else {
+    return;
+  };
+
+  let mut vm = Vm::new();
+  vm.quirks = Quirks {
+    shift: quirks & 0x01 != 0,
+    load_store: quirks & 0x02 != 0,
+    vf_order: quirks & 0x04 != 0,
+    logic: quirks & 0x08 != 0,
+    clip: quirks & 0x10 != 0,
+    jump: quirks & 0x20 != 0,
+    vblank: quirks & 0x40 != 0,
+  };
+
+  if let Err(err) = vm.load_program(program) {
+    assert!(matches!(err, VmError::BadProgramTooLarge(..)));
+    return;
+  }
+
+  for frame in 0..FRAMES {
+    let keys = (*keys as u16).rotate_left(frame);
+    vm.signal_keys(VmKey::from_bits_truncate(keys));
+
+    if let Err(err) = vm.run_frame() {
+      let _ = err.to_string();
+      return;
+    }
+  }
+});
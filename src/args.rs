@@ -0,0 +1,419 @@
+//! Command line arguments.
+
+use crate::{
+  dump::VramFormat,
+  ui::{
+    filter::Filter,
+    theme::{parse_color, ColorSupport, Theme},
+  },
+  vm::symbols::parse_range,
+};
+use eyre::{bail, eyre, Result};
+use std::{env, ops::Range};
+
+/// Default number of frames run by commands without an interface.
+const FRAMES: u32 = 600;
+/// Options that don't take a value.
+const FLAGS: [&str; 6] = [
+  "is-paused",
+  "headless",
+  "ansi",
+  "watch",
+  "keep-debug",
+  "update",
+];
+
+/// Default number of entries listed in a profile report.
+const PROFILE_TOP: usize = 20;
+
+/// What to do with the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+  /// Runs the program in the terminal interface, or without an interface for `frames`
+  /// frames when `headless`, then writes the machine state to `dump`, or stdout. When
+  /// `ansi` the program runs in real time, printing frames to stdout, for `frames` frames
+  /// if given. The last frame is written to the image `screenshot` if given.
+  Run {
+    headless: bool,
+    ansi: bool,
+    frames: Option<u32>,
+    screenshot: Option<String>,
+    dump: Option<String>,
+    dump_mem: Range<u16>,
+    vram_format: VramFormat,
+  },
+  /// Runs the program without an interface for `frames` frames and prints the `top`
+  /// hottest addresses and subroutines.
+  Profile { frames: u32, top: usize },
+  /// Runs the Rhai script at `path` against the program without an interface.
+  Script { path: String },
+  /// Runs the program without an interface for `frames` frames and writes a coverage
+  /// report to `output`, or stdout.
+  Coverage {
+    frames: u32,
+    format: CoverageFormat,
+    output: Option<String>,
+  },
+  /// Runs every program in the directory without an interface and compares its last
+  /// frame to a golden frame, or writes the golden frames when `update`.
+  Test { update: bool },
+}
+
+/// Format of a coverage report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageFormat {
+  /// An lcov tracefile.
+  Lcov,
+  /// Annotated disassembly.
+  Asm,
+}
+
+/// Parsed command line arguments.
+pub struct Args {
+  /// What to do with the program.
+  pub command: Command,
+  /// Path to the program to run, or a directory to browse or test programs in.
+  pub program: Option<String>,
+  /// Whether the virtual machine starts paused.
+  pub is_paused: bool,
+  /// Colour theme, already converted to colours the terminal supports.
+  pub theme: Theme,
+  /// Whether the theme or its pixel colours were chosen, which takes precedence over
+  /// colours from the ROM database.
+  pub custom_theme: bool,
+  /// Anti-flicker display filter.
+  pub filter: Filter,
+  /// Path to a debugger command script executed on startup.
+  pub script: Option<String>,
+  /// Path to a symbol file used to label disassembly.
+  pub symbols: Option<String>,
+  /// Path to a movie played back as key input.
+  pub movie: Option<String>,
+  /// Movie entries played back as key input, separated by commas.
+  pub keys: Option<String>,
+  /// Path to a movie file key input is recorded to.
+  pub record: Option<String>,
+  /// Path to the ROM database, or `None` for the default path.
+  pub rom_db: Option<String>,
+  /// Whether the program is reloaded when its file changes.
+  pub watch: bool,
+  /// Whether reloading the program keeps breakpoints and symbols.
+  pub keep_debug: bool,
+  /// Path a crash dump is written to when the program faults.
+  pub crash_dump: Option<String>,
+  /// Path to a state dump, such as a crash dump, loaded after the program.
+  pub restore: Option<String>,
+}
+
+impl Args {
+  /// Parses arguments from the process environment.
+  pub fn parse() -> Result<Self> {
+    Self::parse_from(env::args().skip(1))
+  }
+
+  /// Parses arguments from an iterator, excluding the binary name.
+  ///
+  /// Options are written as `--name=value` or `--name value`, except for flags such as
+  /// `--is-paused`, which are set alone, or followed by `true` or `false`. After `=`, flags
+  /// also take `1`, `0`, `yes` or `no`.
+  pub fn parse_from<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+    let mut args = args.into_iter().peekable();
+    let command = match args.peek().map(String::as_str) {
+      Some(command @ ("run" | "profile" | "coverage" | "script" | "test")) => {
+        let command = command.to_string();
+        args.next();
+        command
+      }
+      _ => "run".to_string(),
+    };
+
+    let mut positional = Vec::new();
+    let mut frames = None;
+    let mut top = PROFILE_TOP;
+    let mut format = CoverageFormat::Asm;
+    let mut output = None;
+    let mut movie = None;
+    let mut keys = None;
+    let mut headless = false;
+    let mut ansi = false;
+    let mut screenshot = None;
+    let mut dump = None;
+    let mut dump_mem = 0..0x1000;
+    let mut vram_format = VramFormat::Bits;
+    let mut record = None;
+    let mut is_paused = false;
+    let mut theme = None;
+    let mut theme_fg = None;
+    let mut theme_bg = None;
+    let mut theme_label = None;
+    let mut theme_accent = None;
+    let mut theme_value = None;
+    let mut theme_error = None;
+    let mut color_support = None;
+    let mut filter = Filter::default();
+    let mut script = None;
+    let mut symbols = None;
+    let mut rom_db = None;
+    let mut watch = false;
+    let mut keep_debug = false;
+    let mut update = false;
+    let mut crash_dump = None;
+    let mut restore = None;
+
+    while let Some(arg) = args.next() {
+      let (name, value) = match arg.strip_prefix("--") {
+        Some(flag) => match flag.split_once('=') {
+          Some((name, value)) => (name, value.to_string()),
+          None if FLAGS.contains(&flag) => match args.peek().map(String::as_str) {
+            Some(value @ ("true" | "false")) => {
+              let value = value.to_string();
+              args.next();
+              (flag, value)
+            }
+            _ => (flag, String::new()),
+          },
+          None => (flag, args.next().unwrap_or_default()),
+        },
+        None => {
+          positional.push(arg);
+          continue;
+        }
+      };
+      let value = value.as_str();
+
+      match name {
+        "is-paused" => is_paused = parse_arg_flag(name, value)?,
+        "theme" => {
+          let named =
+            Theme::named(value).ok_or_else(|| eyre!("Unknown theme `{value}`"))?;
+          theme = Some(named);
+        }
+        "theme-fg" => theme_fg = Some(parse_arg_color(name, value)?),
+        "theme-bg" => theme_bg = Some(parse_arg_color(name, value)?),
+        "theme-label" => theme_label = Some(parse_arg_color(name, value)?),
+        "theme-accent" => theme_accent = Some(parse_arg_color(name, value)?),
+        "theme-value" => theme_value = Some(parse_arg_color(name, value)?),
+        "theme-error" => theme_error = Some(parse_arg_color(name, value)?),
+        "color" => {
+          color_support = Some(match value {
+            "truecolor" | "24bit" => ColorSupport::TrueColor,
+            "256" => ColorSupport::Ansi256,
+            "16" => ColorSupport::Ansi16,
+            _ => bail!("Unknown colour support `{value}`"),
+          })
+        }
+        "filter" => {
+          filter =
+            Filter::named(value).ok_or_else(|| eyre!("Unknown filter `{value}`"))?
+        }
+        "script" => script = Some(value.to_string()),
+        "symbols" => symbols = Some(value.to_string()),
+        "frames" => frames = Some(parse_arg_number(name, value)?),
+        "top" => top = parse_arg_number(name, value)?,
+        "format" => {
+          format = match value {
+            "lcov" => CoverageFormat::Lcov,
+            "asm" => CoverageFormat::Asm,
+            _ => bail!("Unknown coverage format `{value}`"),
+          }
+        }
+        "output" => output = Some(value.to_string()),
+        "movie" => movie = Some(value.to_string()),
+        "keys" => keys = Some(value.to_string()),
+        "headless" => headless = parse_arg_flag(name, value)?,
+        "ansi" => ansi = parse_arg_flag(name, value)?,
+        "screenshot" => screenshot = Some(value.to_string()),
+        "dump-state" => dump = Some(value.to_string()),
+        "dump-mem" => {
+          dump_mem = parse_range(value)
+            .filter(|range| range.start <= range.end && range.end <= 0x1000)
+            .ok_or_else(|| eyre!("Bad memory range `{value}` for `--{name}`"))?
+        }
+        "vram-format" => {
+          vram_format = VramFormat::named(value)
+            .ok_or_else(|| eyre!("Unknown vram format `{value}`"))?
+        }
+        "record" => record = Some(value.to_string()),
+        "rom-db" => rom_db = Some(value.to_string()),
+        "watch" => watch = parse_arg_flag(name, value)?,
+        "keep-debug" => keep_debug = parse_arg_flag(name, value)?,
+        "crash-dump" => crash_dump = Some(value.to_string()),
+        "restore" => restore = Some(value.to_string()),
+        "update" => update = parse_arg_flag(name, value)?,
+        _ => bail!("Unknown option `--{name}`"),
+      }
+    }
+
+    if movie.is_some() && keys.is_some() {
+      bail!("Expected only one of `--movie` and `--keys`");
+    }
+
+    if dump.is_some() && !headless {
+      bail!("Expected `--headless` with `--dump-state`");
+    }
+
+    if headless && ansi {
+      bail!("Expected only one of `--headless` and `--ansi`");
+    }
+
+    if screenshot.is_some() && !headless && !ansi {
+      bail!("Expected `--headless` or `--ansi` with `--screenshot`");
+    }
+
+    if watch && (headless || ansi) {
+      bail!("Expected the interface with `--watch`");
+    }
+
+    let support = color_support.unwrap_or_else(ColorSupport::detect);
+
+    // colours override the named theme whichever order they are given in.
+    let custom_theme = theme.is_some() || theme_fg.is_some() || theme_bg.is_some();
+    let mut theme = theme.unwrap_or_default();
+    let colors = [
+      (&mut theme.fg, theme_fg),
+      (&mut theme.bg, theme_bg),
+      (&mut theme.label, theme_label),
+      (&mut theme.accent, theme_accent),
+      (&mut theme.value, theme_value),
+      (&mut theme.error, theme_error),
+    ];
+    for (color, value) in colors {
+      if let Some(value) = value {
+        *color = value;
+      }
+    }
+
+    let mut positional = positional.into_iter();
+    let script_path = match command.as_str() {
+      "script" => Some(
+        positional
+          .next()
+          .ok_or_else(|| eyre!("Expected script path"))?,
+      ),
+      _ => None,
+    };
+
+    let program = positional.next();
+    if let Some(arg) = positional.next() {
+      bail!("Unexpected argument `{arg}`");
+    }
+
+    let command = match command.as_str() {
+      "script" => Command::Script {
+        path: script_path.unwrap_or_default(),
+      },
+      "profile" => Command::Profile {
+        frames: frames.unwrap_or(FRAMES),
+        top,
+      },
+      "coverage" => Command::Coverage {
+        frames: frames.unwrap_or(FRAMES),
+        format,
+        output,
+      },
+      "test" => Command::Test { update },
+      _ => Command::Run {
+        headless,
+        ansi,
+        frames: match headless {
+          true => Some(frames.unwrap_or(FRAMES)),
+          false => frames,
+        },
+        screenshot,
+        dump,
+        dump_mem,
+        vram_format,
+      },
+    };
+
+    Ok(Self {
+      command,
+      program,
+      is_paused,
+      theme: theme.fallback(support),
+      custom_theme,
+      filter,
+      script,
+      symbols,
+      movie,
+      keys,
+      record,
+      rom_db,
+      watch,
+      keep_debug,
+      crash_dump,
+      restore,
+    })
+  }
+}
+
+fn parse_arg_color(name: &str, value: &str) -> Result<tui::style::Color> {
+  parse_color(value).ok_or_else(|| eyre!("Bad colour `{value}` for `--{name}`"))
+}
+
+/// Parses the value of a flag, which is `true` when given alone.
+fn parse_arg_flag(name: &str, value: &str) -> Result<bool> {
+  match value {
+    "" | "true" | "1" | "yes" => Ok(true),
+    "false" | "0" | "no" => Ok(false),
+    _ => bail!("Bad value `{value}` for `--{name}`, expected true or false"),
+  }
+}
+
+fn parse_arg_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T> {
+  value
+    .parse()
+    .map_err(|_| eyre!("Bad number `{value}` for `--{name}`"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(args: &[&str]) -> Args {
+    Args::parse_from(args.iter().map(|arg| arg.to_string())).unwrap()
+  }
+
+  #[test]
+  fn is_paused() {
+    assert!(!parse(&["game.ch8"]).is_paused);
+    assert!(parse(&["game.ch8", "--is-paused"]).is_paused);
+    assert!(parse(&["--is-paused", "game.ch8"]).is_paused);
+    assert!(parse(&["game.ch8", "--is-paused=true"]).is_paused);
+    assert!(!parse(&["game.ch8", "--is-paused=false"]).is_paused);
+    assert!(parse(&["--is-paused", "true", "game.ch8"]).is_paused);
+    assert!(!parse(&["--is-paused", "false", "game.ch8"]).is_paused);
+    assert!(parse(&["game.ch8", "--is-paused=yes"]).is_paused);
+    assert!(!parse(&["game.ch8", "--is-paused=0"]).is_paused);
+    assert!(!parse(&["game.ch8", "--is-paused=no"]).is_paused);
+    assert!(!parse(&["game.ch8", "--keep-debug=false"]).keep_debug);
+
+    let args = ["game.ch8", "--is-paused=off"].map(String::from);
+    assert!(Args::parse_from(args).is_err());
+    assert_eq!(
+      parse(&["--is-paused", "true", "game.ch8"])
+        .program
+        .as_deref(),
+      Some("game.ch8")
+    );
+  }
+
+  #[test]
+  fn theme_overrides() {
+    let named = Theme::named("amber").unwrap();
+    let red = parse_color("#ff0000").unwrap();
+
+    for args in [
+      ["--theme-fg=#ff0000", "--theme=amber", "--color=truecolor"],
+      ["--theme=amber", "--theme-fg=#ff0000", "--color=truecolor"],
+    ] {
+      let args = parse(&args);
+      assert!(args.custom_theme);
+      assert_eq!(args.theme.fg, red);
+      assert_eq!(args.theme.bg, named.bg);
+    }
+
+    let args = parse(&["--theme-error=#ff0000", "--color=truecolor"]);
+    assert_eq!(args.theme.error, red);
+  }
+}
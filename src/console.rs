@@ -0,0 +1,472 @@
+//! Debugger commands.
+//!
+//! # Commands
+//! * `break <addr>` - Adds a breakpoint.
+//! * `delete [addr]` - Removes a breakpoint, or every breakpoint.
+//! * `watch mem <beg>..<end>` - Stops when memory in range is written to.
+//! * `unwatch mem <beg>..<end>` - Removes a watch.
+//! * `set <reg> <val>` - Sets a register, e.g. `set v3 0x10`.
+//! * `set mem <addr> <val>` - Sets a byte of memory.
+//! * `x/<n> <addr>` - Examines `n` bytes of memory, e.g. `x/16 i`.
+//! * `goto <addr>` - Sets the program counter.
+//! * `step [n]` - Executes `n` instructions.
+//! * `until <addr>|ret` - Runs until the program counter reaches `addr`, or until the
+//!   current subroutine returns.
+//! * `continue` - Resumes execution.
+//! * `pause` - Pauses execution.
+//! * `save <slot>` - Saves machine state to a slot.
+//! * `load <slot>` - Loads machine state from a slot.
+//! * `dump <path>` - Writes machine state and any fault to a JSON file.
+//! * `restore <path>` - Loads machine state from a JSON file, such as a crash dump.
+//! * `info` - Lists breakpoints, watches and slots.
+//! * `script <path>` - Runs a Rhai script, see [crate::script].
+//! * `help` - Lists commands.
+//!
+//! Values are decimal, or hex when prefixed with `0x` or `$`. Register names such as
+//! `i` or `pc` and symbol names can be used wherever an address is expected.
+
+use crate::{
+  dump::StateDump,
+  script,
+  vm::{debug::Until, reg::VmReg, Vm},
+};
+use eyre::{bail, eyre, Result, WrapErr};
+use std::{collections::BTreeMap, fs, ops::Range, path::Path};
+
+/// Maximum number of instructions executed by `until` before giving up.
+pub const UNTIL_BUDGET: u32 = 1_000_000;
+
+/// Names of every command, used for completion.
+pub const COMMANDS: [&str; 18] = [
+  "break", "delete", "watch", "unwatch", "set", "x", "goto", "step", "until", "continue",
+  "pause", "save", "load", "dump", "restore", "info", "script", "help",
+];
+
+/// A parsed debugger command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+  Break(u16),
+  Delete(Option<u16>),
+  Watch(Range<u16>),
+  Unwatch(Range<u16>),
+  SetReg(VmReg, u16),
+  SetMem(u16, u8),
+  Examine(u16, u16),
+  Goto(u16),
+  Step(u32),
+  Until(Until),
+  Continue,
+  Pause,
+  Save(String),
+  Load(String),
+  Dump(String),
+  Restore(String),
+  Info,
+  Script(String),
+  Help,
+}
+
+impl Command {
+  /// Parses a command, resolving register names against `vm`.
+  pub fn parse(line: &str, vm: &Vm) -> Result<Self> {
+    let mut words = line.split_whitespace();
+    let name = words.next().ok_or_else(|| eyre!("Expected command"))?;
+    let args = words.collect::<Vec<_>>();
+    let arg = |i: usize| {
+      args
+        .get(i)
+        .copied()
+        .ok_or_else(|| eyre!("`{name}` expects {} argument(s)", i + 1))
+    };
+
+    let (name, count) = match name.split_once('/') {
+      Some((name, count)) => (name, Some(count)),
+      None => (name, None),
+    };
+
+    Ok(match name {
+      "break" | "b" => Self::Break(parse_value(arg(0)?, vm)?),
+      "delete" | "d" => Self::Delete(match args.first() {
+        Some(arg) => Some(parse_value(arg, vm)?),
+        None => None,
+      }),
+      "watch" | "w" => Self::Watch(parse_mem_range(&args, vm)?),
+      "unwatch" => Self::Unwatch(parse_mem_range(&args, vm)?),
+      "set" => match arg(0)? {
+        "mem" => {
+          let value = parse_value(arg(2)?, vm)?;
+          let value =
+            u8::try_from(value).map_err(|_| eyre!("Value `{value:#x}` out of range"))?;
+          Self::SetMem(parse_value(arg(1)?, vm)?, value)
+        }
+        reg => Self::SetReg(
+          VmReg::parse(reg).ok_or_else(|| eyre!("Unknown register `{reg}`"))?,
+          parse_value(arg(1)?, vm)?,
+        ),
+      },
+      "x" => Self::Examine(
+        parse_value(arg(0)?, vm)?,
+        match count {
+          Some(count) => parse_value(count, vm)?,
+          None => 16,
+        },
+      ),
+      "goto" | "g" => Self::Goto(parse_value(arg(0)?, vm)?),
+      "step" | "s" => Self::Step(match args.first() {
+        Some(n) => parse_value(n, vm)? as u32,
+        None => 1,
+      }),
+      "until" | "u" => Self::Until(match arg(0)? {
+        "ret" => Until::Ret,
+        addr => Until::Addr(parse_value(addr, vm)?),
+      }),
+      "continue" | "c" => Self::Continue,
+      "pause" | "p" => Self::Pause,
+      "save" => Self::Save(arg(0)?.to_string()),
+      "load" => Self::Load(arg(0)?.to_string()),
+      "dump" => Self::Dump(arg(0)?.to_string()),
+      "restore" => Self::Restore(arg(0)?.to_string()),
+      "info" => Self::Info,
+      "script" => Self::Script(arg(0)?.to_string()),
+      "help" | "?" => Self::Help,
+      _ => bail!("Unknown command `{name}`"),
+    })
+  }
+}
+
+/// The result of executing a command.
+#[derive(Debug, Default)]
+pub struct Output {
+  /// Lines of text to display.
+  pub lines: Vec<String>,
+  /// Whether execution should be paused or resumed.
+  pub paused: Option<bool>,
+}
+
+impl Output {
+  fn line(line: impl Into<String>) -> Self {
+    Self {
+      lines: vec![line.into()],
+      paused: None,
+    }
+  }
+}
+
+/// Executes debugger commands against a [Vm].
+#[derive(Default)]
+pub struct Console {
+  /// Saved machine states by slot name.
+  pub slots: BTreeMap<String, Vm>,
+}
+
+impl Console {
+  /// Creates a [Console].
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Parses and executes a command line.
+  pub fn execute(&mut self, vm: &mut Vm, line: &str) -> Result<Output> {
+    let command = Command::parse(line, vm)?;
+    self.execute_command(vm, command)
+  }
+
+  /// Executes a parsed command.
+  pub fn execute_command(&mut self, vm: &mut Vm, command: Command) -> Result<Output> {
+    Ok(match command {
+      Command::Break(addr) => {
+        vm.add_breakpoint(addr);
+        Output::line(format!("Breakpoint at {addr:#06x}"))
+      }
+      Command::Delete(Some(addr)) => match vm.remove_breakpoint(addr) {
+        true => Output::line(format!("Deleted breakpoint at {addr:#06x}")),
+        false => bail!("No breakpoint at {addr:#06x}"),
+      },
+      Command::Delete(None) => {
+        vm.breakpoints.clear();
+        Output::line("Deleted all breakpoints")
+      }
+      Command::Watch(range) => {
+        let line = format!("Watching {:#06x}..{:#06x}", range.start, range.end);
+        vm.add_watch(range);
+        Output::line(line)
+      }
+      Command::Unwatch(range) => match vm.remove_watch(&range) {
+        true => Output::line(format!(
+          "Unwatched {:#06x}..{:#06x}",
+          range.start, range.end
+        )),
+        false => bail!("Not watching {:#06x}..{:#06x}", range.start, range.end),
+      },
+      Command::SetReg(reg, val) => {
+        vm.write_reg(reg, val);
+        Output::line(format!("{} = {:#x}", reg.name(), vm.read_reg(reg)))
+      }
+      Command::SetMem(addr, val) => {
+        let byte = vm
+          .mem
+          .get_mut(addr as usize)
+          .ok_or_else(|| eyre!("Bad address {addr:#06x}"))?;
+
+        *byte = val;
+        Output::line(format!("[{addr:#06x}] = {val:#04x}"))
+      }
+      Command::Examine(addr, len) => Output {
+        lines: examine(vm, addr, len),
+        paused: None,
+      },
+      Command::Goto(addr) => {
+        vm.reg_pc = addr;
+        Output::line(format!("PC = {addr:#06x}"))
+      }
+      Command::Step(n) => {
+        let brk = vm.step_n(n)?;
+        let mut output = Output::line(format!("PC = {:#06x}", vm.reg_pc));
+
+        output.lines.extend(brk.map(|brk| brk.to_string()));
+        output.paused = Some(true);
+        output
+      }
+      Command::Until(until) => {
+        let brk = vm.run_until(until, UNTIL_BUDGET)?;
+        let mut output = Output::line(brk.to_string());
+
+        output.paused = Some(true);
+        output
+      }
+      Command::Continue => Output {
+        lines: vec!["Continuing".to_string()],
+        paused: Some(false),
+      },
+      Command::Pause => Output {
+        lines: vec!["Paused".to_string()],
+        paused: Some(true),
+      },
+      Command::Save(slot) => {
+        self.slots.insert(slot.clone(), vm.clone());
+        Output::line(format!("Saved `{slot}`"))
+      }
+      Command::Load(slot) => {
+        let state = self
+          .slots
+          .get(&slot)
+          .ok_or_else(|| eyre!("No saved slot `{slot}`"))?;
+
+        vm.restore(state);
+        Output::line(format!("Loaded `{slot}`"))
+      }
+      Command::Dump(path) => {
+        StateDump::full(vm)?.write(&path)?;
+        Output::line(format!("Wrote `{path}`"))
+      }
+      Command::Restore(path) => {
+        StateDump::from_file(&path)?.restore(vm)?;
+        Output {
+          lines: vec![format!("Restored `{path}`")],
+          paused: Some(true),
+        }
+      }
+      Command::Info => Output {
+        lines: info(self, vm),
+        paused: None,
+      },
+      Command::Script(path) => {
+        let output = script::run_file(vm, path)?;
+        let mut lines = output.lines;
+        lines.extend(output.brk.as_ref().map(|brk| brk.to_string()));
+        lines.extend(output.error.map(|err| format!("Error: {err}")));
+
+        Output {
+          lines,
+          paused: output.brk.map(|_| true),
+        }
+      }
+      Command::Help => Output {
+        lines: vec![format!("Commands: {}", COMMANDS.join(", "))],
+        paused: None,
+      },
+    })
+  }
+
+  /// Executes every line of a script file, ignoring blank lines and `#` comments.
+  pub fn run_script(&mut self, vm: &mut Vm, path: impl AsRef<Path>) -> Result<Output> {
+    let path = path.as_ref();
+    let script = fs::read_to_string(path)
+      .wrap_err_with(|| format!("Failed to read script `{}`", path.display()))?;
+
+    let mut output = Output::default();
+
+    for (i, line) in script.lines().enumerate() {
+      let line = line.split('#').next().unwrap_or_default().trim();
+      if line.is_empty() {
+        continue;
+      }
+
+      let out = self
+        .execute(vm, line)
+        .wrap_err_with(|| format!("{}:{}", path.display(), i + 1))?;
+
+      output.lines.extend(out.lines);
+      output.paused = out.paused.or(output.paused);
+    }
+
+    Ok(output)
+  }
+
+  /// Completes the last word of `input`, returns `None` if nothing matches.
+  ///
+  /// Completes to the longest common prefix when several candidates match.
+  pub fn complete(&self, vm: &Vm, input: &str) -> Option<String> {
+    let (head, word) = match input.rfind(' ') {
+      Some(i) => (&input[..=i], &input[i + 1..]),
+      None => ("", input),
+    };
+
+    let registers = VmReg::ALL.map(|reg| reg.name().to_ascii_lowercase());
+    let slots = self.slots.keys().map(String::as_str);
+    let candidates: Vec<&str> = match head.split_whitespace().collect::<Vec<_>>()[..] {
+      [] => COMMANDS.to_vec(),
+      ["set"] => registers
+        .iter()
+        .map(String::as_str)
+        .chain(["mem"])
+        .collect(),
+      ["watch"] | ["unwatch"] => vec!["mem"],
+      ["until"] => vm.symbols.names().chain(["ret"]).collect(),
+      ["load"] | ["save"] => slots.collect(),
+      ["break"] | ["delete"] | ["goto"] => vm.symbols.names().collect(),
+      [x] if x == "x" || x.starts_with("x/") => vm.symbols.names().collect(),
+      _ => vec![],
+    };
+
+    let matches = candidates
+      .into_iter()
+      .filter(|c| c.starts_with(word))
+      .collect::<Vec<_>>();
+
+    let first = matches.first()?;
+    let prefix = matches.iter().fold(first.len(), |len, c| {
+      let common = first
+        .chars()
+        .zip(c.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+      common.min(len)
+    });
+
+    let suffix = if matches.len() == 1 { " " } else { "" };
+    Some(format!("{head}{}{suffix}", &first[..prefix]))
+  }
+}
+
+/// Parses a value as decimal, hex prefixed with `0x` or `$`, a register name or a symbol
+/// name.
+pub fn parse_value(s: &str, vm: &Vm) -> Result<u16> {
+  let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix('$'));
+  let val = match hex {
+    Some(hex) => u16::from_str_radix(hex, 16).ok(),
+    None => s.parse().ok(),
+  };
+
+  val
+    .or_else(|| VmReg::parse(s).map(|reg| vm.read_reg(reg)))
+    .or_else(|| vm.symbols.addr(s))
+    .ok_or_else(|| eyre!("Bad value `{s}`"))
+}
+
+/// Parses `mem <beg>..<end>` or `mem <addr>` into a range of addresses.
+fn parse_mem_range(args: &[&str], vm: &Vm) -> Result<Range<u16>> {
+  let range = match args {
+    ["mem", range] => range,
+    _ => bail!("Expected `mem <beg>..<end>`"),
+  };
+
+  match range.split_once("..") {
+    Some((beg, end)) => Ok(parse_value(beg, vm)?..parse_value(end, vm)?),
+    None => {
+      let addr = parse_value(range, vm)?;
+      Ok(addr..addr.saturating_add(1))
+    }
+  }
+}
+
+/// Formats `len` bytes of memory at `addr` as rows of hex.
+fn examine(vm: &Vm, addr: u16, len: u16) -> Vec<String> {
+  let beg = (addr as usize).min(vm.mem.len());
+  let end = (beg + len as usize).min(vm.mem.len());
+
+  vm.mem[beg..end]
+    .chunks(8)
+    .enumerate()
+    .map(|(i, row)| {
+      let bytes = row.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>();
+      format!("{:#06x}: {}", beg + i * 8, bytes.join(" "))
+    })
+    .collect()
+}
+
+/// Lists breakpoints, watches and saved slots.
+fn info(console: &Console, vm: &Vm) -> Vec<String> {
+  let breakpoints = vm
+    .breakpoints
+    .iter()
+    .map(|addr| format!("{addr:#06x}"))
+    .collect::<Vec<_>>();
+
+  let watches = vm
+    .watches
+    .iter()
+    .map(|range| format!("{:#06x}..{:#06x}", range.start, range.end))
+    .collect::<Vec<_>>();
+
+  let slots = console.slots.keys().cloned().collect::<Vec<_>>();
+
+  vec![
+    format!("Breakpoints: {}", breakpoints.join(", ")),
+    format!("Watches: {}", watches.join(", ")),
+    format!("Slots: {}", slots.join(", ")),
+  ]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn complete_non_ascii() {
+    let mut vm = Vm::new();
+    vm.symbols.add_label("é1", 0x200);
+    vm.symbols.add_label("ê1", 0x202);
+    vm.symbols.add_label("ê2", 0x204);
+
+    let console = Console::new();
+    assert_eq!(console.complete(&vm, "break "), Some("break ".to_string()));
+    assert_eq!(
+      console.complete(&vm, "break ê"),
+      Some("break ê".to_string())
+    );
+    assert_eq!(
+      console.complete(&vm, "break é"),
+      Some("break é1 ".to_string())
+    );
+  }
+
+  #[test]
+  fn set_mem_range() {
+    let vm = Vm::new();
+    assert!(Command::parse("set mem 300 0xff", &vm).is_ok());
+    assert!(Command::parse("set mem 300 0x1ff", &vm).is_err());
+  }
+
+  #[test]
+  fn step_ticks_timers() {
+    let mut vm = Vm::new();
+    vm.load_program(&[0x12, 0x00]).unwrap();
+    vm.reg_dt = 10;
+    vm.tickrate = 8;
+
+    Console::new().execute(&mut vm, "step 80").unwrap();
+    assert_eq!(vm.reg_dt, 0);
+  }
+}
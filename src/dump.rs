@@ -0,0 +1,248 @@
+//! Virtual machine state dumps for scripts and crash reports.
+
+use crate::vm::{fault::Fault, flags::VmKey, quirks::Quirks, Vm, VRAM_HEIGHT};
+use eyre::{bail, eyre, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::{fs, ops::Range, path::Path};
+
+/// How framebuffer rows are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VramFormat {
+  /// Rows of 16 hex digits, the most significant bit is the leftmost pixel.
+  Hex,
+  /// Rows of 64 `0` and `1` characters.
+  Bits,
+}
+
+impl VramFormat {
+  /// Gets a format by name.
+  pub fn named(name: &str) -> Option<Self> {
+    match name {
+      "hex" => Some(Self::Hex),
+      "bits" => Some(Self::Bits),
+      _ => None,
+    }
+  }
+}
+
+/// A snapshot of the machine state serialised as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateDump {
+  /// Number of 60Hz frames since the program was loaded.
+  pub frames: u64,
+  pub pc: u16,
+  pub i: u16,
+  pub sp: u8,
+  pub dt: u8,
+  pub st: u8,
+  /// Registers `V0` to `VF`.
+  pub v: [u8; 16],
+  /// Return addresses on the stack, oldest first.
+  pub stack: Vec<u16>,
+  /// Keys held down.
+  pub keys: Vec<u8>,
+  /// Whether the machine is waiting for a key press.
+  pub waiting_key: bool,
+  pub memory: MemoryDump,
+  /// Framebuffer rows, top first.
+  pub vram: Vec<String>,
+  /// Behaviours the program ran with, kept by the machine when absent.
+  #[serde(default)]
+  pub quirks: Option<Quirks>,
+  /// Instructions per timer tick, kept by the machine when absent.
+  #[serde(default)]
+  pub tickrate: Option<u32>,
+  /// The fault the machine stopped with, for crash dumps.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub fault: Option<Fault>,
+}
+
+/// A range of memory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryDump {
+  /// Address of the first byte.
+  pub start: u16,
+  /// Bytes as hex digits.
+  pub bytes: String,
+}
+
+impl StateDump {
+  /// Captures the state of `vm`, including the memory in `mem`.
+  pub fn new(vm: &Vm, mem: Range<u16>, format: VramFormat) -> Result<Self> {
+    let bytes = vm
+      .mem
+      .get(mem.start as usize..mem.end as usize)
+      .ok_or_else(|| eyre!("Bad memory range `{:#x}..{:#x}`", mem.start, mem.end))?;
+
+    let vram = vm
+      .vram
+      .iter()
+      .map(|row| match format {
+        VramFormat::Hex => format!("{row:016x}"),
+        VramFormat::Bits => format!("{row:064b}"),
+      })
+      .collect();
+
+    let depth = (vm.reg_sp as usize).min(vm.stack.len());
+
+    Ok(Self {
+      frames: vm.frames,
+      pc: vm.reg_pc,
+      i: vm.reg_i,
+      sp: vm.reg_sp,
+      dt: vm.reg_dt,
+      st: vm.reg_st,
+      v: vm.reg8,
+      stack: vm.stack[..depth].to_vec(),
+      keys: vm.keys.to_vec(),
+      waiting_key: vm.is_waiting_key(),
+      memory: MemoryDump {
+        start: mem.start,
+        bytes: bytes.iter().map(|byte| format!("{byte:02x}")).collect(),
+      },
+      vram,
+      quirks: Some(vm.quirks),
+      tickrate: Some(vm.tickrate),
+      fault: vm.fault().cloned(),
+    })
+  }
+
+  /// Captures the whole state of `vm`, as written to crash dumps.
+  pub fn full(vm: &Vm) -> Result<Self> {
+    let mem = 0..vm.mem.len().min(u16::MAX as usize) as u16;
+    Self::new(vm, mem, VramFormat::Bits)
+  }
+
+  /// Reads a dump written by [StateDump::to_json].
+  pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+    let path = path.as_ref();
+    let json = fs::read_to_string(path)
+      .wrap_err_with(|| format!("Failed to read `{}`", path.display()))?;
+
+    serde_json::from_str(&json)
+      .wrap_err_with(|| format!("Bad state dump `{}`", path.display()))
+  }
+
+  /// Serialises the dump as pretty printed JSON.
+  pub fn to_json(&self) -> Result<String> {
+    Ok(serde_json::to_string_pretty(self)?)
+  }
+
+  /// Writes the dump to `path` as JSON.
+  pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    fs::write(path, self.to_json()? + "\n")
+      .wrap_err_with(|| format!("Failed to write `{}`", path.display()))
+  }
+
+  /// Loads the dump into `vm`, keeping memory outside the dumped range, breakpoints and
+  /// symbols.
+  ///
+  /// A machine waiting for a key press waits again when the key wait instruction at the
+  /// program counter runs.
+  pub fn restore(&self, vm: &mut Vm) -> Result<()> {
+    let bytes = (0..self.memory.bytes.len())
+      .step_by(2)
+      .map(|i| {
+        let hex = self.memory.bytes.get(i..i + 2).unwrap_or_default();
+        u8::from_str_radix(hex, 16).map_err(|_| eyre!("Bad memory byte `{hex}`"))
+      })
+      .collect::<Result<Vec<_>>>()?;
+
+    let start = self.memory.start as usize;
+    let mem = vm
+      .mem
+      .get_mut(start..start + bytes.len())
+      .ok_or_else(|| eyre!("Memory dump at `{start:#x}` doesn't fit in memory"))?;
+
+    if self.vram.len() != VRAM_HEIGHT {
+      bail!("Expected {VRAM_HEIGHT} vram rows, got {}", self.vram.len());
+    }
+
+    let mut vram = [0; VRAM_HEIGHT];
+    for (row, text) in vram.iter_mut().zip(&self.vram) {
+      let radix = match text.len() {
+        16 => 16,
+        64 => 2,
+        _ => bail!("Bad vram row `{text}`"),
+      };
+
+      *row =
+        u64::from_str_radix(text, radix).map_err(|_| eyre!("Bad vram row `{text}`"))?;
+    }
+
+    if self.stack.len() > vm.stack.len() {
+      bail!("Expected at most {} return addresses", vm.stack.len());
+    }
+
+    if self.sp as usize != self.stack.len() {
+      bail!(
+        "Expected `sp` {} to match {} return addresses",
+        self.sp,
+        self.stack.len()
+      );
+    }
+
+    if self.tickrate == Some(0) {
+      bail!("Expected a tick rate above 0");
+    }
+
+    mem.copy_from_slice(&bytes);
+    vm.vram = vram;
+    vm.frames = self.frames;
+    vm.reg_pc = self.pc;
+    vm.reg_i = self.i;
+    vm.reg_dt = self.dt;
+    vm.reg_st = self.st;
+    vm.reg8 = self.v;
+    vm.stack = [0; 16];
+    vm.stack[..self.stack.len()].copy_from_slice(&self.stack);
+    vm.reg_sp = self.sp;
+    vm.keys = self
+      .keys
+      .iter()
+      .fold(VmKey::empty(), |keys, &key| keys | VmKey::from(key));
+    vm.quirks = self.quirks.unwrap_or(vm.quirks);
+    vm.tickrate = self.tickrate.unwrap_or(vm.tickrate);
+    vm.cancel_wait();
+    vm.set_fault(self.fault.clone());
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn quirks_and_tickrate() {
+    let mut vm = Vm::new();
+    vm.quirks.vblank = true;
+    vm.quirks.shift = false;
+    vm.tickrate = 30;
+
+    let json = StateDump::full(&vm).unwrap().to_json().unwrap();
+    let dump: StateDump = serde_json::from_str(&json).unwrap();
+
+    let mut restored = Vm::new();
+    dump.restore(&mut restored).unwrap();
+    assert_eq!(restored.quirks, vm.quirks);
+    assert_eq!(restored.tickrate, 30);
+  }
+
+  #[test]
+  fn invalid() {
+    let mut vm = Vm::new();
+    let dump = StateDump::full(&vm).unwrap();
+
+    let bad_tickrate = StateDump {
+      tickrate: Some(0),
+      ..dump.clone()
+    };
+    assert!(bad_tickrate.restore(&mut vm).is_err());
+
+    let bad_sp = StateDump { sp: 1, ..dump };
+    assert!(bad_sp.restore(&mut vm).is_err());
+  }
+}
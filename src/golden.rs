@@ -0,0 +1,381 @@
+//! Golden-frame regression tests.
+//!
+//! A test directory holds programs, the frames they should end on, and optionally a
+//! `golden.json` of settings keyed by file name:
+//!
+//! ```json
+//! {
+//!   "flags.ch8": { "frames": 120, "platform": "originalChip8" },
+//!   "keypad.ch8": { "frames": 200, "keys": "60 5, 90, 120 a, 150" }
+//! }
+//! ```
+//!
+//! `frames` defaults to 600 and `keys` takes movie entries separated by commas. The other
+//! fields are those of the [ROM database](crate::rom::db), and take precedence over it.
+//!
+//! Every program in the directory is a test. The golden frame of `flags.ch8` is
+//! `flags.pbm`, a PBM image as written by `--screenshot`, or `flags.sha1`, the SHA-1 of
+//! vram rows as big-endian bytes, whichever exists.
+
+use crate::{
+  movie::{run_headless, Movie},
+  render::{image::ImageRenderer, Framebuffer, Renderer},
+  rom::{
+    db::{RomDb, RomEntry},
+    is_program, sha1, Rom,
+  },
+  vm::{Vm, VRAM_HEIGHT},
+};
+use eyre::{bail, eyre, Result, WrapErr};
+use serde::Deserialize;
+use std::{
+  collections::HashMap,
+  fmt::{self, Display},
+  fs,
+  path::{Path, PathBuf},
+};
+
+/// Name of the settings file in a test directory.
+pub const MANIFEST: &str = "golden.json";
+/// Number of frames a program runs for unless configured otherwise.
+const FRAMES: u32 = 600;
+/// Number of pixels in a vram row.
+const WIDTH: usize = u64::BITS as usize;
+
+/// Settings a program is tested with.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GoldenConfig {
+  /// Number of frames run before comparing.
+  pub frames: Option<u32>,
+  /// Movie entries separated by commas, played back as key input.
+  pub keys: Option<String>,
+  /// Title, platform, quirks and tick rate.
+  #[serde(flatten)]
+  pub rom: RomEntry,
+}
+
+/// The frame a program is expected to end on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Golden {
+  /// Vram rows read from a PBM image.
+  Image(Vec<u64>),
+  /// SHA-1 of vram rows.
+  Hash(String),
+}
+
+/// A program and the golden frame it is compared to.
+#[derive(Debug, Clone)]
+pub struct GoldenTest {
+  /// File name of the program.
+  pub name: String,
+  pub path: PathBuf,
+  pub config: GoldenConfig,
+}
+
+/// Result of a [GoldenTest].
+#[derive(Debug, Clone)]
+pub enum Outcome {
+  /// The frame matched.
+  Pass,
+  /// The frame differs from the golden frame.
+  Fail { expected: Golden, actual: Vec<u64> },
+  /// There is no golden frame to compare to.
+  Missing(Vec<u64>),
+  /// The golden frame was written.
+  Updated,
+  /// The program failed to load or run.
+  Error(String),
+}
+
+/// The [Outcome] of a [GoldenTest] by name.
+#[derive(Debug, Clone)]
+pub struct Report {
+  pub name: String,
+  pub outcome: Outcome,
+}
+
+/// Lists the tests in `dir`, sorted by name.
+pub fn discover(dir: impl AsRef<Path>) -> Result<Vec<GoldenTest>> {
+  let dir = dir.as_ref();
+  let manifest = dir.join(MANIFEST);
+  let mut configs: HashMap<String, GoldenConfig> = match manifest.exists() {
+    true => {
+      let json = fs::read_to_string(&manifest)
+        .wrap_err_with(|| format!("Failed to read `{}`", manifest.display()))?;
+      serde_json::from_str(&json)
+        .wrap_err_with(|| format!("Bad test settings `{}`", manifest.display()))?
+    }
+    false => HashMap::new(),
+  };
+
+  let read =
+    fs::read_dir(dir).wrap_err_with(|| format!("Failed to read `{}`", dir.display()))?;
+
+  let mut tests = Vec::new();
+  for entry in read {
+    let path = entry?.path();
+    if !path.is_file() || !is_program(&path) {
+      continue;
+    }
+
+    let name = path
+      .file_name()
+      .map(|name| name.to_string_lossy().into_owned())
+      .unwrap_or_default();
+
+    tests.push(GoldenTest {
+      config: configs.remove(&name).unwrap_or_default(),
+      name,
+      path,
+    });
+  }
+
+  if let Some(name) = configs.keys().next() {
+    bail!(
+      "No program `{name}` for settings in `{}`",
+      manifest.display()
+    );
+  }
+
+  tests.sort_by(|a, b| a.name.cmp(&b.name));
+  Ok(tests)
+}
+
+/// Runs the tests in `dir`, looking programs up in `db`. When `update` is set, golden
+/// frames are written instead of compared.
+pub fn run_dir(
+  dir: impl AsRef<Path>,
+  db: Option<&RomDb>,
+  update: bool,
+) -> Result<Vec<Report>> {
+  let reports = discover(dir)?
+    .into_iter()
+    .map(|test| Report {
+      outcome: test.check(db, update),
+      name: test.name,
+    })
+    .collect();
+
+  Ok(reports)
+}
+
+impl GoldenTest {
+  /// Runs the program and gets its last frame.
+  pub fn run(&self, db: Option<&RomDb>) -> Result<Vec<u64>> {
+    let mut rom = Rom::from_file(&self.path)?;
+    if let Some(db) = db {
+      rom.apply_db(db);
+    }
+
+    rom.platform = self.config.rom.platform().or(rom.platform);
+    rom.config = self.config.rom.config().or(rom.config);
+
+    let movie = match &self.config.keys {
+      Some(keys) => Some(Movie::from_keys(keys).wrap_err("Bad keys")?),
+      None => None,
+    };
+
+    let mut vm = Vm::new();
+    rom.load(&mut vm)?;
+    run_headless(
+      &mut vm,
+      self.config.frames.unwrap_or(FRAMES),
+      movie.as_ref(),
+    )?;
+
+    Ok(vm.vram.to_vec())
+  }
+
+  /// Gets the path of the golden image.
+  pub fn image_path(&self) -> PathBuf {
+    self.path.with_extension("pbm")
+  }
+
+  /// Gets the path of the golden hash.
+  pub fn hash_path(&self) -> PathBuf {
+    self.path.with_extension("sha1")
+  }
+
+  /// Reads the golden frame, if there is one.
+  pub fn golden(&self) -> Result<Option<Golden>> {
+    let image = self.image_path();
+    if image.exists() {
+      let text = fs::read_to_string(&image)
+        .wrap_err_with(|| format!("Failed to read `{}`", image.display()))?;
+      let rows = parse_pbm(&text)
+        .wrap_err_with(|| format!("Bad golden image `{}`", image.display()))?;
+      return Ok(Some(Golden::Image(rows)));
+    }
+
+    let hash = self.hash_path();
+    if hash.exists() {
+      let text = fs::read_to_string(&hash)
+        .wrap_err_with(|| format!("Failed to read `{}`", hash.display()))?;
+      return Ok(Some(Golden::Hash(text.trim().to_ascii_lowercase())));
+    }
+
+    Ok(None)
+  }
+
+  /// Writes `vram` as the golden frame, as a hash if the golden frame already is one
+  /// and as an image otherwise.
+  pub fn update(&self, vram: &[u64]) -> Result<()> {
+    let hash = self.hash_path();
+    match hash.exists() && !self.image_path().exists() {
+      true => fs::write(&hash, vram_sha1(vram) + "\n")
+        .wrap_err_with(|| format!("Failed to write `{}`", hash.display())),
+      false => ImageRenderer::new(self.image_path().to_string_lossy())
+        .render(&Framebuffer::new(vram)),
+    }
+  }
+
+  /// Runs the program and compares its last frame to the golden frame, or writes the
+  /// golden frame when `update` is set.
+  pub fn check(&self, db: Option<&RomDb>, update: bool) -> Outcome {
+    let result = self.run(db).and_then(|actual| {
+      let golden = self.golden()?;
+      if golden
+        .as_ref()
+        .is_some_and(|golden| golden.matches(&actual))
+      {
+        return Ok(Outcome::Pass);
+      }
+
+      if update {
+        self.update(&actual)?;
+        return Ok(Outcome::Updated);
+      }
+
+      Ok(match golden {
+        Some(expected) => Outcome::Fail { expected, actual },
+        None => Outcome::Missing(actual),
+      })
+    });
+
+    result.unwrap_or_else(|err| Outcome::Error(format!("{err:#}")))
+  }
+}
+
+impl Golden {
+  /// Returns `true` if `vram` is the golden frame.
+  pub fn matches(&self, vram: &[u64]) -> bool {
+    match self {
+      Self::Image(rows) => rows == vram,
+      Self::Hash(hash) => *hash == vram_sha1(vram),
+    }
+  }
+}
+
+impl Outcome {
+  /// Returns `true` unless the frame differs or couldn't be compared.
+  pub fn is_ok(&self) -> bool {
+    matches!(self, Self::Pass | Self::Updated)
+  }
+}
+
+impl Display for Report {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = &self.name;
+    match &self.outcome {
+      Outcome::Pass => write!(f, "ok      {name}"),
+      Outcome::Updated => write!(f, "updated {name}"),
+      Outcome::Error(err) => write!(f, "error   {name}: {err}"),
+      Outcome::Missing(actual) => write!(
+        f,
+        "missing {name}: got {}, run with `--update` to write it",
+        vram_sha1(actual)
+      ),
+      Outcome::Fail {
+        expected: Golden::Hash(hash),
+        actual,
+      } => write!(
+        f,
+        "FAIL    {name}: expected hash {hash}, got {}, no reference image is stored",
+        vram_sha1(actual)
+      ),
+      Outcome::Fail {
+        expected: Golden::Image(expected),
+        actual,
+      } => {
+        writeln!(
+          f,
+          "FAIL    {name}: `+` lit, `-` unlit unlike the golden frame"
+        )?;
+        write!(f, "{}", diff(expected, actual))
+      }
+    }
+  }
+}
+
+/// Gets the SHA-1 of vram rows as big-endian bytes.
+pub fn vram_sha1(vram: &[u64]) -> String {
+  let bytes = vram
+    .iter()
+    .flat_map(|row| row.to_be_bytes())
+    .collect::<Vec<_>>();
+
+  sha1(&bytes)
+}
+
+/// Draws `actual` as text, `#` for lit pixels and `.` for unlit ones, or `+` and `-`
+/// where they differ from `expected`.
+pub fn diff(expected: &[u64], actual: &[u64]) -> String {
+  let mut text = String::new();
+
+  for (&expected, &actual) in expected.iter().zip(actual) {
+    for x in (0..WIDTH).rev() {
+      let pixels = (expected >> x & 1, actual >> x & 1);
+      text.push(match pixels {
+        (1, 1) => '#',
+        (0, 0) => '.',
+        (0, _) => '+',
+        _ => '-',
+      });
+    }
+
+    text.push('\n');
+  }
+
+  text
+}
+
+/// Parses a plain PBM image of the screen into vram rows, lit pixels are black.
+pub fn parse_pbm(text: &str) -> Result<Vec<u64>> {
+  let mut words = text
+    .lines()
+    .map(|line| line.split('#').next().unwrap_or_default())
+    .flat_map(str::split_whitespace);
+
+  if words.next() != Some("P1") {
+    bail!("Expected a plain PBM image");
+  }
+
+  let mut size = || -> Result<usize> {
+    let word = words.next().ok_or_else(|| eyre!("Expected image size"))?;
+    word.parse().map_err(|_| eyre!("Bad image size `{word}`"))
+  };
+
+  let (width, height) = (size()?, size()?);
+  if (width, height) != (WIDTH, VRAM_HEIGHT) {
+    bail!("Expected a {WIDTH}x{VRAM_HEIGHT} image, got {width}x{height}");
+  }
+
+  let mut pixels = words.flat_map(str::chars);
+  let mut rows = vec![0; height];
+
+  for row in &mut rows {
+    for _ in 0..width {
+      let pixel = match pixels.next() {
+        Some('0') => 0,
+        Some('1') => 1,
+        Some(pixel) => bail!("Bad pixel `{pixel}`"),
+        None => bail!("Expected {} pixels", width * height),
+      };
+
+      *row = *row << 1 | pixel;
+    }
+  }
+
+  Ok(rows)
+}
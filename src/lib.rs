@@ -0,0 +1,15 @@
+//! CHIP-8 virtual machine, assembler, debugger and the frontends built on them.
+
+pub mod args;
+pub mod console;
+pub mod dump;
+pub mod golden;
+pub mod insn;
+pub mod movie;
+pub mod octo;
+pub mod render;
+pub mod rom;
+pub mod scheduler;
+pub mod script;
+pub mod ui;
+pub mod vm;
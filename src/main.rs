@@ -1,50 +1,309 @@
-use eyre::Result;
+use chip8::{
+  args::{Args, Command, CoverageFormat},
+  dump::StateDump,
+  golden,
+  movie::{run_headless, Movie, Recorder},
+  render::{ansi::AnsiRenderer, image::ImageRenderer, Framebuffer, Renderer},
+  rom::{db::RomDb, Rom},
+  script,
+  ui::{browser::BrowserView, filter::Display, Ui},
+  vm::{symbols::Symbols, Vm},
+};
+use eyre::{bail, eyre, Report, Result};
 use std::{
-  env, fs,
+  fs,
+  path::{Path, PathBuf},
+  thread,
   time::{Duration, Instant},
 };
-use ui::Ui;
-use vm::Vm;
-
-pub mod insn;
-pub mod ui;
-pub mod vm;
 
 fn main() -> Result<()> {
+  let args = Args::parse()?;
+  let is_tui = matches!(
+    args.command,
+    Command::Run {
+      headless: false,
+      ansi: false,
+      ..
+    }
+  );
+
+  let db = match &args.rom_db {
+    Some(path) => Some(RomDb::from_file(path)?),
+    None => RomDb::from_default_path()?,
+  };
+
+  // without a program, the interface starts in the browser.
+  let browse = match args.program.as_deref() {
+    None => Some(PathBuf::from(".")),
+    Some(path) if Path::new(path).is_dir() => Some(PathBuf::from(path)),
+    Some(_) => None,
+  };
+
+  let rom = match (&browse, &args.program) {
+    (None, Some(path)) => {
+      let mut rom = Rom::from_file(path)?;
+      if let Some(db) = &db {
+        rom.apply_db(db);
+      }
+
+      Some(rom)
+    }
+    _ if is_tui || matches!(args.command, Command::Test { .. }) => None,
+    _ => bail!("Expected program path"),
+  };
+
   let mut vm = Vm::new();
+  match &rom {
+    Some(rom) => rom.load(&mut vm)?,
+    None => vm.load_program(&[])?,
+  }
+
+  if let Some(symbols) = &args.symbols {
+    vm.symbols = Symbols::from_file(symbols)?;
+  }
+
+  if let Some(restore) = &args.restore {
+    StateDump::from_file(restore)?.restore(&mut vm)?;
+  }
+
+  let crash_dump = args.crash_dump.as_deref();
+
+  let movie = match (&args.movie, &args.keys) {
+    (Some(movie), _) => Some(Movie::from_file(movie)?),
+    (None, Some(keys)) => Some(Movie::from_keys(keys)?),
+    (None, None) => None,
+  };
+
+  match args.command {
+    Command::Run {
+      headless: false,
+      ansi: false,
+      ..
+    } => {}
+    Command::Run {
+      headless: false,
+      frames,
+      screenshot,
+      ..
+    } => {
+      let mut display = Display::default();
+      display.filter = args.filter;
+
+      run_ansi(&mut vm, &mut display, frames, movie.as_ref())
+        .map_err(|err| with_fault(&vm, crash_dump, err))?;
+
+      if let Some(screenshot) = screenshot {
+        ImageRenderer::new(screenshot).render(&display.framebuffer())?;
+      }
+
+      return Ok(());
+    }
+    Command::Run {
+      frames,
+      screenshot,
+      dump,
+      dump_mem,
+      vram_format,
+      ..
+    } => {
+      run_headless(&mut vm, frames.unwrap_or_default(), movie.as_ref())
+        .map_err(|err| with_fault(&vm, crash_dump, err))?;
+
+      if let Some(screenshot) = screenshot {
+        ImageRenderer::new(screenshot).render(&Framebuffer::new(&vm.vram))?;
+      }
+
+      let state = StateDump::new(&vm, dump_mem, vram_format)?.to_json()?;
+      match dump {
+        Some(dump) => fs::write(dump, state + "\n")?,
+        None => println!("{state}"),
+      }
+
+      return Ok(());
+    }
+    Command::Script { path } => {
+      let output = script::run_file(&mut vm, path)?;
+      for line in output.lines {
+        println!("{line}");
+      }
+
+      return match output.error {
+        Some(error) => Err(eyre!(error)),
+        None => Ok(()),
+      };
+    }
+    Command::Profile { frames, top } => {
+      run_headless(&mut vm, frames, movie.as_ref())
+        .map_err(|err| with_fault(&vm, crash_dump, err))?;
+      print!("{}", vm.profile_report(top));
+      return Ok(());
+    }
+    Command::Coverage {
+      frames,
+      format,
+      output,
+    } => {
+      run_headless(&mut vm, frames, movie.as_ref())
+        .map_err(|err| with_fault(&vm, crash_dump, err))?;
+
+      let report = match format {
+        CoverageFormat::Lcov => {
+          vm.coverage_lcov(args.program.as_deref().unwrap_or_default())
+        }
+        CoverageFormat::Asm => vm.coverage_report(),
+      };
+
+      match output {
+        Some(output) => fs::write(output, report)?,
+        None => print!("{report}"),
+      }
+
+      return Ok(());
+    }
+    Command::Test { update } => {
+      let dir = args.program.as_deref().unwrap_or(".");
+      let reports = golden::run_dir(dir, db.as_ref(), update)?;
+      for report in &reports {
+        println!("{report}");
+      }
+
+      let failed = reports
+        .iter()
+        .filter(|report| !report.outcome.is_ok())
+        .count();
+      return match failed {
+        0 => Ok(()),
+        _ => Err(eyre!("{failed} of {} tests failed", reports.len())),
+      };
+    }
+  }
+
+  let mut recorder = match &args.record {
+    Some(record) => Some(Recorder::create(record)?),
+    None => None,
+  };
+
   let mut ui = Ui::new()?;
+  ui.paused = args.is_paused || args.restore.is_some();
+  ui.theme = args.theme;
+  ui.rom_colors = !args.custom_theme;
+  ui.watch = args.watch;
+  ui.keep_debug = args.keep_debug;
+  ui.symbols_path = args.symbols.as_ref().map(PathBuf::from);
+  if let Some(crash_dump) = crash_dump {
+    ui.fault.dump_path = PathBuf::from(crash_dump);
+  }
 
-  let program = env::args().nth(1).expect("Expected program path");
-  let program = fs::read(program)?;
+  let program_dir = args
+    .program
+    .as_deref()
+    .and_then(|path| Path::new(path).parent())
+    .filter(|dir| !dir.as_os_str().is_empty())
+    .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
 
-  vm.load_program(&program)?;
+  ui.browser = BrowserView::new(program_dir, db.unwrap_or_default());
+  if let Some(dir) = browse {
+    ui.browse(dir);
+  }
+
+  if let (Some(rom), Some(path)) = (rom, &args.program) {
+    ui.set_program(PathBuf::from(path), rom);
+  }
 
-  ui.paused = env::args()
-    .nth(2)
-    .map(|arg| match arg.split_once("--is-paused=") {
-      Some((_, flag)) => flag.starts_with('t') || flag.starts_with('T'),
-      None => false,
-    })
-    .unwrap_or_default();
+  ui.display.filter = args.filter;
 
-  let rate_cpu = Duration::from_secs_f64(1. / 500.);
-  let rate_timers = Duration::from_secs_f64(1. / 60.);
-  let mut clock_cpu = Instant::now();
-  let mut clock_timers = Instant::now();
+  if let Some(script) = &args.script {
+    if let Some(paused) = ui.console.run_script(&mut vm, script) {
+      ui.paused = paused;
+    }
+  }
+
+  let mut on_frame = |vm: &mut Vm| -> Result<()> {
+    if let Some(movie) = &movie {
+      movie.apply(vm);
+    }
+
+    if let Some(recorder) = &mut recorder {
+      recorder.record(vm)?;
+    }
+
+    Ok(())
+  };
 
   loop {
-    if !ui.paused {
-      if clock_cpu.elapsed() >= rate_cpu {
-        clock_cpu = Instant::now();
-        vm.update()?;
-      }
+    let result = if let Some(step) = ui.step.take() {
+      let brk = ui.scheduler.step(&mut vm, step, &mut on_frame);
+      ui.scheduler.idle();
+      brk.map(|brk| ui.stepped(brk))
+    } else if ui.paused {
+      ui.scheduler.idle();
+      Ok(())
+    } else {
+      ui.scheduler.run(&mut vm, &mut on_frame)
+    };
 
-      if clock_timers.elapsed() >= rate_timers {
-        clock_timers = Instant::now();
-        vm.update_timers();
+    // faults halt the machine and are shown by the interface.
+    if let Err(err) = result {
+      if vm.fault().is_none() {
+        return Err(err);
       }
     }
 
     ui.update(&mut vm)?;
+
+    if ui.step.is_none() {
+      ui.scheduler.wait(&vm, ui.paused);
+    }
   }
 }
+
+/// Replaces `err` with a report of the machine state if `vm` faulted, writing a crash
+/// dump to `crash_dump` if given.
+fn with_fault(vm: &Vm, crash_dump: Option<&str>, err: Report) -> Report {
+  let Some(fault) = vm.fault() else {
+    return err;
+  };
+
+  let mut report = fault.report(vm);
+  if let Some(path) = crash_dump {
+    report.push(
+      match StateDump::full(vm).and_then(|dump| dump.write(path)) {
+        Ok(()) => format!("Wrote crash dump `{path}`"),
+        Err(err) => format!("{err:#}"),
+      },
+    );
+  }
+
+  eyre!(report.join("\n"))
+}
+
+/// Runs `frames` frames, or forever, in real time, printing each frame to stdout and
+/// playing back key input from `movie`.
+fn run_ansi(
+  vm: &mut Vm,
+  display: &mut Display,
+  frames: Option<u32>,
+  movie: Option<&Movie>,
+) -> Result<()> {
+  let rate = Duration::from_secs_f64(1. / 60.);
+  let mut renderer = AnsiRenderer::stdout();
+  let mut clock = Instant::now();
+  let mut frame = 0;
+
+  while frames.is_none_or(|frames| frame < frames) {
+    if let Some(movie) = movie {
+      movie.apply(vm);
+    }
+
+    vm.run_frame()?;
+    display.update(vm);
+    renderer.render(&display.framebuffer())?;
+
+    frame += 1;
+    clock += rate;
+    thread::sleep(clock.saturating_duration_since(Instant::now()));
+  }
+
+  Ok(())
+}
@@ -0,0 +1,144 @@
+//! Recorded key input.
+//!
+//! Movies are plain text with one entry per line, `#` starts a comment. Each entry is a
+//! frame number followed by the hex digits of the keys held from that frame on, an entry
+//! without keys releases every key:
+//!
+//! ```text
+//! 120 5     # hold 5
+//! 150 5 a   # hold 5 and a
+//! 180       # release everything
+//! ```
+//!
+//! Frames are counted in timer ticks since the program was loaded, see [Vm::frames].
+
+use crate::vm::{flags::VmKey, Vm};
+use eyre::{bail, Result, WrapErr};
+use std::{
+  fs::{self, File},
+  io::Write,
+  path::Path,
+};
+
+/// A sequence of key states by frame.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Movie {
+  /// Key states sorted by the frame they start at.
+  pub frames: Vec<(u64, VmKey)>,
+}
+
+impl Movie {
+  /// Reads a movie from a file.
+  pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+      .wrap_err_with(|| format!("Failed to read movie `{}`", path.display()))?;
+
+    Self::parse(&text).wrap_err_with(|| format!("Bad movie `{}`", path.display()))
+  }
+
+  /// Parses a movie from text.
+  pub fn parse(text: &str) -> Result<Self> {
+    let mut frames = Vec::<(u64, VmKey)>::new();
+
+    for (i, line) in text.lines().enumerate() {
+      let line = line.split('#').next().unwrap_or_default();
+      let mut words = line.split_whitespace();
+      let Some(frame) = words.next() else {
+        continue;
+      };
+
+      let Ok(frame) = frame.parse() else {
+        bail!("Bad frame on line {}: `{frame}`", i + 1);
+      };
+
+      if frames.last().is_some_and(|&(last, _)| last >= frame) {
+        bail!("Frame on line {} is not after the previous frame", i + 1);
+      }
+
+      let mut keys = VmKey::empty();
+      for word in words {
+        match u8::from_str_radix(word, 16) {
+          Ok(key) if key < 16 => keys.insert(key.into()),
+          _ => bail!("Bad key on line {}: `{word}`", i + 1),
+        }
+      }
+
+      frames.push((frame, keys));
+    }
+
+    Ok(Self { frames })
+  }
+
+  /// Parses movie entries separated by commas, as given to `--keys`.
+  pub fn from_keys(keys: &str) -> Result<Self> {
+    Self::parse(&keys.replace(',', "\n"))
+  }
+
+  /// Gets the keys held at `frame`, or `None` before the first entry.
+  pub fn keys_at(&self, frame: u64) -> Option<VmKey> {
+    let idx = self.frames.partition_point(|&(start, _)| start <= frame);
+    idx.checked_sub(1).map(|idx| self.frames[idx].1)
+  }
+
+  /// Signals the keys held at the current frame of `vm`.
+  pub fn apply(&self, vm: &mut Vm) {
+    if let Some(keys) = self.keys_at(vm.frames) {
+      vm.signal_keys(keys);
+    }
+  }
+}
+
+/// Runs `frames` frames without an interface, playing back key input from `movie`.
+pub fn run_headless(vm: &mut Vm, frames: u32, movie: Option<&Movie>) -> Result<()> {
+  for _ in 0..frames {
+    if let Some(movie) = movie {
+      movie.apply(vm);
+    }
+
+    vm.run_frame()?;
+  }
+
+  Ok(())
+}
+
+/// Writes key states to a movie file as they change.
+///
+/// Entries are flushed as they are written so that the movie survives the process
+/// exiting at any point.
+pub struct Recorder {
+  file: File,
+  keys: VmKey,
+}
+
+impl Recorder {
+  /// Creates a movie file at `path`, replacing any existing file.
+  pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+    let path = path.as_ref();
+    let file = File::create(path)
+      .wrap_err_with(|| format!("Failed to create movie `{}`", path.display()))?;
+
+    Ok(Self {
+      file,
+      keys: VmKey::empty(),
+    })
+  }
+
+  /// Writes an entry if the keys held by `vm` changed since the last entry.
+  pub fn record(&mut self, vm: &Vm) -> Result<()> {
+    if vm.keys == self.keys {
+      return Ok(());
+    }
+
+    self.keys = vm.keys;
+
+    let mut line = vm.frames.to_string();
+    for key in vm.keys.to_vec() {
+      line.push_str(&format!(" {key:x}"));
+    }
+
+    writeln!(self.file, "{line}")?;
+    self.file.flush()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,124 @@
+//! `:calc` expressions.
+//!
+//! Like Octo, expressions have no precedence and are evaluated right to left, so
+//! `2 * 3 + 1` is `8`. Parentheses group.
+
+use super::{error, parse_number, AsmResult, Assembler};
+use crate::vm::PROG_OFF;
+
+impl Assembler {
+  /// Parses and evaluates a `{ expression }`.
+  pub(super) fn calc(&mut self) -> AsmResult<f64> {
+    self.expect("{")?;
+    let value = self.calc_expr()?;
+    self.expect("}")?;
+    Ok(value)
+  }
+
+  fn calc_expr(&mut self) -> AsmResult<f64> {
+    let lhs = self.calc_term()?;
+
+    let Some(op) = self.tokens.front().filter(|token| !token.is_string) else {
+      return Ok(lhs);
+    };
+
+    if matches!(op.text.as_str(), "}" | ")") {
+      return Ok(lhs);
+    }
+
+    let op = self.next()?;
+    let rhs = self.calc_expr()?;
+
+    let value = match op.text.as_str() {
+      "+" => lhs + rhs,
+      "-" => lhs - rhs,
+      "*" => lhs * rhs,
+      "/" => lhs / rhs,
+      "%" => lhs % rhs,
+      "&" => (lhs as i64 & rhs as i64) as f64,
+      "|" => (lhs as i64 | rhs as i64) as f64,
+      "^" => (lhs as i64 ^ rhs as i64) as f64,
+      "<<" => (lhs as i64).wrapping_shl(rhs as u32) as f64,
+      ">>" => (lhs as i64).wrapping_shr(rhs as u32) as f64,
+      "pow" => lhs.powf(rhs),
+      "min" => lhs.min(rhs),
+      "max" => lhs.max(rhs),
+      "<" => (lhs < rhs) as u8 as f64,
+      "<=" => (lhs <= rhs) as u8 as f64,
+      "==" => (lhs == rhs) as u8 as f64,
+      "!=" => (lhs != rhs) as u8 as f64,
+      ">=" => (lhs >= rhs) as u8 as f64,
+      ">" => (lhs > rhs) as u8 as f64,
+      text => return Err(error(&op, &format!("Unknown operator `{text}`"))),
+    };
+
+    Ok(value)
+  }
+
+  fn calc_term(&mut self) -> AsmResult<f64> {
+    let token = self.next()?;
+    if token.is_string {
+      return Err(error(&token, "Expected a value, found a string"));
+    }
+
+    let value = match token.text.as_str() {
+      "(" => {
+        let value = self.calc_expr()?;
+        self.expect(")")?;
+        value
+      }
+      "-" => -self.calc_term()?,
+      "~" => !(self.calc_term()? as i64) as f64,
+      "!" => (self.calc_term()? == 0.) as u8 as f64,
+      "sin" => self.calc_term()?.sin(),
+      "cos" => self.calc_term()?.cos(),
+      "tan" => self.calc_term()?.tan(),
+      "exp" => self.calc_term()?.exp(),
+      "log" => self.calc_term()?.ln(),
+      "abs" => self.calc_term()?.abs(),
+      "sqrt" => self.calc_term()?.sqrt(),
+      "sign" => {
+        let value = self.calc_term()?;
+        if value == 0. {
+          0.
+        } else {
+          value.signum()
+        }
+      }
+      "ceil" => self.calc_term()?.ceil(),
+      "floor" => self.calc_term()?.floor(),
+      "@" => {
+        self.addr_used = true;
+        let addr = self.calc_term()?;
+        let byte = (addr as usize)
+          .checked_sub(PROG_OFF)
+          .and_then(|idx| self.rom.get(idx));
+        byte.copied().unwrap_or_default() as f64
+      }
+      "HERE" => {
+        self.addr_used = true;
+        self.here as f64
+      }
+      "PI" => std::f64::consts::PI,
+      "E" => std::f64::consts::E,
+      text => match self.consts.get(text) {
+        Some(&value) => value,
+        None => match self.labels.get(text) {
+          Some(&addr) => {
+            self.addr_used = true;
+            addr as f64
+          }
+          None => match parse_number(text) {
+            Some(value) => value as f64,
+            None => match text.parse::<f64>() {
+              Ok(value) => value,
+              Err(_) => return Err(error(&token, &format!("Undefined name `{text}`"))),
+            },
+          },
+        },
+      },
+    };
+
+    Ok(value)
+  }
+}
@@ -0,0 +1,1012 @@
+//! Octo assembler.
+//!
+//! Compiles [Octo](https://github.com/JohnEarnest/Octo) source to bytes loaded at
+//! `0x200`. Supported are labels, `:const`, `:alias`, `:org`, `:next`, `:unpack`,
+//! `:breakpoint`, `:macro`, `:calc`, `:byte`, `:pointer`, `:call` and `:assert`,
+//! `if ... then`, `if ... begin ... else ... end`, `loop ... while ... again` and every
+//! chip-8, SUPER-CHIP and XO-CHIP statement.
+//!
+//! Programs start at `: main`, a jump to it is placed at `0x200` unless it is the first
+//! label.
+
+use self::token::{tokenize, Pos, Token};
+use crate::vm::{symbols::Symbols, PROG_OFF};
+use std::{
+  collections::{HashMap, VecDeque},
+  fmt::Display,
+};
+
+mod calc;
+pub mod token;
+
+#[cfg(test)]
+mod tests;
+
+/// Largest address a program may occupy.
+const MEM_END: usize = 0x1000;
+/// Number of macro expansions after which a macro is assumed to call itself forever.
+const MAX_EXPANSIONS: usize = 100_000;
+
+/// An assembly error and where in the source it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+  pub pos: Pos,
+  pub message: String,
+}
+
+impl Display for AsmError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}: {}", self.pos, self.message)
+  }
+}
+
+impl std::error::Error for AsmError {}
+
+type AsmResult<T> = Result<T, AsmError>;
+
+/// A compiled program.
+#[derive(Debug, Clone, Default)]
+pub struct Assembly {
+  /// Program bytes loaded at `0x200`.
+  pub bytes: Vec<u8>,
+  /// Labels defined by the program.
+  pub symbols: Symbols,
+  /// Addresses of `:breakpoint` directives.
+  pub breakpoints: Vec<u16>,
+}
+
+/// Compiles Octo source.
+pub fn assemble(source: &str) -> AsmResult<Assembly> {
+  Assembler::new(source).run()
+}
+
+/// A comparison in an `if` or `while` condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cmp {
+  Eq,
+  Ne,
+  Lt,
+  Gt,
+  Le,
+  Ge,
+  Key,
+  NotKey,
+}
+
+impl Cmp {
+  fn not(self) -> Self {
+    match self {
+      Self::Eq => Self::Ne,
+      Self::Ne => Self::Eq,
+      Self::Lt => Self::Ge,
+      Self::Ge => Self::Lt,
+      Self::Gt => Self::Le,
+      Self::Le => Self::Gt,
+      Self::Key => Self::NotKey,
+      Self::NotKey => Self::Key,
+    }
+  }
+}
+
+/// Right hand side of a condition.
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+  Reg(u8),
+  Byte(u8),
+  None,
+}
+
+/// How an address is written once a forward reference is defined.
+#[derive(Debug, Clone, Copy)]
+enum FixupKind {
+  /// The low 12 bits of the instruction at the address.
+  Addr12,
+  /// The 16 bits at the address.
+  Addr16,
+  /// The bytes of the two `vx := nn` instructions at the address, `nibble` is the high
+  /// nibble of the first.
+  Unpack(u8),
+}
+
+#[derive(Debug, Clone)]
+struct Fixup {
+  name: Token,
+  addr: u16,
+  kind: FixupKind,
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+  args: Vec<String>,
+  body: Vec<Token>,
+}
+
+/// An open `loop`.
+struct Loop {
+  token: Token,
+  start: u16,
+  /// Addresses of `while` jumps out of the loop.
+  breaks: Vec<u16>,
+}
+
+/// An open `if ... begin`.
+struct Branch {
+  token: Token,
+  /// Address of the jump taken when the condition is false, or past `else`.
+  jump: u16,
+  has_else: bool,
+}
+
+struct Assembler {
+  tokens: VecDeque<Token>,
+  last: Pos,
+  rom: Vec<u8>,
+  here: u16,
+  has_main_jump: bool,
+  /// Whether an address was read, after which the jump to main is kept.
+  addr_used: bool,
+  labels: HashMap<String, u16>,
+  consts: HashMap<String, f64>,
+  aliases: HashMap<String, u8>,
+  macros: HashMap<String, Macro>,
+  expansions: usize,
+  fixups: Vec<Fixup>,
+  loops: Vec<Loop>,
+  branches: Vec<Branch>,
+  breakpoints: Vec<u16>,
+}
+
+impl Assembler {
+  fn new(source: &str) -> Self {
+    Self {
+      tokens: tokenize(source).into(),
+      last: Pos { line: 1, col: 1 },
+      rom: Vec::new(),
+      here: PROG_OFF as u16,
+      has_main_jump: false,
+      addr_used: false,
+      labels: HashMap::new(),
+      consts: HashMap::new(),
+      aliases: HashMap::new(),
+      macros: HashMap::new(),
+      expansions: 0,
+      fixups: Vec::new(),
+      loops: Vec::new(),
+      branches: Vec::new(),
+      breakpoints: Vec::new(),
+    }
+  }
+
+  fn run(mut self) -> AsmResult<Assembly> {
+    // reserves a jump to main, removed if main is the first label.
+    self.has_main_jump = true;
+    self.emit_insn(Pos::default(), 0x1000)?;
+
+    while !self.tokens.is_empty() {
+      self.statement()?;
+    }
+
+    if let Some(lp) = self.loops.first() {
+      return Err(error(&lp.token, "Expected `again` for `loop`"));
+    }
+
+    if let Some(branch) = self.branches.first() {
+      return Err(error(&branch.token, "Expected `end` for `begin`"));
+    }
+
+    if self.has_main_jump {
+      let main = *self.labels.get("main").ok_or_else(|| AsmError {
+        pos: Pos { line: 1, col: 1 },
+        message: "Expected a `: main` label".to_string(),
+      })?;
+
+      self.patch(PROG_OFF as u16, main, FixupKind::Addr12);
+    }
+
+    for fixup in std::mem::take(&mut self.fixups) {
+      match self.labels.get(&fixup.name.text) {
+        Some(&addr) => self.patch(fixup.addr, addr, fixup.kind),
+        None => {
+          let message = format!("Undefined name `{}`", fixup.name.text);
+          return Err(error(&fixup.name, &message));
+        }
+      }
+    }
+
+    let mut symbols = Symbols::new();
+    for (name, &addr) in &self.labels {
+      symbols.add_label(name, addr);
+    }
+
+    Ok(Assembly {
+      bytes: self.rom,
+      symbols,
+      breakpoints: self.breakpoints,
+    })
+  }
+
+  /// Compiles one statement.
+  fn statement(&mut self) -> AsmResult<()> {
+    if let Some(mac) = self.front_macro() {
+      let token = self.next()?;
+      return self.expand(&token, mac);
+    }
+
+    let token = self.next()?;
+
+    match token.text.as_str() {
+      ":" => {
+        let name = self.next()?;
+        self.define_label(&name, self.here)?;
+      }
+      ":const" => {
+        let name = self.next()?;
+        let value = self.next()?;
+        self.addr_used |= self.labels.contains_key(&value.text);
+        let value = self.value(&value)?;
+        self.define_const(&name, value as f64)?;
+      }
+      ":alias" => {
+        let name = self.next()?;
+        // an alias may be pointed at another register.
+        if !self.aliases.contains_key(&name.text) {
+          self.check_name(&name)?;
+        }
+
+        let reg = self.next()?;
+        let reg = self.reg(&reg)?;
+        self.aliases.insert(name.text, reg);
+      }
+      ":org" => {
+        let addr = self.next()?;
+        let value = self.value(&addr)?;
+        match usize::try_from(value) {
+          Ok(value) if (PROG_OFF..MEM_END).contains(&value) => self.here = value as u16,
+          _ => return Err(error(&addr, &format!("Bad address `{value:#x}`"))),
+        }
+      }
+      ":next" => {
+        let name = self.next()?;
+        self.define_label(&name, self.here + 1)?;
+      }
+      ":unpack" => {
+        let nibble = self.next()?;
+        let nibble = self.nibble(&nibble)?;
+        let name = self.next()?;
+        let addr = self.here;
+
+        self.emit_insn(token.pos, 0x6000)?;
+        self.emit_insn(token.pos, 0x6100)?;
+        self.reference(name, addr, FixupKind::Unpack(nibble))?;
+      }
+      ":breakpoint" => {
+        self.next()?;
+        self.addr_used = true;
+        self.breakpoints.push(self.here);
+      }
+      ":monitor" => {
+        self.next()?;
+        self.next()?;
+      }
+      ":macro" => self.define_macro()?,
+      ":calc" => {
+        let name = self.next()?;
+        let value = self.calc()?;
+        self.define_const(&name, value)?;
+      }
+      ":byte" => {
+        let value = self.expr_or_value()?;
+        self.emit_byte(&token, value)?;
+      }
+      ":pointer" => {
+        let value = self.expr_or_value()?;
+        let value = value as i64 as u16;
+        self.emit(token.pos, (value >> 8) as u8)?;
+        self.emit(token.pos, value as u8)?;
+      }
+      ":call" => {
+        let target = self.next()?;
+        self.emit_addr_insn(target, 0x2000)?;
+      }
+      ":assert" => {
+        let message = match self.tokens.front() {
+          Some(message) if message.is_string => self.next()?.text,
+          _ => "Assertion failed".to_string(),
+        };
+
+        if self.calc()? == 0. {
+          return Err(error(&token, &message));
+        }
+      }
+      "return" | ";" => self.emit_insn(token.pos, 0x00ee)?,
+      "clear" => self.emit_insn(token.pos, 0x00e0)?,
+      "hires" => self.emit_insn(token.pos, 0x00ff)?,
+      "lores" => self.emit_insn(token.pos, 0x00fe)?,
+      "exit" => self.emit_insn(token.pos, 0x00fd)?,
+      "scroll-left" => self.emit_insn(token.pos, 0x00fc)?,
+      "scroll-right" => self.emit_insn(token.pos, 0x00fb)?,
+      "scroll-down" | "scroll-up" => {
+        let n = self.next()?;
+        let n = self.nibble(&n)? as u16;
+        let op = if token.text == "scroll-down" {
+          0x00c0
+        } else {
+          0x00d0
+        };
+        self.emit_insn(token.pos, op | n)?;
+      }
+      "audio" => self.emit_insn(token.pos, 0xf002)?,
+      "plane" => {
+        let n = self.next()?;
+        let n = self.nibble(&n)? as u16;
+        self.emit_insn(token.pos, 0xf001 | n << 8)?;
+      }
+      "bcd" => self.reg_insn(&token, 0xf033)?,
+      "saveflags" => self.reg_insn(&token, 0xf075)?,
+      "loadflags" => self.reg_insn(&token, 0xf085)?,
+      "save" | "load" => {
+        let x = self.next()?;
+        let x = self.reg(&x)? as u16;
+
+        if self.peek_is("-") {
+          self.next()?;
+          let y = self.next()?;
+          let y = self.reg(&y)? as u16;
+          let op = if token.text == "save" { 0x5002 } else { 0x5003 };
+          self.emit_insn(token.pos, op | x << 8 | y << 4)?;
+        } else {
+          let op = if token.text == "save" { 0xf055 } else { 0xf065 };
+          self.emit_insn(token.pos, op | x << 8)?;
+        }
+      }
+      "sprite" => {
+        let x = self.next()?;
+        let x = self.reg(&x)? as u16;
+        let y = self.next()?;
+        let y = self.reg(&y)? as u16;
+        let n = self.next()?;
+        let n = self.nibble(&n)? as u16;
+        self.emit_insn(token.pos, 0xd000 | x << 8 | y << 4 | n)?;
+      }
+      "jump" => {
+        let target = self.next()?;
+        self.emit_addr_insn(target, 0x1000)?;
+      }
+      "jump0" => {
+        let target = self.next()?;
+        self.emit_addr_insn(target, 0xb000)?;
+      }
+      "native" => {
+        let target = self.next()?;
+        self.emit_addr_insn(target, 0x0000)?;
+      }
+      "delay" | "buzzer" | "pitch" => {
+        self.expect(":=")?;
+        let op = match token.text.as_str() {
+          "delay" => 0xf015,
+          "buzzer" => 0xf018,
+          _ => 0xf03a,
+        };
+        self.reg_insn(&token, op)?;
+      }
+      "i" => self.i_statement(&token)?,
+      "if" => self.if_statement(&token)?,
+      "else" => {
+        let jump = self.here;
+        self.emit_insn(token.pos, 0x1000)?;
+
+        let here = self.here;
+        let branch = match self.branches.last_mut() {
+          Some(branch) if !branch.has_else => branch,
+          _ => return Err(error(&token, "Expected `if ... begin` before `else`")),
+        };
+
+        let prev = std::mem::replace(&mut branch.jump, jump);
+        branch.has_else = true;
+        self.patch(prev, here, FixupKind::Addr12);
+      }
+      "end" => {
+        let branch = self
+          .branches
+          .pop()
+          .ok_or_else(|| error(&token, "Expected `if ... begin` before `end`"))?;
+        self.patch(branch.jump, self.here, FixupKind::Addr12);
+      }
+      "loop" => self.loops.push(Loop {
+        token,
+        start: self.here,
+        breaks: Vec::new(),
+      }),
+      "while" => {
+        if self.loops.is_empty() {
+          return Err(error(&token, "Expected `loop` before `while`"));
+        }
+
+        let (x, cmp, operand) = self.condition()?;
+        self.emit_condition(&token, x, cmp.not(), operand)?;
+
+        let jump = self.here;
+        self.emit_insn(token.pos, 0x1000)?;
+        if let Some(lp) = self.loops.last_mut() {
+          lp.breaks.push(jump);
+        }
+      }
+      "again" => {
+        let lp = self
+          .loops
+          .pop()
+          .ok_or_else(|| error(&token, "Expected `loop` before `again`"))?;
+
+        self.emit_insn(token.pos, 0x1000 | lp.start)?;
+        for jump in lp.breaks {
+          self.patch(jump, self.here, FixupKind::Addr12);
+        }
+      }
+      _ if self.try_reg(&token).is_some() => self.reg_statement(&token)?,
+      _ if self.labels.contains_key(&token.text) => self.emit_addr_insn(token, 0x2000)?,
+      _ => match self.try_value(&token) {
+        Some(value) => self.emit_byte(&token, value as f64)?,
+        None if is_name(&token.text) => self.emit_addr_insn(token, 0x2000)?,
+        None => {
+          return Err(error(&token, &format!("Unexpected `{}`", token.text)));
+        }
+      },
+    }
+
+    Ok(())
+  }
+
+  /// Compiles a statement starting with `i`.
+  fn i_statement(&mut self, token: &Token) -> AsmResult<()> {
+    let op = self.next()?;
+    match op.text.as_str() {
+      ":=" => {
+        let value = self.next()?;
+        match value.text.as_str() {
+          "hex" => self.reg_insn(token, 0xf029),
+          "bighex" => self.reg_insn(token, 0xf030),
+          "long" => {
+            let target = self.next()?;
+            let addr = self.here + 2;
+            self.emit_insn(token.pos, 0xf000)?;
+            self.emit_insn(token.pos, 0x0000)?;
+            self.reference(target, addr, FixupKind::Addr16)
+          }
+          _ => self.emit_addr_insn(value, 0xa000),
+        }
+      }
+      "+=" => self.reg_insn(token, 0xf01e),
+      _ => Err(error(
+        &op,
+        &format!("Expected `:=` or `+=`, found `{}`", op.text),
+      )),
+    }
+  }
+
+  /// Compiles a statement starting with a register.
+  fn reg_statement(&mut self, token: &Token) -> AsmResult<()> {
+    let x = self.reg(token)? as u16;
+    let op = self.next()?;
+    let rhs = self.next()?;
+
+    let reg_op = match op.text.as_str() {
+      ":=" => 0x8000,
+      "|=" => 0x8001,
+      "&=" => 0x8002,
+      "^=" => 0x8003,
+      "+=" => 0x8004,
+      "-=" => 0x8005,
+      ">>=" => 0x8006,
+      "=-" => 0x8007,
+      "<<=" => 0x800e,
+      _ => return Err(error(&op, &format!("Unknown operator `{}`", op.text))),
+    };
+
+    if let Some(y) = self.try_reg(&rhs) {
+      return self.emit_insn(token.pos, reg_op | x << 8 | (y as u16) << 4);
+    }
+
+    match (op.text.as_str(), rhs.text.as_str()) {
+      (":=", "key") => self.emit_insn(token.pos, 0xf00a | x << 8),
+      (":=", "delay") => self.emit_insn(token.pos, 0xf007 | x << 8),
+      (":=", "random") => {
+        let mask = self.next()?;
+        let mask = self.byte(&mask)? as u16;
+        self.emit_insn(token.pos, 0xc000 | x << 8 | mask)
+      }
+      (":=", _) => {
+        let nn = self.byte(&rhs)? as u16;
+        self.emit_insn(token.pos, 0x6000 | x << 8 | nn)
+      }
+      ("+=", _) => {
+        let nn = self.byte(&rhs)? as u16;
+        self.emit_insn(token.pos, 0x7000 | x << 8 | nn)
+      }
+      ("-=", _) => {
+        let nn = self.byte(&rhs)?.wrapping_neg() as u16;
+        self.emit_insn(token.pos, 0x7000 | x << 8 | nn)
+      }
+      _ => {
+        let message = format!("Expected a register after `{}`", op.text);
+        Err(error(&rhs, &message))
+      }
+    }
+  }
+
+  /// Compiles `if <condition> then <statement>` or `if <condition> begin`.
+  fn if_statement(&mut self, token: &Token) -> AsmResult<()> {
+    let (x, cmp, operand) = self.condition()?;
+    let keyword = self.next()?;
+
+    match keyword.text.as_str() {
+      "then" => {
+        self.emit_condition(token, x, cmp, operand)?;
+
+        while let Some(mac) = self.front_macro() {
+          let token = self.next()?;
+          self.expand(&token, mac)?;
+        }
+
+        let start = self.here;
+        self.statement()?;
+        if self.here != start + 2 {
+          return Err(error(token, "Expected a single instruction after `then`"));
+        }
+
+        Ok(())
+      }
+      "begin" => {
+        self.emit_condition(token, x, cmp.not(), operand)?;
+
+        let jump = self.here;
+        self.emit_insn(token.pos, 0x1000)?;
+        self.branches.push(Branch {
+          token: token.clone(),
+          jump,
+          has_else: false,
+        });
+
+        Ok(())
+      }
+      text => Err(error(
+        &keyword,
+        &format!("Expected `then` or `begin`, found `{text}`"),
+      )),
+    }
+  }
+
+  /// Parses a condition such as `v0 == 3`, `v1 != v2` or `v3 key`.
+  fn condition(&mut self) -> AsmResult<(u8, Cmp, Operand)> {
+    let x = self.next()?;
+    let x = self.reg(&x)?;
+    let op = self.next()?;
+
+    let cmp = match op.text.as_str() {
+      "==" => Cmp::Eq,
+      "!=" => Cmp::Ne,
+      "<" => Cmp::Lt,
+      ">" => Cmp::Gt,
+      "<=" => Cmp::Le,
+      ">=" => Cmp::Ge,
+      "key" => return Ok((x, Cmp::Key, Operand::None)),
+      "-key" => return Ok((x, Cmp::NotKey, Operand::None)),
+      _ => return Err(error(&op, &format!("Unknown comparison `{}`", op.text))),
+    };
+
+    let rhs = self.next()?;
+    let operand = match self.try_reg(&rhs) {
+      Some(y) => Operand::Reg(y),
+      None => Operand::Byte(self.byte(&rhs)?),
+    };
+
+    Ok((x, cmp, operand))
+  }
+
+  /// Emits instructions that skip the next instruction when `cmp` is false.
+  ///
+  /// Ordered comparisons are computed into `VF` by subtraction, whose flag is set when
+  /// the left hand side is at least the right hand side.
+  fn emit_condition(
+    &mut self,
+    token: &Token,
+    x: u8,
+    cmp: Cmp,
+    operand: Operand,
+  ) -> AsmResult<()> {
+    let pos = token.pos;
+    let x = x as u16;
+
+    match (cmp, operand) {
+      (Cmp::Key, _) => self.emit_insn(pos, 0xe0a1 | x << 8),
+      (Cmp::NotKey, _) => self.emit_insn(pos, 0xe09e | x << 8),
+      (Cmp::Eq, Operand::Reg(y)) => {
+        self.emit_insn(pos, 0x9000 | x << 8 | (y as u16) << 4)
+      }
+      (Cmp::Ne, Operand::Reg(y)) => {
+        self.emit_insn(pos, 0x5000 | x << 8 | (y as u16) << 4)
+      }
+      (Cmp::Eq, Operand::Byte(nn)) => self.emit_insn(pos, 0x4000 | x << 8 | nn as u16),
+      (Cmp::Ne, Operand::Byte(nn)) => self.emit_insn(pos, 0x3000 | x << 8 | nn as u16),
+      (Cmp::Lt | Cmp::Ge, operand) => {
+        // vf := rhs, vf =- x sets the flag when x >= rhs.
+        match operand {
+          Operand::Reg(y) => self.emit_insn(pos, 0x8f00 | (y as u16) << 4)?,
+          Operand::Byte(nn) => self.emit_insn(pos, 0x6f00 | nn as u16)?,
+          Operand::None => unreachable!(),
+        }
+
+        self.emit_insn(pos, 0x8f07 | x << 4)?;
+        self.emit_insn(pos, if cmp == Cmp::Lt { 0x3f01 } else { 0x3f00 })
+      }
+      (Cmp::Gt | Cmp::Le, operand) => {
+        // sets the flag when rhs >= x.
+        match operand {
+          Operand::Reg(y) => {
+            self.emit_insn(pos, 0x8f00 | x << 4)?;
+            self.emit_insn(pos, 0x8f07 | (y as u16) << 4)?;
+          }
+          Operand::Byte(nn) => {
+            self.emit_insn(pos, 0x6f00 | nn as u16)?;
+            self.emit_insn(pos, 0x8f05 | x << 4)?;
+          }
+          Operand::None => unreachable!(),
+        }
+
+        self.emit_insn(pos, if cmp == Cmp::Gt { 0x3f01 } else { 0x3f00 })
+      }
+      (_, Operand::None) => unreachable!(),
+    }
+  }
+
+  /// Parses `:macro name args... { body }`.
+  fn define_macro(&mut self) -> AsmResult<()> {
+    let name = self.next()?;
+    let mut args = Vec::new();
+
+    loop {
+      let token = self.next()?;
+      if token.text == "{" {
+        break;
+      }
+
+      args.push(token.text);
+    }
+
+    let mut body = Vec::new();
+    let mut depth = 0;
+
+    loop {
+      let token = self.next()?;
+      match token.text.as_str() {
+        "{" => depth += 1,
+        "}" if depth == 0 => break,
+        "}" => depth -= 1,
+        _ => {}
+      }
+
+      body.push(token);
+    }
+
+    self.macros.insert(name.text, Macro { args, body });
+    Ok(())
+  }
+
+  /// Replaces a macro invocation with its body.
+  fn expand(&mut self, token: &Token, mac: Macro) -> AsmResult<()> {
+    self.expansions += 1;
+    if self.expansions > MAX_EXPANSIONS {
+      return Err(error(token, "Too many macro expansions"));
+    }
+
+    let mut args = HashMap::new();
+    for arg in &mac.args {
+      args.insert(arg.clone(), self.next()?);
+    }
+
+    for body in mac.body.iter().rev() {
+      let token = match args.get(&body.text) {
+        Some(arg) if !body.is_string => arg.clone(),
+        _ => body.clone(),
+      };
+
+      self.tokens.push_front(token);
+    }
+
+    Ok(())
+  }
+
+  fn next(&mut self) -> AsmResult<Token> {
+    match self.tokens.pop_front() {
+      Some(token) => {
+        self.last = token.pos;
+        Ok(token)
+      }
+      None => Err(AsmError {
+        pos: self.last,
+        message: "Unexpected end of source".to_string(),
+      }),
+    }
+  }
+
+  /// Gets the macro the next token invokes.
+  fn front_macro(&self) -> Option<Macro> {
+    let token = self.tokens.front().filter(|token| !token.is_string)?;
+    self.macros.get(&token.text).cloned()
+  }
+
+  fn peek_is(&self, text: &str) -> bool {
+    self
+      .tokens
+      .front()
+      .is_some_and(|token| token.text == text && !token.is_string)
+  }
+
+  fn expect(&mut self, text: &str) -> AsmResult<Token> {
+    let token = self.next()?;
+    match token.text == text {
+      true => Ok(token),
+      false => Err(error(
+        &token,
+        &format!("Expected `{text}`, found `{}`", token.text),
+      )),
+    }
+  }
+
+  fn define_label(&mut self, name: &Token, addr: u16) -> AsmResult<()> {
+    self.check_name(name)?;
+
+    // main directly after the reserved jump removes the jump, unless an address was
+    // already read and would be left pointing past it.
+    let first = self.has_main_jump && self.here == PROG_OFF as u16 + 2;
+    if name.text == "main" && first && !self.addr_used {
+      self.has_main_jump = false;
+      self.rom.clear();
+      self.here = PROG_OFF as u16;
+
+      for addr in self.labels.values_mut() {
+        *addr = PROG_OFF as u16;
+      }
+
+      self.labels.insert(name.text.clone(), self.here);
+      return Ok(());
+    }
+
+    self.labels.insert(name.text.clone(), addr);
+    Ok(())
+  }
+
+  fn define_const(&mut self, name: &Token, value: f64) -> AsmResult<()> {
+    self.check_name(name)?;
+    self.consts.insert(name.text.clone(), value);
+    Ok(())
+  }
+
+  fn check_name(&self, name: &Token) -> AsmResult<()> {
+    if !is_name(&name.text) || self.try_reg(name).is_some() {
+      return Err(error(name, &format!("Bad name `{}`", name.text)));
+    }
+
+    if self.labels.contains_key(&name.text) || self.consts.contains_key(&name.text) {
+      return Err(error(name, &format!("`{}` is already defined", name.text)));
+    }
+
+    Ok(())
+  }
+
+  /// Gets the register a token names, `v0` to `vf` or an alias.
+  fn try_reg(&self, token: &Token) -> Option<u8> {
+    if token.is_string {
+      return None;
+    }
+
+    if let Some(&reg) = self.aliases.get(&token.text) {
+      return Some(reg);
+    }
+
+    let text = token.text.to_ascii_lowercase();
+    let digit = text.strip_prefix('v')?;
+    match digit.len() {
+      1 => u8::from_str_radix(digit, 16).ok(),
+      _ => None,
+    }
+  }
+
+  fn reg(&self, token: &Token) -> AsmResult<u8> {
+    self.try_reg(token).ok_or_else(|| {
+      error(
+        token,
+        &format!("Expected a register, found `{}`", token.text),
+      )
+    })
+  }
+
+  /// Gets the value of a number, constant or defined label.
+  fn try_value(&self, token: &Token) -> Option<i64> {
+    if token.is_string {
+      return None;
+    }
+
+    if let Some(value) = parse_number(&token.text) {
+      return Some(value);
+    }
+
+    if let Some(&value) = self.consts.get(&token.text) {
+      return Some(value.floor() as i64);
+    }
+
+    self.labels.get(&token.text).map(|&addr| addr as i64)
+  }
+
+  fn value(&self, token: &Token) -> AsmResult<i64> {
+    self
+      .try_value(token)
+      .ok_or_else(|| error(token, &format!("Expected a value, found `{}`", token.text)))
+  }
+
+  /// Gets a value that fits in a byte, negative values wrap.
+  fn byte(&self, token: &Token) -> AsmResult<u8> {
+    match self.value(token)? {
+      value @ -128..=255 => Ok(value as u8),
+      value => Err(error(
+        token,
+        &format!("Value `{value}` doesn't fit in a byte"),
+      )),
+    }
+  }
+
+  fn nibble(&self, token: &Token) -> AsmResult<u8> {
+    match self.value(token)? {
+      value @ 0..=15 => Ok(value as u8),
+      value => Err(error(
+        token,
+        &format!("Value `{value}` doesn't fit in a nibble"),
+      )),
+    }
+  }
+
+  /// Parses a value or a `{ expression }`.
+  fn expr_or_value(&mut self) -> AsmResult<f64> {
+    if self.peek_is("{") {
+      return self.calc();
+    }
+
+    let token = self.next()?;
+    Ok(self.value(&token)? as f64)
+  }
+
+  /// Emits an instruction with register `x` from the next token.
+  fn reg_insn(&mut self, token: &Token, op: u16) -> AsmResult<()> {
+    let x = self.next()?;
+    let x = self.reg(&x)? as u16;
+    self.emit_insn(token.pos, op | x << 8)
+  }
+
+  /// Emits an instruction with a 12-bit address, which may be a label defined later.
+  fn emit_addr_insn(&mut self, target: Token, op: u16) -> AsmResult<()> {
+    let addr = self.here;
+    self.emit_insn(target.pos, op)?;
+    self.reference(target, addr, FixupKind::Addr12)
+  }
+
+  /// Writes the address of `target` at `addr` now, or once it is defined.
+  fn reference(&mut self, target: Token, addr: u16, kind: FixupKind) -> AsmResult<()> {
+    match self.try_value(&target) {
+      Some(value @ 0..=0xffff) => {
+        if matches!(kind, FixupKind::Addr12 | FixupKind::Unpack(_)) && value > 0xfff {
+          let message = format!("Address `{value:#x}` doesn't fit in 12 bits");
+          return Err(error(&target, &message));
+        }
+
+        self.patch(addr, value as u16, kind);
+        Ok(())
+      }
+      Some(value) => Err(error(&target, &format!("Bad address `{value}`"))),
+      None if is_name(&target.text) => {
+        self.fixups.push(Fixup {
+          name: target,
+          addr,
+          kind,
+        });
+
+        Ok(())
+      }
+      None => Err(error(
+        &target,
+        &format!("Expected an address, found `{}`", target.text),
+      )),
+    }
+  }
+
+  fn patch(&mut self, addr: u16, target: u16, kind: FixupKind) {
+    let idx = addr as usize - PROG_OFF;
+    let [hi, lo] = target.to_be_bytes();
+
+    match kind {
+      FixupKind::Addr12 => {
+        self.rom[idx] = self.rom[idx] & 0xf0 | hi & 0x0f;
+        self.rom[idx + 1] = lo;
+      }
+      FixupKind::Addr16 => {
+        self.rom[idx] = hi;
+        self.rom[idx + 1] = lo;
+      }
+      FixupKind::Unpack(nibble) => {
+        self.rom[idx + 1] = nibble << 4 | hi & 0x0f;
+        self.rom[idx + 3] = lo;
+      }
+    }
+  }
+
+  fn emit_byte(&mut self, token: &Token, value: f64) -> AsmResult<()> {
+    match value.floor() as i64 {
+      value @ -128..=255 => self.emit(token.pos, value as u8),
+      value => Err(error(
+        token,
+        &format!("Value `{value}` doesn't fit in a byte"),
+      )),
+    }
+  }
+
+  fn emit_insn(&mut self, pos: Pos, insn: u16) -> AsmResult<()> {
+    let [hi, lo] = insn.to_be_bytes();
+    self.emit(pos, hi)?;
+    self.emit(pos, lo)
+  }
+
+  fn emit(&mut self, pos: Pos, byte: u8) -> AsmResult<()> {
+    let addr = self.here as usize;
+    if addr >= MEM_END {
+      return Err(AsmError {
+        pos,
+        message: "Program doesn't fit in memory".to_string(),
+      });
+    }
+
+    let idx = addr - PROG_OFF;
+    if self.rom.len() <= idx {
+      self.rom.resize(idx + 1, 0);
+    }
+
+    self.rom[idx] = byte;
+    self.here += 1;
+    Ok(())
+  }
+}
+
+fn error(token: &Token, message: &str) -> AsmError {
+  AsmError {
+    pos: token.pos,
+    message: message.to_string(),
+  }
+}
+
+/// Returns `true` if `text` can name a label, constant or macro.
+fn is_name(text: &str) -> bool {
+  text
+    .chars()
+    .next()
+    .is_some_and(|c| c.is_alphabetic() || c == '_')
+    && text
+      .chars()
+      .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Parses a decimal, `0x` hex or `0b` binary number, optionally negative.
+fn parse_number(text: &str) -> Option<i64> {
+  let (neg, digits) = match text.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, text),
+  };
+
+  let value = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+    i64::from_str_radix(hex, 16).ok()?
+  } else if let Some(bin) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+    i64::from_str_radix(bin, 2).ok()?
+  } else if digits.chars().all(|c| c.is_ascii_digit()) && !digits.is_empty() {
+    digits.parse().ok()?
+  } else {
+    return None;
+  };
+
+  Some(if neg { -value } else { value })
+}
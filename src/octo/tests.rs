@@ -0,0 +1,158 @@
+//! Bytes emitted for assembler statements.
+
+use super::assemble;
+
+/// Assembles `source` and returns its bytes.
+fn bytes(source: &str) -> Vec<u8> {
+  assemble(source).unwrap().bytes
+}
+
+/// Assembles `source` and returns the error message.
+fn error(source: &str) -> String {
+  assemble(source).unwrap_err().message
+}
+
+#[test]
+fn labels() {
+  let source = "
+    : main
+      forward
+      jump ahead
+    : back
+      return
+      back
+    : forward
+      jump back
+    : ahead
+      back
+  ";
+
+  assert_eq!(
+    bytes(source),
+    [0x22, 0x08, 0x12, 0x0a, 0x00, 0xee, 0x22, 0x04, 0x12, 0x04, 0x22, 0x04]
+  );
+}
+
+#[test]
+fn main_jump() {
+  let source = "
+    : helper
+      return
+    : main
+      helper
+  ";
+
+  assert_eq!(bytes(source), [0x12, 0x04, 0x00, 0xee, 0x22, 0x02]);
+}
+
+#[test]
+fn main_jump_kept() {
+  assert_eq!(
+    bytes(": main :calc start { main } i := start"),
+    [0xa2, 0x00]
+  );
+  assert_eq!(
+    bytes(": boot :calc start { boot } : main i := start"),
+    [0x12, 0x02, 0xa2, 0x02]
+  );
+  assert_eq!(
+    bytes(":calc start { HERE } : main i := start"),
+    [0x12, 0x02, 0xa2, 0x02]
+  );
+  assert_eq!(
+    bytes(": boot :const start boot : main i := start"),
+    [0x12, 0x02, 0xa2, 0x02]
+  );
+
+  let assembly = assemble(":breakpoint boot : main clear").unwrap();
+  assert_eq!(assembly.bytes, [0x12, 0x02, 0x00, 0xe0]);
+  assert_eq!(assembly.breakpoints, [0x202]);
+}
+
+#[test]
+fn const_and_alias() {
+  let source = "
+    :const speed 3
+    :alias x v4
+    : main
+      x := speed
+      x += 1
+      :alias x v5
+      x := 0x10
+  ";
+
+  assert_eq!(bytes(source), [0x64, 0x03, 0x74, 0x01, 0x65, 0x10]);
+}
+
+#[test]
+fn alias_names() {
+  assert_eq!(error(": main :alias 3x v0"), "Bad name `3x`");
+  assert_eq!(error(": main :alias v1 v0"), "Bad name `v1`");
+  assert_eq!(error(": main :alias main v0"), "`main` is already defined");
+}
+
+#[test]
+fn if_then() {
+  assert_eq!(
+    bytes(": main if v0 == 1 then v1 := 2"),
+    [0x40, 0x01, 0x61, 0x02]
+  );
+}
+
+#[test]
+fn if_begin_else_end() {
+  let source = "
+    : main
+      if v0 != v2 begin
+        v3 := 1
+      else
+        v3 := 2
+      end
+  ";
+
+  assert_eq!(
+    bytes(source),
+    [0x90, 0x20, 0x12, 0x08, 0x63, 0x01, 0x12, 0x0a, 0x63, 0x02]
+  );
+}
+
+#[test]
+fn loop_while_again() {
+  let source = "
+    : main
+      loop
+        v0 += 1
+      while v0 != 5
+      again
+  ";
+
+  assert_eq!(
+    bytes(source),
+    [0x70, 0x01, 0x40, 0x05, 0x12, 0x08, 0x12, 0x00]
+  );
+}
+
+#[test]
+fn calc() {
+  assert_eq!(
+    bytes(":calc half { 64 / 2 } : main v0 := half"),
+    [0x60, 0x20]
+  );
+}
+
+#[test]
+fn unpack() {
+  assert_eq!(
+    bytes(": main :unpack 0xa data : data 0xff"),
+    [0x60, 0xa2, 0x61, 0x04, 0xff]
+  );
+}
+
+#[test]
+fn errors() {
+  assert_eq!(error(": main jump nowhere"), "Undefined name `nowhere`");
+  assert_eq!(
+    error(": main sprite v0 q 3"),
+    "Expected a register, found `q`"
+  );
+}
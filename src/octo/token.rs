@@ -0,0 +1,93 @@
+//! Octo source tokens.
+
+use std::fmt::Display;
+
+/// A word of source and where it starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+  pub text: String,
+  /// Whether the token was written in double quotes.
+  pub is_string: bool,
+  pub pos: Pos,
+}
+
+/// A 1-based line and column in source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Pos {
+  pub line: usize,
+  pub col: usize,
+}
+
+impl Display for Pos {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "line {}, column {}", self.line, self.col)
+  }
+}
+
+/// Splits source into whitespace separated tokens, skipping `#` comments.
+///
+/// Braces and parentheses are always tokens of their own, and text in double quotes is a
+/// single token.
+pub fn tokenize(source: &str) -> Vec<Token> {
+  let mut tokens = Vec::new();
+
+  for (idx, line) in source.lines().enumerate() {
+    let mut chars = line.chars().enumerate().peekable();
+
+    while let Some(&(col, c)) = chars.peek() {
+      let pos = Pos {
+        line: idx + 1,
+        col: col + 1,
+      };
+
+      if c == '#' {
+        break;
+      }
+
+      if c.is_whitespace() {
+        chars.next();
+        continue;
+      }
+
+      if c == '"' {
+        chars.next();
+        let text = chars
+          .by_ref()
+          .map(|(_, c)| c)
+          .take_while(|&c| c != '"')
+          .collect();
+
+        tokens.push(Token {
+          text,
+          is_string: true,
+          pos,
+        });
+
+        continue;
+      }
+
+      let mut text = String::new();
+      while let Some(&(_, c)) = chars.peek() {
+        let is_bracket = matches!(c, '{' | '}' | '(' | ')');
+        if c.is_whitespace() || (is_bracket && !text.is_empty()) {
+          break;
+        }
+
+        text.push(c);
+        chars.next();
+
+        if is_bracket {
+          break;
+        }
+      }
+
+      tokens.push(Token {
+        text,
+        is_string: false,
+        pos,
+      });
+    }
+  }
+
+  tokens
+}
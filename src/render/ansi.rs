@@ -0,0 +1,78 @@
+//! Plain ANSI renderer.
+
+use super::{Framebuffer, Renderer, Resolution, Shade};
+use crossterm::terminal;
+use eyre::Result;
+use std::io::{self, Stdout, Write};
+
+/// Writes frames as text, moving the cursor home before each frame instead of using an
+/// alternate screen so output stays in the terminal scrollback once finished.
+pub struct AnsiRenderer<W: Write> {
+  out: W,
+  resolution: Option<Resolution>,
+  cleared: bool,
+}
+
+impl AnsiRenderer<Stdout> {
+  /// Creates an [AnsiRenderer] writing to stdout.
+  pub fn stdout() -> Self {
+    Self::new(io::stdout())
+  }
+}
+
+impl<W: Write> AnsiRenderer<W> {
+  /// Creates an [AnsiRenderer] writing to `out`.
+  pub fn new(out: W) -> Self {
+    Self {
+      out,
+      resolution: None,
+      cleared: false,
+    }
+  }
+
+  /// Sets the resolution, otherwise the highest resolution fitting the terminal is used.
+  pub fn resolution(mut self, resolution: Resolution) -> Self {
+    self.resolution = Some(resolution);
+    self
+  }
+
+  fn fit(&self, framebuffer: &Framebuffer) -> Resolution {
+    self
+      .resolution
+      .or_else(|| {
+        let (width, height) = terminal::size().ok()?;
+        Resolution::fit(framebuffer, width as usize, height as usize)
+      })
+      .unwrap_or(Resolution::Blocks)
+  }
+}
+
+impl<W: Write> Renderer for AnsiRenderer<W> {
+  fn render(&mut self, framebuffer: &Framebuffer) -> Result<()> {
+    let cells = framebuffer.cells(self.fit(framebuffer));
+    let mut frame = String::new();
+
+    if !self.cleared {
+      self.cleared = true;
+      frame.push_str("\x1b[2J");
+    }
+
+    frame.push_str("\x1b[H");
+
+    for row in cells.rows() {
+      for cell in row {
+        frame.push(match (cell.symbol, cell.bg) {
+          (' ', Shade::On) => '█',
+          (' ', Shade::Glow(_)) => '▒',
+          (symbol, _) => symbol,
+        });
+      }
+
+      frame.push_str("\x1b[K\r\n");
+    }
+
+    self.out.write_all(frame.as_bytes())?;
+    self.out.flush()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,72 @@
+//! Image exporter.
+
+use super::{Framebuffer, Renderer};
+use eyre::{Result, WrapErr};
+use std::fs;
+
+/// Writes frames to plain PBM images, or PGM images when pixels glow, lit pixels are
+/// black. Rows are wrapped so lines stay within the 70 characters Netpbm allows.
+pub struct ImageRenderer {
+  path: String,
+  scale: usize,
+  frame: u64,
+}
+
+impl ImageRenderer {
+  /// Creates an [ImageRenderer] writing to `path`, where `{}` is replaced by the number
+  /// of frames rendered so far.
+  pub fn new(path: impl Into<String>) -> Self {
+    Self {
+      path: path.into(),
+      scale: 1,
+      frame: 0,
+    }
+  }
+
+  /// Sets the number of image pixels per framebuffer pixel in each direction.
+  pub fn scale(mut self, scale: usize) -> Self {
+    self.scale = scale.max(1);
+    self
+  }
+
+  /// Encodes `framebuffer` as an image.
+  pub fn encode(&self, framebuffer: &Framebuffer) -> String {
+    let glow = framebuffer.glow.is_some();
+    let width = framebuffer.width() * self.scale;
+    let height = framebuffer.height() * self.scale;
+
+    let (mut image, per_line) = match glow {
+      true => (format!("P2\n{width} {height}\n255\n"), 16),
+      false => (format!("P1\n{width} {height}\n"), 32),
+    };
+
+    for y in 0..height {
+      let pixels = (0..width)
+        .map(|x| {
+          let level = framebuffer.level(x / self.scale, y / self.scale);
+          match glow {
+            true => (255. - level * 255.).round().to_string(),
+            false => (level as u8).to_string(),
+          }
+        })
+        .collect::<Vec<_>>();
+
+      for line in pixels.chunks(per_line) {
+        image.push_str(&line.join(" "));
+        image.push('\n');
+      }
+    }
+
+    image
+  }
+}
+
+impl Renderer for ImageRenderer {
+  fn render(&mut self, framebuffer: &Framebuffer) -> Result<()> {
+    let path = self.path.replace("{}", &self.frame.to_string());
+    self.frame += 1;
+
+    fs::write(&path, self.encode(framebuffer))
+      .wrap_err_with(|| format!("Failed to write `{path}`"))
+  }
+}
@@ -0,0 +1,330 @@
+//! Framebuffer rendering independent of any terminal library.
+//!
+//! A [Framebuffer] borrows vram rows and is turned into a grid of text [Cells] at one of
+//! three [Resolution]s, which [Renderer]s then display. The tui game widget, the plain
+//! [ansi::AnsiRenderer], the [image::ImageRenderer] and the [test::TestRenderer] all
+//! share this logic.
+
+use eyre::Result;
+
+pub mod ansi;
+pub mod image;
+pub mod test;
+
+#[cfg(test)]
+mod tests;
+
+/// Number of pixels in a vram row.
+pub const ROW_BITS: usize = u64::BITS as usize;
+
+/// Monochrome pixels stored as rows of bits, the most significant bit is the leftmost
+/// pixel, with an optional brightness for unlit pixels that are still glowing.
+#[derive(Debug, Clone, Copy)]
+pub struct Framebuffer<'a> {
+  rows: &'a [u64],
+  width: usize,
+  glow: Option<&'a [[f32; ROW_BITS]]>,
+}
+
+impl<'a> Framebuffer<'a> {
+  /// Creates a [Framebuffer] displaying every bit of `rows`.
+  pub fn new(rows: &'a [u64]) -> Self {
+    Self {
+      rows,
+      width: ROW_BITS,
+      glow: None,
+    }
+  }
+
+  /// Sets the number of pixels displayed from the most significant bit of each row.
+  pub fn with_width(mut self, width: usize) -> Self {
+    self.width = width.min(ROW_BITS);
+    self
+  }
+
+  /// Sets the brightness of unlit pixels that are still glowing, from 0 to 1.
+  pub fn with_glow(mut self, glow: &'a [[f32; ROW_BITS]]) -> Self {
+    self.glow = Some(glow);
+    self
+  }
+
+  /// Gets the width in pixels.
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  /// Gets the height in pixels.
+  pub fn height(&self) -> usize {
+    self.rows.len()
+  }
+
+  /// Gets the rows of pixels.
+  pub fn rows(&self) -> &'a [u64] {
+    self.rows
+  }
+
+  /// Returns `true` if the pixel at `x`, `y` is lit, pixels outside are unlit.
+  pub fn pixel(&self, x: usize, y: usize) -> bool {
+    x < self.width && self.row(y) >> (ROW_BITS - 1 - x) & 1 != 0
+  }
+
+  /// Gets the brightness of the pixel at `x`, `y`, 1 when lit.
+  pub fn level(&self, x: usize, y: usize) -> f32 {
+    match self.pixel(x, y) {
+      true => 1.,
+      false => self
+        .glow
+        .and_then(|glow| glow.get(y)?.get(x).copied())
+        .unwrap_or(0.),
+    }
+  }
+
+  /// Converts the framebuffer into cells at `resolution`.
+  pub fn cells(&self, resolution: Resolution) -> Cells {
+    let (scale_x, scale_y) = resolution.scale();
+    let width = self.width.div_ceil(scale_x);
+    let height = self.height().div_ceil(scale_y);
+    let mut cells = Vec::with_capacity(width * height);
+
+    for y in (0..self.height()).step_by(scale_y) {
+      for x in (0..self.width).step_by(scale_x) {
+        cells.push(match resolution {
+          Resolution::Blocks => self.block_cell(x, y),
+          Resolution::Quarters => self.glyph_cell(x, y, resolution, get_quarter_bits),
+          Resolution::Braille => self.glyph_cell(x, y, resolution, get_braille_bits),
+        });
+      }
+    }
+
+    Cells {
+      width,
+      height,
+      cells,
+    }
+  }
+
+  fn row(&self, y: usize) -> u64 {
+    self.rows.get(y).copied().unwrap_or(0)
+  }
+
+  fn glow_row(&self, y: usize) -> u64 {
+    let Some(row) = self.glow.and_then(|glow| glow.get(y)) else {
+      return 0;
+    };
+
+    row
+      .iter()
+      .enumerate()
+      .filter(|(_, &level)| level > 0.)
+      .fold(0, |bits, (x, _)| bits | 1 << (ROW_BITS - 1 - x))
+  }
+
+  fn block_cell(&self, x: usize, y: usize) -> Cell {
+    let bg = match self.level(x, y) {
+      level if level >= 1. => Shade::On,
+      level if level > 0. => Shade::Glow(level),
+      _ => Shade::Off,
+    };
+
+    Cell {
+      symbol: ' ',
+      fg: Shade::Off,
+      bg,
+    }
+  }
+
+  /// Gets the glyph of the lit pixels in the cell at `x`, `y`, or of the glowing pixels
+  /// in the colour of the brightest one when none are lit.
+  fn glyph_cell(
+    &self,
+    x: usize,
+    y: usize,
+    resolution: Resolution,
+    get_bits: fn([u64; 4], usize) -> u8,
+  ) -> Cell {
+    let (scale_x, scale_y) = resolution.scale();
+    let rows = [
+      self.row(y),
+      self.row(y + 1),
+      self.row(y + 2),
+      self.row(y + 3),
+    ];
+    let rows = rows.map(|row| row & self.mask());
+
+    let (bits, fg) = match get_bits(rows, x) {
+      0 if self.glow.is_some() => {
+        let rows = [0, 1, 2, 3].map(|dy| self.glow_row(y + dy) & self.mask());
+        let level = (0..scale_y)
+          .flat_map(|dy| (0..scale_x).map(move |dx| (x + dx, y + dy)))
+          .map(|(x, y)| self.level(x, y))
+          .fold(0f32, f32::max);
+
+        (get_bits(rows, x), Shade::Glow(level))
+      }
+      bits => (bits, Shade::On),
+    };
+
+    let symbol = match resolution {
+      Resolution::Quarters => get_quarter_for_bits(bits),
+      _ => get_braille_for_bits(bits),
+    };
+
+    Cell {
+      symbol,
+      fg,
+      bg: Shade::Off,
+    }
+  }
+
+  fn mask(&self) -> u64 {
+    match self.width {
+      ROW_BITS => u64::MAX,
+      width => !(u64::MAX >> width),
+    }
+  }
+}
+
+/// Number of pixels displayed by each text cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+  /// One pixel per cell, drawn with the cell background.
+  Blocks,
+  /// 2x2 pixels per cell, drawn with quarter block characters.
+  Quarters,
+  /// 2x4 pixels per cell, drawn with braille characters.
+  Braille,
+}
+
+impl Resolution {
+  /// Gets the number of pixels per cell horizontally and vertically.
+  pub fn scale(self) -> (usize, usize) {
+    match self {
+      Self::Blocks => (1, 1),
+      Self::Quarters => (2, 2),
+      Self::Braille => (2, 4),
+    }
+  }
+
+  /// Gets the highest resolution that fits `framebuffer` in `width` by `height` cells.
+  pub fn fit(framebuffer: &Framebuffer, width: usize, height: usize) -> Option<Self> {
+    [Self::Blocks, Self::Quarters, Self::Braille]
+      .into_iter()
+      .find(|resolution| {
+        let (scale_x, scale_y) = resolution.scale();
+        width >= framebuffer.width().div_ceil(scale_x)
+          && height >= framebuffer.height().div_ceil(scale_y)
+      })
+  }
+}
+
+/// How a part of a cell is coloured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shade {
+  /// The colour of unlit pixels.
+  Off,
+  /// The colour of lit pixels.
+  On,
+  /// Between the unlit and lit colours, from 0 to 1.
+  Glow(f32),
+}
+
+/// A text cell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+  pub symbol: char,
+  pub fg: Shade,
+  pub bg: Shade,
+}
+
+/// A grid of text cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cells {
+  pub width: usize,
+  pub height: usize,
+  cells: Vec<Cell>,
+}
+
+impl Cells {
+  /// Gets the cell at `x`, `y`.
+  pub fn get(&self, x: usize, y: usize) -> Option<&Cell> {
+    match x < self.width {
+      true => self.cells.get(y * self.width + x),
+      false => None,
+    }
+  }
+
+  /// Gets rows of cells, top first.
+  pub fn rows(&self) -> impl Iterator<Item = &[Cell]> {
+    self.cells.chunks(self.width.max(1))
+  }
+
+  /// Gets the position of the top left cell that centres the cells in `width` by
+  /// `height` cells.
+  pub fn centre(&self, width: usize, height: usize) -> (usize, usize) {
+    (
+      width.saturating_sub(self.width) / 2,
+      height.saturating_sub(self.height) / 2,
+    )
+  }
+}
+
+/// Displays framebuffers.
+pub trait Renderer {
+  /// Displays a frame.
+  fn render(&mut self, framebuffer: &Framebuffer) -> Result<()>;
+}
+
+/// Gets the 2x2 pixels at `x` of the first two `rows` where trailing bits correspond to
+/// tl, tr, bl, br.
+fn get_quarter_bits([n1, n2, ..]: [u64; 4], x: usize) -> u8 {
+  get_pair(n1, x) << 2 | get_pair(n2, x)
+}
+
+/// Gets the 2x4 pixels at `x` of `rows` ordered as braille dots.
+fn get_braille_bits([n1, n2, n3, n4]: [u64; 4], x: usize) -> u8 {
+  // Braille patterns are ordered as follows:
+  //  1 4
+  //  2 5
+  //  3 6
+  //  7 8
+  let [n1, n2, n3, n4] = [n1, n2, n3, n4].map(|row| get_pair(row, x));
+
+  // left pixels are dots 1, 2, 3 and 7, right pixels dots 4, 5, 6 and 8.
+  let left = (n1 >> 1) | (n2 >> 1) << 1 | (n3 >> 1) << 2 | (n4 >> 1) << 6;
+  let right = (n1 & 1) << 3 | (n2 & 1) << 4 | (n3 & 1) << 5 | (n4 & 1) << 7;
+
+  left | right
+}
+
+/// Gets the pixels at `x` and `x + 1` of `row` as the two trailing bits, left first.
+fn get_pair(row: u64, x: usize) -> u8 {
+  (row.checked_shl(x as u32).unwrap_or(0) >> 62) as u8
+}
+
+fn get_quarter_for_bits(bits: u8) -> char {
+  match bits & 0b1111 {
+    0b0001 => '▗',
+    0b0010 => '▖',
+    0b0011 => '▄',
+    0b0100 => '▝',
+    0b0101 => '▐',
+    0b0110 => '▞',
+    0b0111 => '▟',
+    0b1000 => '▘',
+    0b1001 => '▚',
+    0b1010 => '▌',
+    0b1011 => '▙',
+    0b1100 => '▀',
+    0b1101 => '▜',
+    0b1110 => '▛',
+    0b1111 => '█',
+    _ => ' ',
+  }
+}
+
+fn get_braille_for_bits(bits: u8) -> char {
+  match bits {
+    0 => ' ',
+    bits => char::from_u32(0x2800u32 | bits as u32).unwrap_or(' '),
+  }
+}
@@ -0,0 +1,69 @@
+//! Renderer that keeps frames in memory.
+
+use super::{Framebuffer, Renderer, Resolution, Shade};
+use eyre::{eyre, Result};
+
+/// Collects frames as rows of text so they can be compared.
+///
+/// Frames are drawn like the game widget, at the highest [Resolution] that fits the
+/// area and centred in it, with lit blocks as `#`, unlit ones as `.` and the rest of
+/// the area as spaces.
+#[derive(Debug, Clone)]
+pub struct TestRenderer {
+  width: usize,
+  height: usize,
+  /// Resolution of the last frame rendered.
+  pub resolution: Option<Resolution>,
+  /// Frames rendered so far, oldest first.
+  pub frames: Vec<Vec<String>>,
+}
+
+impl Default for TestRenderer {
+  fn default() -> Self {
+    Self::new(64, 32)
+  }
+}
+
+impl TestRenderer {
+  /// Creates a [TestRenderer] drawing to an area of `width` by `height` cells.
+  pub fn new(width: usize, height: usize) -> Self {
+    Self {
+      width,
+      height,
+      resolution: None,
+      frames: Vec::new(),
+    }
+  }
+
+  /// Gets the last frame rendered.
+  pub fn last(&self) -> Option<&[String]> {
+    self.frames.last().map(Vec::as_slice)
+  }
+}
+
+impl Renderer for TestRenderer {
+  fn render(&mut self, framebuffer: &Framebuffer) -> Result<()> {
+    let resolution = Resolution::fit(framebuffer, self.width, self.height)
+      .ok_or_else(|| eyre!("Too small for {}x{} cells", self.width, self.height))?;
+
+    let cells = framebuffer.cells(resolution);
+    let (mid_x, mid_y) = cells.centre(self.width, self.height);
+    let mut frame = vec![vec![' '; self.width]; self.height];
+
+    for (y, row) in cells.rows().enumerate() {
+      for (x, cell) in row.iter().enumerate() {
+        frame[mid_y + y][mid_x + x] = match (resolution, cell.bg == Shade::On) {
+          (Resolution::Blocks, true) => '#',
+          (Resolution::Blocks, false) => '.',
+          _ => cell.symbol,
+        };
+      }
+    }
+
+    self.resolution = Some(resolution);
+    self
+      .frames
+      .push(frame.into_iter().map(String::from_iter).collect());
+    Ok(())
+  }
+}
@@ -0,0 +1,152 @@
+//! Cells drawn at each resolution, see [TestRenderer], and images written by
+//! [ImageRenderer].
+
+use super::{
+  image::ImageRenderer, test::TestRenderer, Framebuffer, Renderer, Resolution,
+};
+
+/// Renders `rows` displayed `width` pixels wide to `cols` by `lines` cells.
+fn render(rows: &[u64], width: usize, cols: usize, lines: usize) -> TestRenderer {
+  let mut renderer = TestRenderer::new(cols, lines);
+  let framebuffer = Framebuffer::new(rows).with_width(width);
+  renderer.render(&framebuffer).unwrap();
+  renderer
+}
+
+/// Gets a row with the pixels at `xs` lit.
+fn row(xs: &[usize]) -> u64 {
+  xs.iter().fold(0, |row, x| row | 1 << (63 - x))
+}
+
+#[test]
+fn fit() {
+  let rows = [0; 32];
+  let fits = |cols, lines| {
+    let framebuffer = Framebuffer::new(&rows);
+    Resolution::fit(&framebuffer, cols, lines)
+  };
+
+  assert_eq!(fits(64, 32), Some(Resolution::Blocks));
+  assert_eq!(fits(63, 32), Some(Resolution::Quarters));
+  assert_eq!(fits(64, 31), Some(Resolution::Quarters));
+  assert_eq!(fits(32, 16), Some(Resolution::Quarters));
+  assert_eq!(fits(31, 16), None);
+  assert_eq!(fits(32, 15), Some(Resolution::Braille));
+  assert_eq!(fits(32, 8), Some(Resolution::Braille));
+  assert_eq!(fits(31, 8), None);
+  assert_eq!(fits(32, 7), None);
+
+  let mut renderer = TestRenderer::new(31, 8);
+  assert!(renderer.render(&Framebuffer::new(&rows)).is_err());
+  assert!(renderer.frames.is_empty());
+}
+
+#[test]
+fn resolutions() {
+  let mut rows = [0; 32];
+  rows[0] = row(&[0, 63]);
+  rows[31] = row(&[0, 63]);
+
+  let blocks = render(&rows, 64, 64, 32);
+  assert_eq!(blocks.resolution, Some(Resolution::Blocks));
+  let frame = blocks.last().unwrap();
+  assert_eq!(frame.len(), 32);
+  assert_eq!(frame[0], format!("#{}#", ".".repeat(62)));
+  assert_eq!(frame[1], ".".repeat(64));
+
+  let quarters = render(&rows, 64, 32, 16);
+  assert_eq!(quarters.resolution, Some(Resolution::Quarters));
+  let frame = quarters.last().unwrap();
+  assert_eq!(frame.len(), 16);
+  assert_eq!(frame[0], format!("▘{}▝", " ".repeat(30)));
+  assert_eq!(frame[15], format!("▖{}▗", " ".repeat(30)));
+
+  let braille = render(&rows, 64, 32, 8);
+  assert_eq!(braille.resolution, Some(Resolution::Braille));
+  let frame = braille.last().unwrap();
+  assert_eq!(frame.len(), 8);
+  assert_eq!(frame[0], format!("⠁{}⠈", " ".repeat(30)));
+  assert_eq!(frame[7], format!("⡀{}⢀", " ".repeat(30)));
+}
+
+#[test]
+fn centre() {
+  let rows = [row(&[0]), row(&[3])];
+  let renderer = render(&rows, 4, 8, 5);
+
+  assert_eq!(
+    renderer.last().unwrap(),
+    ["        ", "  #...  ", "  ...#  ", "        ", "        "]
+  );
+}
+
+#[test]
+fn quarter_glyphs() {
+  let glyphs = " ▗▖▄▝▐▞▟▘▚▌▙▀▜▛█".chars();
+
+  for (bits, glyph) in glyphs.enumerate() {
+    let pixel = |bit: usize, x: usize| match bits >> bit & 1 {
+      1 => vec![x],
+      _ => vec![],
+    };
+
+    // top left, top right, bottom left, bottom right are bits 3 to 0.
+    let top = [pixel(3, 0), pixel(2, 1)].concat();
+    let bottom = [pixel(1, 0), pixel(0, 1)].concat();
+    let renderer = render(&[row(&top), row(&bottom)], 2, 1, 1);
+
+    assert_eq!(renderer.resolution, Some(Resolution::Quarters));
+    assert_eq!(renderer.last().unwrap(), [glyph.to_string()], "{bits:04b}");
+  }
+}
+
+#[test]
+fn braille_glyphs() {
+  // dots 1 to 8 as x, y.
+  let dots = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (1, 0),
+    (1, 1),
+    (1, 2),
+    (0, 3),
+    (1, 3),
+  ];
+
+  for (dot, (x, y)) in dots.into_iter().enumerate() {
+    let mut rows = [0; 4];
+    rows[y] = row(&[x]);
+    let renderer = render(&rows, 2, 1, 1);
+
+    let glyph = char::from_u32(0x2800 | 1 << dot).unwrap();
+    assert_eq!(renderer.resolution, Some(Resolution::Braille));
+    assert_eq!(
+      renderer.last().unwrap(),
+      [glyph.to_string()],
+      "dot {}",
+      dot + 1
+    );
+  }
+}
+
+#[test]
+fn pbm_round_trip() {
+  let rows = (0..32)
+    .map(|y| 0x8000_0000_0000_0001 | y << 16)
+    .collect::<Vec<_>>();
+  let image = ImageRenderer::new("").encode(&Framebuffer::new(&rows));
+
+  assert!(image.lines().all(|line| line.len() <= 70));
+  assert_eq!(crate::golden::parse_pbm(&image).unwrap(), rows);
+}
+
+#[test]
+fn pgm_line_length() {
+  let rows = [u64::MAX; 32];
+  let glow = [[0.5; 64]; 32];
+  let framebuffer = Framebuffer::new(&rows).with_glow(&glow);
+  let image = ImageRenderer::new("").scale(2).encode(&framebuffer);
+
+  assert!(image.lines().all(|line| line.len() <= 70));
+}
@@ -0,0 +1,266 @@
+//! Local ROM metadata database.
+//!
+//! A JSON object keyed by the lowercase SHA-1 of program bytes, in the style of the
+//! community CHIP-8 database:
+//!
+//! ```json
+//! {
+//!   "0df2789f661358d8f7370e6cf93490c5bcd44b01": {
+//!     "title": "Tetris",
+//!     "authors": ["Fran Dachille"],
+//!     "platform": "originalChip8",
+//!     "quirks": { "vblank": false },
+//!     "tickrate": 15,
+//!     "keys": { "left": 5, "right": 6, "up": 4, "down": 7 },
+//!     "colors": { "pixels": ["#000000", "#ffffff"] }
+//!   }
+//! }
+//! ```
+//!
+//! `platform` selects a quirk profile and `quirks` overrides parts of it using the
+//! community names `shift`, `memoryLeaveIUnchanged`, `wrap`, `jump`, `vblank` and
+//! `logic`.
+
+use super::{KeyHints, Palette, Platform, RomConfig};
+use crate::{
+  ui::theme::parse_color,
+  vm::{flags::VmKey, quirks::Quirks},
+};
+use eyre::{Result, WrapErr};
+use serde::Deserialize;
+use std::{collections::HashMap, env, fs, num::NonZeroU32, path::PathBuf};
+
+/// Known programs keyed by SHA-1.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RomDb {
+  #[serde(flatten)]
+  entries: HashMap<String, RomEntry>,
+}
+
+/// What the database knows about a program.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RomEntry {
+  pub title: Option<String>,
+  pub authors: Vec<String>,
+  /// Community platform id such as `originalChip8`, `superchip` or `xochip`.
+  pub platform: Option<String>,
+  quirks: QuirkOverrides,
+  /// Instructions per frame, rejected when 0.
+  pub tickrate: Option<NonZeroU32>,
+  keys: HashMap<String, u8>,
+  colors: Colors,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct QuirkOverrides {
+  shift: Option<bool>,
+  memory_leave_i_unchanged: Option<bool>,
+  wrap: Option<bool>,
+  jump: Option<bool>,
+  vblank: Option<bool>,
+  logic: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct Colors {
+  /// Unlit then lit pixel colours.
+  pixels: Vec<String>,
+}
+
+impl RomDb {
+  /// Loads a database from `path`.
+  pub fn from_file(path: impl Into<PathBuf>) -> Result<Self> {
+    let path = path.into();
+    let json = fs::read_to_string(&path)
+      .wrap_err_with(|| format!("Failed to read `{}`", path.display()))?;
+
+    serde_json::from_str(&json)
+      .wrap_err_with(|| format!("Bad ROM database `{}`", path.display()))
+  }
+
+  /// Loads the database at `$XDG_CONFIG_HOME/chip8/roms.json`, or
+  /// `~/.config/chip8/roms.json`, if there is one.
+  pub fn from_default_path() -> Result<Option<Self>> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+      .map(PathBuf::from)
+      .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    match config.map(|config| config.join("chip8").join("roms.json")) {
+      Some(path) if path.exists() => Self::from_file(path).map(Some),
+      _ => Ok(None),
+    }
+  }
+
+  /// Gets the entry for a program by its SHA-1.
+  pub fn get(&self, sha1: &str) -> Option<&RomEntry> {
+    self.entries.get(&sha1.to_ascii_lowercase())
+  }
+}
+
+impl RomEntry {
+  /// Gets the machine the program was written for.
+  pub fn platform(&self) -> Option<Platform> {
+    match self.platform.as_deref()? {
+      "originalChip8" | "hybridVIP" | "modernChip8" => Some(Platform::Chip8),
+      "chip48" | "superchip1" | "superchip" => Some(Platform::SuperChip),
+      "xochip" => Some(Platform::XoChip),
+      _ => None,
+    }
+  }
+
+  /// Gets the settings the program should run with.
+  pub fn config(&self) -> RomConfig {
+    let overrides = &self.quirks;
+    let has_overrides = [
+      overrides.shift,
+      overrides.memory_leave_i_unchanged,
+      overrides.wrap,
+      overrides.jump,
+      overrides.vblank,
+      overrides.logic,
+    ]
+    .iter()
+    .any(Option::is_some);
+
+    let profile = self.platform.as_deref().and_then(platform_quirks);
+    let quirks = match (profile, has_overrides) {
+      (None, false) => None,
+      (profile, _) => {
+        let quirks = profile.unwrap_or_default();
+        Some(Quirks {
+          shift: overrides.shift.unwrap_or(quirks.shift),
+          load_store: overrides
+            .memory_leave_i_unchanged
+            .unwrap_or(quirks.load_store),
+          clip: overrides.wrap.map(|wrap| !wrap).unwrap_or(quirks.clip),
+          jump: overrides.jump.unwrap_or(quirks.jump),
+          vblank: overrides.vblank.unwrap_or(quirks.vblank),
+          logic: overrides.logic.unwrap_or(quirks.logic),
+          ..quirks
+        })
+      }
+    };
+
+    let key = |name: &str| self.keys.get(name).map(|&key| VmKey::from(key & 0xf));
+    let color = |idx: usize| self.colors.pixels.get(idx).and_then(|c| parse_color(c));
+
+    RomConfig {
+      quirks,
+      tickrate: self.tickrate.map(NonZeroU32::get),
+      palette: Palette {
+        fg: color(1),
+        bg: color(0),
+      },
+      keys: KeyHints {
+        up: key("up"),
+        down: key("down"),
+        left: key("left"),
+        right: key("right"),
+        a: key("a"),
+        b: key("b"),
+      },
+    }
+  }
+}
+
+/// Gets the quirk profile of a community platform id.
+fn platform_quirks(platform: &str) -> Option<Quirks> {
+  let chip8 = Quirks {
+    shift: false,
+    load_store: false,
+    vf_order: false,
+    logic: true,
+    clip: true,
+    jump: false,
+    vblank: true,
+  };
+
+  match platform {
+    "originalChip8" | "hybridVIP" => Some(chip8),
+    "modernChip8" => Some(Quirks {
+      logic: false,
+      vblank: false,
+      ..chip8
+    }),
+    // chip48 increments `I` by `X` rather than `X + 1`, which isn't modelled, so it is
+    // treated as incrementing past the last register.
+    "chip48" => Some(Quirks {
+      shift: true,
+      logic: false,
+      jump: true,
+      vblank: false,
+      ..chip8
+    }),
+    "superchip1" | "superchip" => Some(Quirks {
+      shift: true,
+      load_store: true,
+      logic: false,
+      jump: true,
+      vblank: false,
+      ..chip8
+    }),
+    "xochip" => Some(Quirks {
+      logic: false,
+      clip: false,
+      vblank: false,
+      ..chip8
+    }),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn platforms() {
+    let quirks = |platform| {
+      let q = platform_quirks(platform).unwrap();
+      [
+        q.shift,
+        q.load_store,
+        q.vf_order,
+        q.logic,
+        q.clip,
+        q.jump,
+        q.vblank,
+      ]
+    };
+
+    // shift, load_store, vf_order, logic, clip, jump, vblank
+    let chip8 = [false, false, false, true, true, false, true];
+    assert_eq!(quirks("originalChip8"), chip8);
+    assert_eq!(quirks("hybridVIP"), chip8);
+    assert_eq!(
+      quirks("modernChip8"),
+      [false, false, false, false, true, false, false]
+    );
+    assert_eq!(
+      quirks("chip48"),
+      [true, false, false, false, true, true, false]
+    );
+
+    let superchip = [true, true, false, false, true, true, false];
+    assert_eq!(quirks("superchip1"), superchip);
+    assert_eq!(quirks("superchip"), superchip);
+    assert_eq!(
+      quirks("xochip"),
+      [false, false, false, false, false, false, false]
+    );
+    assert_eq!(platform_quirks("megachip8"), None);
+  }
+
+  #[test]
+  fn zero_tickrate() {
+    let json = r#"{ "0123": { "tickrate": 0 } }"#;
+    assert!(serde_json::from_str::<RomDb>(json).is_err());
+
+    let json = r#"{ "0123": { "tickrate": 15 } }"#;
+    let db = serde_json::from_str::<RomDb>(json).unwrap();
+    assert_eq!(db.get("0123").unwrap().config().tickrate, Some(15));
+  }
+}
@@ -0,0 +1,279 @@
+//! Program loading from the containers chip-8 programs are distributed in.
+//!
+//! * Raw binaries, usually named `.ch8`, `.sc8` for SUPER-CHIP or `.xo8` for XO-CHIP.
+//! * Octo source, `.8o` files assembled with [crate::octo].
+//! * Octo cartridges, GIF images with Octo source and settings hidden in their pixels.
+//! * Hex dumps, `.hex` or `.txt` files of hex bytes such as `00e0 a22a` or `0x00, 0xe0`.
+//!   `#` and `;` start comments and lines may start with an address such as `0200:`.
+
+use self::db::RomDb;
+use crate::{
+  octo,
+  vm::{debug::CYCLES_PER_TICK, flags::VmKey, quirks::Quirks, symbols::Symbols, Vm},
+};
+use eyre::{eyre, Result, WrapErr};
+use std::{fs, path::Path};
+use tui::style::Color;
+
+pub mod db;
+pub mod octocart;
+pub mod watch;
+
+/// Extensions of files recognised as programs.
+pub const EXTENSIONS: [&str; 10] = [
+  "ch8", "c8", "sc8", "xo8", "8o", "gif", "hex", "txt", "rom", "bin",
+];
+
+/// How a program is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+  /// Program bytes.
+  Raw,
+  /// Octo source.
+  Octo,
+  /// An Octo cartridge GIF.
+  Octocart,
+  /// Program bytes written as hex text.
+  Hex,
+}
+
+/// The machine a program was written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+  Chip8,
+  SuperChip,
+  XoChip,
+}
+
+/// Colours a program asks to be displayed with.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Palette {
+  /// Colour of lit pixels.
+  pub fg: Option<Color>,
+  /// Colour of unlit pixels.
+  pub bg: Option<Color>,
+}
+
+/// Keys a program uses for directions and actions, which arrow keys, `z` and `x` are
+/// mapped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KeyHints {
+  pub up: Option<VmKey>,
+  pub down: Option<VmKey>,
+  pub left: Option<VmKey>,
+  pub right: Option<VmKey>,
+  pub a: Option<VmKey>,
+  pub b: Option<VmKey>,
+}
+
+/// Settings stored alongside a program.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RomConfig {
+  /// Interpreter behaviours the program expects.
+  pub quirks: Option<Quirks>,
+  /// Instructions executed per 60Hz frame.
+  pub tickrate: Option<u32>,
+  pub palette: Palette,
+  pub keys: KeyHints,
+}
+
+impl RomConfig {
+  /// Fills settings missing from this config with those from `other`.
+  pub fn or(self, other: RomConfig) -> Self {
+    Self {
+      quirks: self.quirks.or(other.quirks),
+      tickrate: self.tickrate.or(other.tickrate),
+      palette: Palette {
+        fg: self.palette.fg.or(other.palette.fg),
+        bg: self.palette.bg.or(other.palette.bg),
+      },
+      keys: KeyHints {
+        up: self.keys.up.or(other.keys.up),
+        down: self.keys.down.or(other.keys.down),
+        left: self.keys.left.or(other.keys.left),
+        right: self.keys.right.or(other.keys.right),
+        a: self.keys.a.or(other.keys.a),
+        b: self.keys.b.or(other.keys.b),
+      },
+    }
+  }
+}
+
+/// A program and the settings stored with it.
+#[derive(Debug, Clone)]
+pub struct Rom {
+  pub container: Container,
+  pub platform: Option<Platform>,
+  /// Program bytes loaded at `0x200`.
+  pub program: Vec<u8>,
+  /// Octo source the program was assembled from.
+  pub source: Option<String>,
+  /// Labels defined by the source.
+  pub symbols: Symbols,
+  /// Breakpoints set by the source.
+  pub breakpoints: Vec<u16>,
+  pub config: RomConfig,
+  /// Title from the ROM database.
+  pub title: Option<String>,
+  /// Authors from the ROM database.
+  pub authors: Vec<String>,
+}
+
+impl Rom {
+  /// Loads a program from `path`, recognising its container from its contents or
+  /// extension.
+  pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+    let path = path.as_ref();
+    let bytes =
+      fs::read(path).wrap_err_with(|| format!("Failed to read `{}`", path.display()))?;
+
+    let ext = path
+      .extension()
+      .and_then(|ext| ext.to_str())
+      .map(str::to_ascii_lowercase)
+      .unwrap_or_default();
+
+    Self::parse(&bytes, &ext)
+      .wrap_err_with(|| format!("Failed to load `{}`", path.display()))
+  }
+
+  /// Parses a program from `bytes`, using the file extension `ext` for containers that
+  /// can't be recognised from their contents.
+  pub fn parse(bytes: &[u8], ext: &str) -> Result<Self> {
+    if octocart::is_octocart(bytes) {
+      return octocart::parse(bytes);
+    }
+
+    let platform = match ext {
+      "ch8" => Some(Platform::Chip8),
+      "sc8" => Some(Platform::SuperChip),
+      "xo8" => Some(Platform::XoChip),
+      _ => None,
+    };
+
+    if ext == "8o" {
+      let source = std::str::from_utf8(bytes).wrap_err("Expected Octo source")?;
+      return Self::from_source(source.to_string());
+    }
+
+    let (container, bytes) = match ext {
+      "hex" | "txt" => {
+        let text = std::str::from_utf8(bytes).wrap_err("Expected hex text")?;
+        (Container::Hex, parse_hex(text)?)
+      }
+      _ => (Container::Raw, bytes.to_vec()),
+    };
+
+    Ok(Self {
+      container,
+      platform,
+      program: bytes,
+      source: None,
+      symbols: Symbols::new(),
+      breakpoints: Vec::new(),
+      config: RomConfig::default(),
+      title: None,
+      authors: Vec::new(),
+    })
+  }
+
+  /// Assembles a program from Octo source.
+  pub fn from_source(source: String) -> Result<Self> {
+    let assembly = octo::assemble(&source)?;
+
+    Ok(Self {
+      container: Container::Octo,
+      platform: None,
+      program: assembly.bytes,
+      source: Some(source),
+      symbols: assembly.symbols,
+      breakpoints: assembly.breakpoints,
+      config: RomConfig::default(),
+      title: None,
+      authors: Vec::new(),
+    })
+  }
+
+  /// Gets the lowercase hex SHA-1 of the program bytes.
+  pub fn sha1(&self) -> String {
+    sha1(&self.program)
+  }
+
+  /// Fills in the title, authors, platform and settings `db` has for the program,
+  /// keeping settings stored with the program. Returns `false` if `db` doesn't know the
+  /// program.
+  pub fn apply_db(&mut self, db: &RomDb) -> bool {
+    let Some(entry) = db.get(&self.sha1()) else {
+      return false;
+    };
+
+    self.title = entry.title.clone();
+    self.authors = entry.authors.clone();
+    self.platform = entry.platform().or(self.platform);
+    self.config = std::mem::take(&mut self.config).or(entry.config());
+    true
+  }
+
+  /// Loads the program into `vm` with its quirks, tick rate, symbols and breakpoints,
+  /// replacing those of the previous program.
+  pub fn load(&self, vm: &mut Vm) -> Result<()> {
+    vm.load_program(&self.program)?;
+    vm.quirks = self.config.quirks.unwrap_or_default();
+    vm.tickrate = self.config.tickrate.unwrap_or(CYCLES_PER_TICK);
+    vm.symbols = self.symbols.clone();
+    vm.breakpoints = self.breakpoints.iter().copied().collect();
+    Ok(())
+  }
+
+  /// Describes the program as its title and authors, if known.
+  pub fn describe(&self) -> Option<String> {
+    let title = self.title.as_deref()?;
+    match self.authors.is_empty() {
+      true => Some(title.to_string()),
+      false => Some(format!("{title} by {}", self.authors.join(", "))),
+    }
+  }
+}
+
+/// Returns `true` if `path` has the extension of a program container.
+pub fn is_program(path: &Path) -> bool {
+  path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .is_some_and(|ext| EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Gets the lowercase hex SHA-1 of `bytes`.
+pub fn sha1(bytes: &[u8]) -> String {
+  sha1_smol::Sha1::from(bytes).digest().to_string()
+}
+
+/// Parses hex text into bytes.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>> {
+  let mut bytes = Vec::new();
+
+  for (idx, line) in text.lines().enumerate() {
+    let line = line.split(['#', ';']).next().unwrap_or_default();
+    let line = match line.split_once(':') {
+      Some((_, line)) => line,
+      None => line,
+    };
+
+    for word in line.split(|c: char| c.is_whitespace() || c == ',') {
+      let digits = word
+        .strip_prefix("0x")
+        .or_else(|| word.strip_prefix("0X"))
+        .unwrap_or(word);
+
+      if digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(eyre!("Bad hex `{word}` on line {}", idx + 1));
+      }
+
+      for i in (0..digits.len()).step_by(2) {
+        bytes.push(u8::from_str_radix(&digits[i..i + 2], 16)?);
+      }
+    }
+  }
+
+  Ok(bytes)
+}
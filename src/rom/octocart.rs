@@ -0,0 +1,115 @@
+//! Octo cartridges.
+//!
+//! An octocart is a GIF whose pixel colour indices carry a payload in their lowest two
+//! bits, most significant pair first across the pixels of every frame in order. The
+//! payload is a 4 byte big endian length followed by that many bytes of JSON holding the
+//! program source and the Octo options it was saved with.
+
+use super::{Container, Palette, Platform, Rom, RomConfig};
+use crate::{ui::theme::parse_color, vm::quirks::Quirks};
+use eyre::{eyre, Result, WrapErr};
+use serde::Deserialize;
+
+/// Octo options stored in a cartridge, unknown options are ignored.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Options {
+  tickrate: Option<u32>,
+  fill_color: Option<String>,
+  background_color: Option<String>,
+  shift_quirks: Option<bool>,
+  load_store_quirks: Option<bool>,
+  vf_order_quirks: Option<bool>,
+  logic_quirks: Option<bool>,
+  clip_quirks: Option<bool>,
+  jump_quirks: Option<bool>,
+  v_blank_quirks: Option<bool>,
+  max_size: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Payload {
+  program: String,
+  #[serde(default)]
+  options: Options,
+}
+
+/// Returns `true` if `bytes` starts with a GIF header.
+pub fn is_octocart(bytes: &[u8]) -> bool {
+  bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a")
+}
+
+/// Parses an octocart.
+pub fn parse(bytes: &[u8]) -> Result<Rom> {
+  let pixels = decode_pixels(bytes).wrap_err("Bad octocart image")?;
+  let mut payload = pixels
+    .chunks_exact(4)
+    .map(|pairs| pairs.iter().fold(0, |byte, pair| byte << 2 | pair & 0b11));
+
+  let len = payload
+    .by_ref()
+    .take(4)
+    .fold(0usize, |len, byte| len << 8 | byte as usize);
+
+  let json = payload.take(len).collect::<Vec<_>>();
+  if json.len() != len {
+    return Err(eyre!(
+      "Expected {len} bytes of octocart payload, found {}",
+      json.len()
+    ));
+  }
+
+  let payload: Payload =
+    serde_json::from_slice(&json).wrap_err("Bad octocart payload")?;
+  let options = payload.options;
+
+  let defaults = Quirks::default();
+  let quirks = Quirks {
+    shift: options.shift_quirks.unwrap_or(defaults.shift),
+    load_store: options.load_store_quirks.unwrap_or(defaults.load_store),
+    vf_order: options.vf_order_quirks.unwrap_or(defaults.vf_order),
+    logic: options.logic_quirks.unwrap_or(defaults.logic),
+    clip: options.clip_quirks.unwrap_or(defaults.clip),
+    jump: options.jump_quirks.unwrap_or(defaults.jump),
+    vblank: options.v_blank_quirks.unwrap_or(defaults.vblank),
+  };
+
+  let platform = match options.max_size {
+    Some(3216) => Some(Platform::Chip8),
+    Some(3583) => Some(Platform::SuperChip),
+    Some(65024) => Some(Platform::XoChip),
+    _ => None,
+  };
+
+  let rom = Rom::from_source(payload.program)?;
+  Ok(Rom {
+    container: Container::Octocart,
+    platform,
+    config: RomConfig {
+      quirks: Some(quirks),
+      // a tick rate of 0 never runs the program, so it falls back to the default.
+      tickrate: options.tickrate.filter(|&tickrate| tickrate > 0),
+      palette: Palette {
+        fg: options.fill_color.as_deref().and_then(parse_color),
+        bg: options.background_color.as_deref().and_then(parse_color),
+      },
+      ..RomConfig::default()
+    },
+    ..rom
+  })
+}
+
+/// Gets the colour indices of every frame, in order.
+fn decode_pixels(bytes: &[u8]) -> Result<Vec<u8>> {
+  let mut options = gif::DecodeOptions::new();
+  options.set_color_output(gif::ColorOutput::Indexed);
+
+  let mut decoder = options.read_info(bytes)?;
+  let mut pixels = Vec::new();
+
+  while let Some(frame) = decoder.read_next_frame()? {
+    pixels.extend_from_slice(&frame.buffer);
+  }
+
+  Ok(pixels)
+}
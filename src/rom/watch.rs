@@ -0,0 +1,59 @@
+//! Program file change detection.
+
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  time::{Duration, Instant, SystemTime},
+};
+
+/// Time between checks of the file.
+const POLL_RATE: Duration = Duration::from_millis(250);
+
+/// Polls a file for changes to its modification time or size.
+#[derive(Debug, Clone)]
+pub struct Watcher {
+  path: PathBuf,
+  stamp: Option<(SystemTime, u64)>,
+  checked: Instant,
+}
+
+impl Watcher {
+  /// Watches `path` for changes from its current contents.
+  pub fn new(path: impl Into<PathBuf>) -> Self {
+    let path = path.into();
+    Self {
+      stamp: stamp(&path),
+      path,
+      checked: Instant::now(),
+    }
+  }
+
+  /// Gets the watched path.
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Returns `true` if the file changed since the last call, checking at most every
+  /// 250ms. A file that disappears is only reported once it is written again.
+  pub fn changed(&mut self) -> bool {
+    if self.checked.elapsed() < POLL_RATE {
+      return false;
+    }
+
+    self.checked = Instant::now();
+
+    match stamp(&self.path) {
+      Some(stamp) if self.stamp != Some(stamp) => {
+        self.stamp = Some(stamp);
+        true
+      }
+      _ => false,
+    }
+  }
+}
+
+/// Gets the modification time and size of the file at `path`.
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+  let meta = fs::metadata(path).ok()?;
+  Some((meta.modified().ok()?, meta.len()))
+}
@@ -0,0 +1,257 @@
+//! Real-time execution pacing.
+//!
+//! Instructions run at [Vm::tickrate] per 60Hz frame with the timers ticking after every
+//! frame's worth of instructions, so slowing down scales the timers along with the
+//! program. A machine that is parked, see [Vm::activity], skips to the end of the frame
+//! instead of running instructions that change nothing.
+
+use crate::{
+  insn::Insn,
+  vm::{debug::Break, Vm},
+};
+use eyre::Result;
+use std::{
+  thread,
+  time::{Duration, Instant},
+};
+
+/// Instructions per frame stepped through when changing speed.
+pub const TICKRATES: [u32; 15] =
+  [1, 2, 3, 5, 7, 8, 10, 15, 20, 30, 50, 100, 200, 500, 1000];
+/// Slow motion factors stepped through when changing speed.
+pub const SLOWDOWNS: [u32; 6] = [1, 2, 4, 8, 16, 32];
+/// Frames per second at full speed.
+const FRAME_RATE: f64 = 60.;
+/// Longest time spent executing per call, keeps the interface responsive.
+const MAX_RUN: Duration = Duration::from_millis(16);
+/// Longest sleep between runs, so key presses are handled within a frame.
+const MAX_WAIT: Duration = Duration::from_millis(16);
+/// Most frames of instructions caught up after the interface stalls.
+const MAX_LAG: f64 = 4.;
+/// Most instructions executed by a [Step] before giving up.
+const STEP_BUDGET: u32 = 1_000_000;
+
+/// A way of stepping through a paused program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+  /// Executes one instruction.
+  Insn,
+  /// Runs until the timers next tick.
+  Frame,
+  /// Executes one instruction, running a subroutine it calls until it returns.
+  Over,
+  /// Runs until the current subroutine returns.
+  Out,
+  /// Runs until the program counter reaches an address.
+  To(u16),
+}
+
+/// Runs a machine in real time, slowed down or as fast as possible.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+  /// How many times slower than real time the machine runs.
+  pub slowdown: u32,
+  /// Whether the machine runs as fast as possible.
+  pub fast_forward: bool,
+  /// Instructions due but not executed yet.
+  due: f64,
+  /// Instructions executed since the timers last ticked.
+  cycle: u32,
+  last: Instant,
+}
+
+impl Default for Scheduler {
+  fn default() -> Self {
+    Self {
+      slowdown: 1,
+      fast_forward: false,
+      due: 0.,
+      cycle: 0,
+      last: Instant::now(),
+    }
+  }
+}
+
+impl Scheduler {
+  /// Create a [Scheduler].
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Executes the instructions due since the last call, ticking the timers every
+  /// [Vm::tickrate] instructions and calling `on_frame` after each tick.
+  ///
+  /// Stops early on breakpoints and watches, leaving the break for the caller.
+  pub fn run(
+    &mut self,
+    vm: &mut Vm,
+    mut on_frame: impl FnMut(&mut Vm) -> Result<()>,
+  ) -> Result<()> {
+    let start = Instant::now();
+    let tickrate = vm.tickrate.max(1);
+    let rate = self.rate(vm);
+
+    let elapsed = start.duration_since(self.last).as_secs_f64();
+    self.due = (self.due + elapsed * rate).min(MAX_LAG * tickrate as f64);
+    self.last = start;
+
+    while self.fast_forward || self.due >= 1. {
+      // run only the last instruction of the frame, once enough of it is due.
+      if vm.activity().is_parked() {
+        let rest = tickrate.saturating_sub(self.cycle).max(1);
+        if !self.fast_forward && self.due < rest as f64 {
+          break;
+        }
+
+        self.due -= (rest - 1) as f64;
+        self.cycle = tickrate - 1;
+      }
+
+      self.step_insn(vm, &mut on_frame)?;
+      self.due = (self.due - 1.).max(0.);
+
+      if vm.has_break() || start.elapsed() >= MAX_RUN {
+        break;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Executes a [Step], ticking the timers and calling `on_frame` as [Scheduler::run]
+  /// does.
+  ///
+  /// Stops early on breakpoints, watches and key waits, returns why a step that runs
+  /// more than one instruction stopped.
+  pub fn step(
+    &mut self,
+    vm: &mut Vm,
+    step: Step,
+    mut on_frame: impl FnMut(&mut Vm) -> Result<()>,
+  ) -> Result<Option<Break>> {
+    let pc = vm.reg_pc;
+    let depth = vm.reg_sp;
+
+    let brk = match step {
+      Step::Over if matches!(vm.insn_at_pc(), Some(Insn::Call(_))) => {
+        let ret = pc.wrapping_add(2);
+        self.run_until(vm, on_frame, |vm, _| vm.reg_pc == ret && vm.reg_sp <= depth)?
+      }
+      Step::Insn | Step::Over => {
+        self.step_insn(vm, &mut on_frame)?;
+        return Ok(None);
+      }
+      Step::Frame => self.run_until(vm, on_frame, |_, ticked| ticked)?,
+      Step::Out => self.run_until(vm, on_frame, |vm, _| vm.reg_sp < depth)?,
+      Step::To(addr) => self.run_until(vm, on_frame, |vm, _| vm.reg_pc == addr)?,
+    };
+
+    Ok(Some(brk))
+  }
+
+  /// Executes instructions until `done` returns `true` given the machine and whether
+  /// the timers just ticked.
+  fn run_until(
+    &mut self,
+    vm: &mut Vm,
+    mut on_frame: impl FnMut(&mut Vm) -> Result<()>,
+    done: impl Fn(&Vm, bool) -> bool,
+  ) -> Result<Break> {
+    for _ in 0..STEP_BUDGET {
+      if vm.is_waiting_key() {
+        return Ok(Break::WaitKey);
+      }
+
+      let ticked = self.step_insn(vm, &mut on_frame)?;
+      if let Some(brk) = vm.take_break() {
+        return Ok(brk);
+      }
+
+      if done(vm, ticked) {
+        return Ok(Break::Until(vm.reg_pc));
+      }
+    }
+
+    Ok(Break::Budget(STEP_BUDGET))
+  }
+
+  /// Executes one instruction, ticking the timers and calling `on_frame` if it ends a
+  /// frame. Returns `true` if the timers ticked.
+  fn step_insn(
+    &mut self,
+    vm: &mut Vm,
+    mut on_frame: impl FnMut(&mut Vm) -> Result<()>,
+  ) -> Result<bool> {
+    vm.update()?;
+    self.cycle += 1;
+
+    if self.cycle < vm.tickrate.max(1) {
+      return Ok(false);
+    }
+
+    self.cycle = 0;
+    vm.update_timers();
+    on_frame(vm)?;
+    Ok(true)
+  }
+
+  /// Forgets time passed while not running, call while paused.
+  pub fn idle(&mut self) {
+    self.due = 0.;
+    self.last = Instant::now();
+  }
+
+  /// Sleeps until [Scheduler::run] has instructions to execute, or the timers of a
+  /// parked machine tick, for at most a frame. Sleeps a frame when `paused`.
+  pub fn wait(&self, vm: &Vm, paused: bool) {
+    if paused {
+      thread::sleep(MAX_WAIT);
+      return;
+    }
+
+    if self.fast_forward {
+      return;
+    }
+
+    let tickrate = vm.tickrate.max(1);
+    let needed = match vm.activity().is_parked() {
+      true => tickrate.saturating_sub(self.cycle).max(1) as f64,
+      false => 1.,
+    };
+
+    let rate = self.rate(vm);
+    let due = self.due + self.last.elapsed().as_secs_f64() * rate;
+    let secs = (needed - due).max(0.) / rate;
+    thread::sleep(Duration::from_secs_f64(secs).min(MAX_WAIT));
+  }
+
+  /// Gets the instructions executed per second at the current speed.
+  pub fn rate(&self, vm: &Vm) -> f64 {
+    FRAME_RATE * vm.tickrate.max(1) as f64 / self.slowdown.max(1) as f64
+  }
+}
+
+/// Gets the next value of `steps` after `value`, or the previous if `up` is `false`.
+pub fn step(steps: &[u32], value: u32, up: bool) -> u32 {
+  let next = match up {
+    true => steps.iter().find(|&&step| step > value),
+    false => steps.iter().rev().find(|&&step| step < value),
+  };
+
+  next.copied().unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn zero_tickrate() {
+    let mut vm = Vm::new();
+    vm.tickrate = 0;
+
+    let scheduler = Scheduler::new();
+    assert_eq!(scheduler.rate(&vm), FRAME_RATE);
+    scheduler.wait(&vm, false);
+  }
+}
@@ -0,0 +1,324 @@
+//! Rhai scripts that drive the virtual machine.
+//!
+//! Scripts are run with `chip8 script test.rhai rom.ch8` or the console `script` command
+//! and have the following functions in scope, addresses also accept label names:
+//!
+//! * `pc()`, `i()`, `sp()`, `dt()`, `st()`, `v(x)` - Read registers.
+//! * `reg(name)`, `set_reg(name, val)` - Read or write a register by name, e.g. `"v3"`.
+//! * `peek(addr)`, `poke(addr, val)` - Read or write a byte of memory.
+//! * `key_down(key)`, `key_up(key)` - Signal a key press or release.
+//! * `press(key, frames)` - Hold a key for a number of frames.
+//! * `frame()`, `frames(n)` - Run frames, returning why execution stopped early or `""`.
+//! * `step()`, `step(n)` - Execute instructions, returning why execution stopped or `""`.
+//! * `frame_count()` - Frames run since the program was loaded.
+//! * `break_at(addr)`, `delete(addr)` - Add or remove a breakpoint.
+//! * `addr(label)` - Get the address of a label.
+//! * `pixel(x, y)` - Whether a pixel is lit.
+//! * `screen()` - Framebuffer rows as strings of `0` and `1`.
+//! * `screenshot(path)` - Write the framebuffer to a PBM image.
+//! * `assert(cond, message)` - Stop the script with an error when `cond` is false.
+
+use crate::{
+  render::{image::ImageRenderer, Framebuffer, Renderer},
+  vm::{debug::Break, flags::VmKey, reg::VmReg, Vm, VRAM_HEIGHT},
+};
+use eyre::{Result, WrapErr};
+use rhai::{Dynamic, Engine, EvalAltResult, ImmutableString, INT};
+use std::{cell::RefCell, fs, path::Path, rc::Rc};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Operations a script may run before it is stopped, which ends runaway loops.
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// Depth of nested function calls a script may reach.
+const MAX_CALL_LEVELS: usize = 64;
+
+/// Output of a script.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptOutput {
+  /// Lines printed by the script.
+  pub lines: Vec<String>,
+  /// Reason the last frame or step stopped early, if any.
+  pub brk: Option<Break>,
+  /// Error that stopped the script, if any.
+  pub error: Option<String>,
+}
+
+/// Runs the script at `path` against `vm`.
+pub fn run_file(vm: &mut Vm, path: impl AsRef<Path>) -> Result<ScriptOutput> {
+  let path = path.as_ref();
+  let source = fs::read_to_string(path)
+    .wrap_err_with(|| format!("Failed to read script `{}`", path.display()))?;
+
+  let mut output = run(vm, &source);
+  if let Some(error) = &mut output.error {
+    *error = format!("{}: {error}", path.display());
+  }
+
+  Ok(output)
+}
+
+/// Runs a script against `vm`, errors are returned in [ScriptOutput::error] along with
+/// anything printed before them.
+pub fn run(vm: &mut Vm, source: &str) -> ScriptOutput {
+  let shared = Rc::new(RefCell::new(std::mem::take(vm)));
+  let output = Rc::new(RefCell::new(ScriptOutput::default()));
+
+  let engine = engine(&shared, &output);
+  let result = engine.run(source);
+
+  drop(engine);
+  *vm = shared.replace(Vm::default());
+
+  let mut output = output.take();
+  output.error = result.err().map(|err| err.to_string());
+  output
+}
+
+/// Creates an engine with functions bound to `vm`.
+fn engine(vm: &Rc<RefCell<Vm>>, output: &Rc<RefCell<ScriptOutput>>) -> Engine {
+  let mut engine = Engine::new();
+  engine.set_max_operations(MAX_OPERATIONS);
+  engine.set_max_call_levels(MAX_CALL_LEVELS);
+
+  let out = output.clone();
+  engine.on_print(move |line| out.borrow_mut().lines.push(line.to_string()));
+
+  let out = output.clone();
+  engine.on_debug(move |line, _, pos| {
+    out.borrow_mut().lines.push(format!("{pos:?}: {line}"));
+  });
+
+  let reg = |engine: &mut Engine, name: &str, reg: VmReg| {
+    let vm = vm.clone();
+    engine.register_fn(name, move || vm.borrow().read_reg(reg) as INT);
+  };
+
+  reg(&mut engine, "pc", VmReg::Pc);
+  reg(&mut engine, "i", VmReg::I);
+  reg(&mut engine, "sp", VmReg::Sp);
+  reg(&mut engine, "dt", VmReg::Dt);
+  reg(&mut engine, "st", VmReg::St);
+
+  let v = vm.clone();
+  engine.register_fn("v", move |x: INT| -> ScriptResult<INT> {
+    match x {
+      0..=15 => Ok(v.borrow().reg8[x as usize] as INT),
+      _ => Err(format!("Bad register `V{x}`").into()),
+    }
+  });
+
+  let v = vm.clone();
+  engine.register_fn("reg", move |name: &str| -> ScriptResult<INT> {
+    Ok(v.borrow().read_reg(parse_reg(name)?) as INT)
+  });
+
+  let v = vm.clone();
+  engine.register_fn("set_reg", move |name: &str, val: INT| -> ScriptResult<()> {
+    v.borrow_mut().write_reg(parse_reg(name)?, val as u16);
+    Ok(())
+  });
+
+  let v = vm.clone();
+  engine.register_fn("peek", move |addr: Dynamic| -> ScriptResult<INT> {
+    let vm = v.borrow();
+    let addr = to_addr(&vm, addr)?;
+    Ok(vm.mem[addr as usize] as INT)
+  });
+
+  let v = vm.clone();
+  engine.register_fn("poke", move |addr: Dynamic, val: INT| -> ScriptResult<()> {
+    let mut vm = v.borrow_mut();
+    let addr = to_addr(&vm, addr)?;
+    vm.mem[addr as usize] = val as u8;
+    Ok(())
+  });
+
+  let v = vm.clone();
+  engine.register_fn("key_down", move |key: INT| -> ScriptResult<()> {
+    v.borrow_mut().signal_key_down(to_key(key)?);
+    Ok(())
+  });
+
+  let v = vm.clone();
+  engine.register_fn("key_up", move |key: INT| -> ScriptResult<()> {
+    v.borrow_mut().signal_key_up(to_key(key)?);
+    Ok(())
+  });
+
+  let (v, out) = (vm.clone(), output.clone());
+  engine.register_fn(
+    "press",
+    move |key: INT, frames: INT| -> ScriptResult<ImmutableString> {
+      let key = to_key(key)?;
+      v.borrow_mut().signal_key_down(key);
+      let brk = run_frames(&v, &out, frames);
+      v.borrow_mut().signal_key_up(key);
+      brk
+    },
+  );
+
+  let (v, out) = (vm.clone(), output.clone());
+  engine.register_fn("frame", move || run_frames(&v, &out, 1));
+
+  let (v, out) = (vm.clone(), output.clone());
+  engine.register_fn("frames", move |n: INT| run_frames(&v, &out, n));
+
+  let (v, out) = (vm.clone(), output.clone());
+  engine.register_fn("step", move || step(&v, &out, 1));
+
+  let (v, out) = (vm.clone(), output.clone());
+  engine.register_fn("step", move |n: INT| step(&v, &out, n));
+
+  let v = vm.clone();
+  engine.register_fn("frame_count", move || v.borrow().frames as INT);
+
+  let v = vm.clone();
+  engine.register_fn("break_at", move |addr: Dynamic| -> ScriptResult<bool> {
+    let mut vm = v.borrow_mut();
+    let addr = to_addr(&vm, addr)?;
+    Ok(vm.add_breakpoint(addr))
+  });
+
+  let v = vm.clone();
+  engine.register_fn("delete", move |addr: Dynamic| -> ScriptResult<bool> {
+    let mut vm = v.borrow_mut();
+    let addr = to_addr(&vm, addr)?;
+    Ok(vm.remove_breakpoint(addr))
+  });
+
+  let v = vm.clone();
+  engine.register_fn("addr", move |label: &str| -> ScriptResult<INT> {
+    let vm = v.borrow();
+    match vm.symbols.addr(label) {
+      Some(addr) => Ok(addr as INT),
+      None => Err(format!("Unknown label `{label}`").into()),
+    }
+  });
+
+  let v = vm.clone();
+  engine.register_fn("pixel", move |x: INT, y: INT| {
+    let vm = v.borrow();
+    let row = vm.vram[y.rem_euclid(VRAM_HEIGHT as INT) as usize];
+    row >> (63 - x.rem_euclid(64)) & 1 != 0
+  });
+
+  let v = vm.clone();
+  engine.register_fn("screen", move || {
+    let vm = v.borrow();
+    vm.vram
+      .iter()
+      .map(|row| Dynamic::from(format!("{row:064b}")))
+      .collect::<rhai::Array>()
+  });
+
+  let v = vm.clone();
+  engine.register_fn("screenshot", move |path: &str| -> ScriptResult<()> {
+    let vram = v.borrow().vram;
+    ImageRenderer::new(path)
+      .render(&Framebuffer::new(&vram))
+      .map_err(|err| err.to_string().into())
+  });
+
+  engine.register_fn("assert", |cond: bool, message: &str| -> ScriptResult<()> {
+    match cond {
+      true => Ok(()),
+      false => Err(format!("Assertion failed: {message}").into()),
+    }
+  });
+
+  engine
+}
+
+/// Runs up to `n` frames, stopping early on breakpoints and watches.
+fn run_frames(
+  vm: &Rc<RefCell<Vm>>,
+  output: &Rc<RefCell<ScriptOutput>>,
+  n: INT,
+) -> ScriptResult<ImmutableString> {
+  let mut vm = vm.borrow_mut();
+
+  for _ in 0..n {
+    let brk = vm.step_frame().map_err(|err| err.to_string())?;
+
+    if let Some(brk) = brk {
+      return Ok(stopped(output, brk));
+    }
+  }
+
+  output.borrow_mut().brk = None;
+  Ok(ImmutableString::new())
+}
+
+/// Executes up to `n` instructions, stopping early on breakpoints, watches and key waits.
+fn step(
+  vm: &Rc<RefCell<Vm>>,
+  output: &Rc<RefCell<ScriptOutput>>,
+  n: INT,
+) -> ScriptResult<ImmutableString> {
+  let brk = vm
+    .borrow_mut()
+    .step_n(n.max(0) as u32)
+    .map_err(|err| err.to_string())?;
+
+  match brk {
+    Some(brk) => Ok(stopped(output, brk)),
+    None => {
+      output.borrow_mut().brk = None;
+      Ok(ImmutableString::new())
+    }
+  }
+}
+
+fn stopped(output: &Rc<RefCell<ScriptOutput>>, brk: Break) -> ImmutableString {
+  let reason = brk.to_string();
+  output.borrow_mut().brk = Some(brk);
+  reason.into()
+}
+
+fn parse_reg(name: &str) -> ScriptResult<VmReg> {
+  VmReg::parse(name).ok_or_else(|| format!("Unknown register `{name}`").into())
+}
+
+fn to_key(key: INT) -> ScriptResult<VmKey> {
+  match key {
+    0..=15 => Ok(VmKey::from(key as u8)),
+    _ => Err(format!("Bad key `{key}`").into()),
+  }
+}
+
+/// Converts an integer or label name to an address in memory.
+fn to_addr(vm: &Vm, addr: Dynamic) -> ScriptResult<u16> {
+  let addr = match addr.as_int() {
+    Ok(addr) => addr,
+    Err(_) => {
+      let label = addr.into_string()?;
+      match vm.symbols.addr(&label) {
+        Some(addr) => addr as INT,
+        None => return Err(format!("Unknown label `{label}`").into()),
+      }
+    }
+  };
+
+  match usize::try_from(addr) {
+    Ok(addr) if addr < vm.mem.len() => Ok(addr as u16),
+    _ => Err(format!("Bad address `{addr:#x}`").into()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn limits() {
+    let mut vm = Vm::new();
+    let error = run(&mut vm, "loop {}").error.unwrap();
+    assert!(error.starts_with("Too many operations"), "{error}");
+
+    let error = run(&mut vm, "fn f() { f() } f()").error.unwrap();
+    assert!(error.starts_with("Stack overflow"), "{error}");
+
+    assert_eq!(run(&mut vm, "print(pc())").error, None);
+  }
+}
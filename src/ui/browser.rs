@@ -0,0 +1,306 @@
+//! ROM browser widget.
+
+use super::{game::Game, theme::Theme};
+use crate::{
+  rom::{db::RomDb, is_program, Rom},
+  vm::Vm,
+};
+use crossterm::event::KeyCode;
+use eyre::{Result, WrapErr};
+use std::{fs, path::PathBuf};
+use tui::{
+  backend::Backend,
+  layout::{Alignment, Constraint, Direction, Layout, Rect},
+  style::{Modifier, Style},
+  text::{Span, Spans},
+  widgets::{Block, Borders, Paragraph, Wrap},
+  Frame,
+};
+
+/// Number of frames a program runs for its preview.
+const PREVIEW_FRAMES: u32 = 120;
+/// Number of hex digits of the SHA-1 shown.
+const HASH_LEN: usize = 8;
+
+/// A directory or program in the browser.
+struct Entry {
+  name: String,
+  path: PathBuf,
+  is_dir: bool,
+  size: u64,
+  /// The parsed program, or why it couldn't be parsed.
+  rom: Option<Result<Rom, String>>,
+  /// The screen after [PREVIEW_FRAMES] frames, computed when first selected.
+  preview: Option<Result<Vec<u64>, String>>,
+}
+
+/// Lists programs in a directory with their size, hash and title, and previews the
+/// selected program.
+#[derive(Default)]
+pub struct BrowserView {
+  /// Whether the widget is displayed.
+  pub visible: bool,
+  dir: PathBuf,
+  db: RomDb,
+  entries: Vec<Entry>,
+  cursor: usize,
+  /// Index of the first entry displayed.
+  scroll: usize,
+  /// Number of entries displayed by the last render.
+  height: usize,
+}
+
+impl BrowserView {
+  /// Creates a browser of `dir`, looking programs up in `db`.
+  pub fn new(dir: impl Into<PathBuf>, db: RomDb) -> Self {
+    Self {
+      dir: dir.into(),
+      db,
+      ..Self::default()
+    }
+  }
+
+  /// Lists the current directory again and displays the widget.
+  pub fn show(&mut self) -> Result<()> {
+    self.visible = true;
+    self.open(self.dir.clone())
+  }
+
+  /// Lists the programs and directories in `dir`.
+  pub fn open(&mut self, dir: PathBuf) -> Result<()> {
+    let read = fs::read_dir(&dir)
+      .wrap_err_with(|| format!("Failed to read `{}`", dir.display()))?;
+
+    let mut entries = Vec::new();
+    if let Some(parent) = dir.canonicalize()?.parent() {
+      entries.push(Entry::dir("..".to_string(), parent.to_path_buf()));
+    }
+
+    let mut found = Vec::new();
+    for entry in read {
+      let entry = entry?;
+      let name = entry.file_name().to_string_lossy().into_owned();
+      if name.starts_with('.') {
+        continue;
+      }
+
+      let path = entry.path();
+      let meta = entry.metadata()?;
+
+      if meta.is_dir() {
+        found.push(Entry::dir(name, path));
+      } else if is_program(&path) {
+        found.push(Entry::program(name, path, meta.len(), &self.db));
+      }
+    }
+
+    found.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    entries.extend(found);
+
+    self.dir = dir;
+    self.entries = entries;
+    self.cursor = 0;
+    self.scroll = 0;
+    Ok(())
+  }
+
+  /// Gets the database programs are looked up in.
+  pub fn db(&self) -> &RomDb {
+    &self.db
+  }
+
+  /// Handles a key press, returns the program to load and its path when one is chosen.
+  pub fn handle_key(&mut self, code: KeyCode) -> Result<Option<(PathBuf, Rom)>> {
+    let last = self.entries.len().saturating_sub(1);
+    let page = self.height.max(1);
+
+    match code {
+      KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+      KeyCode::Down => self.cursor = (self.cursor + 1).min(last),
+      KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(page),
+      KeyCode::PageDown => self.cursor = (self.cursor + page).min(last),
+      KeyCode::Home => self.cursor = 0,
+      KeyCode::End => self.cursor = last,
+      KeyCode::Backspace => {
+        if let Some(parent) = self.dir.canonicalize()?.parent() {
+          self.open(parent.to_path_buf())?;
+        }
+      }
+      KeyCode::Enter => {
+        let Some(entry) = self.entries.get(self.cursor) else {
+          return Ok(None);
+        };
+
+        if entry.is_dir {
+          self.open(entry.path.clone())?;
+          return Ok(None);
+        }
+
+        return match &entry.rom {
+          Some(Ok(rom)) => Ok(Some((entry.path.clone(), rom.clone()))),
+          Some(Err(err)) => Err(eyre::eyre!("{err}")),
+          None => Ok(None),
+        };
+      }
+      _ => {}
+    }
+
+    Ok(None)
+  }
+
+  /// Renders the listing and a preview of the selected program.
+  pub fn render<B: Backend>(
+    &mut self,
+    theme: &Theme,
+    focused: bool,
+    area: Rect,
+    ui: &mut Frame<B>,
+  ) {
+    let chunks = Layout::default()
+      .direction(Direction::Horizontal)
+      .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+      .split(area);
+
+    let border = Style::default().fg(if focused { theme.accent } else { theme.label });
+    let block = Block::default()
+      .title(format!(" {} ", self.dir.display()))
+      .title_alignment(Alignment::Center)
+      .borders(Borders::ALL)
+      .border_style(border);
+
+    let inner = block.inner(chunks[0]);
+    self.height = inner.height as usize;
+
+    if self.cursor < self.scroll {
+      self.scroll = self.cursor;
+    } else if self.height > 0 && self.cursor >= self.scroll + self.height {
+      self.scroll = self.cursor + 1 - self.height;
+    }
+
+    let name_width = (inner.width as usize)
+      .saturating_sub(HASH_LEN + 16)
+      .clamp(8, 32);
+    let lines = self
+      .entries
+      .iter()
+      .enumerate()
+      .skip(self.scroll)
+      .take(self.height)
+      .map(|(idx, entry)| {
+        let style = match idx == self.cursor {
+          true => Style::default()
+            .fg(theme.accent)
+            .add_modifier(Modifier::REVERSED),
+          false => Style::default().fg(theme.value),
+        };
+
+        let name = format!("{:name_width$.name_width$}", entry.name);
+        let mut line = vec![Span::styled(name, style)];
+
+        match &entry.rom {
+          _ if entry.is_dir => line.push(Span::styled(
+            format!(" {:>6}", "dir"),
+            Style::default().fg(theme.label),
+          )),
+          Some(Ok(rom)) => {
+            line.push(Span::styled(
+              format!(" {:>6} {}", entry.size, &rom.sha1()[..HASH_LEN]),
+              Style::default().fg(theme.label),
+            ));
+
+            if let Some(title) = &rom.title {
+              line.push(Span::styled(
+                format!(" {title}"),
+                Style::default().fg(theme.fg),
+              ));
+            }
+          }
+          Some(Err(_)) => line.push(Span::styled(
+            format!(" {:>6} bad program", entry.size),
+            Style::default().fg(theme.error),
+          )),
+          None => {}
+        }
+
+        Spans::from(line)
+      })
+      .collect::<Vec<_>>();
+
+    ui.render_widget(block, chunks[0]);
+    ui.render_widget(Paragraph::new(lines), inner);
+
+    let block = Block::default()
+      .title(" preview ")
+      .title_alignment(Alignment::Center)
+      .borders(Borders::ALL)
+      .border_style(border);
+
+    let inner = block.inner(chunks[1]);
+    ui.render_widget(block, chunks[1]);
+
+    let Some(entry) = self.entries.get_mut(self.cursor) else {
+      return;
+    };
+
+    let preview = match &entry.rom {
+      Some(Ok(rom)) => entry.preview.get_or_insert_with(|| preview(rom)),
+      Some(Err(err)) => {
+        let text = Span::styled(err.as_str(), Style::default().fg(theme.error));
+        ui.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), inner);
+        return;
+      }
+      None => return,
+    };
+
+    match preview {
+      Ok(vram) => ui.render_widget(Game::new(vram).theme(*theme), inner),
+      Err(err) => {
+        let text = Span::styled(err.as_str(), Style::default().fg(theme.error));
+        ui.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), inner);
+      }
+    }
+  }
+}
+
+impl Entry {
+  fn dir(name: String, path: PathBuf) -> Self {
+    Self {
+      name,
+      path,
+      is_dir: true,
+      size: 0,
+      rom: None,
+      preview: None,
+    }
+  }
+
+  fn program(name: String, path: PathBuf, size: u64, db: &RomDb) -> Self {
+    let rom = Rom::from_file(&path)
+      .map(|mut rom| {
+        rom.apply_db(db);
+        rom
+      })
+      .map_err(|err| format!("{err:#}"));
+
+    Self {
+      name,
+      path,
+      is_dir: false,
+      size,
+      rom: Some(rom),
+      preview: None,
+    }
+  }
+}
+
+/// Runs `rom` without input for [PREVIEW_FRAMES] frames and gets the screen.
+fn preview(rom: &Rom) -> Result<Vec<u64>, String> {
+  let mut vm = Vm::new();
+  rom.load(&mut vm).map_err(|err| format!("{err:#}"))?;
+
+  for _ in 0..PREVIEW_FRAMES {
+    vm.run_frame().map_err(|err| err.to_string())?;
+  }
+
+  Ok(vm.vram.to_vec())
+}
@@ -0,0 +1,173 @@
+//! Debugger console widget.
+
+use super::theme::Theme;
+use crate::{
+  console::{Console, Output},
+  vm::Vm,
+};
+use crossterm::event::KeyCode;
+use std::path::Path;
+use tui::{
+  backend::Backend,
+  layout::{Alignment, Rect},
+  style::Style,
+  text::{Span, Spans},
+  widgets::{Block, Borders, Paragraph},
+  Frame,
+};
+
+/// Height of the console widget including borders.
+pub const HEIGHT: u16 = 8;
+/// Maximum number of lines kept in the console log.
+const LOG_LEN: usize = 256;
+
+/// A command line with history, completion and a log of command output.
+#[derive(Default)]
+pub struct ConsoleView {
+  pub console: Console,
+
+  input: String,
+  log: Vec<(String, bool)>,
+  history: Vec<String>,
+  history_idx: Option<usize>,
+}
+
+impl ConsoleView {
+  /// Appends a line to the log.
+  pub fn log(&mut self, line: impl Into<String>) {
+    self.push_log(line.into(), false);
+  }
+
+  /// Appends an error line to the log.
+  pub fn log_error(&mut self, line: impl Into<String>) {
+    self.push_log(line.into(), true);
+  }
+
+  /// Executes a script file, logging its output, returns whether execution should be
+  /// paused or resumed.
+  pub fn run_script(&mut self, vm: &mut Vm, path: impl AsRef<Path>) -> Option<bool> {
+    let output = self.console.run_script(vm, path);
+    self.log_output(output)
+  }
+
+  /// Handles a key press, returns `None` if the key was not consumed, otherwise whether
+  /// execution should be paused or resumed.
+  pub fn handle_key(&mut self, vm: &mut Vm, code: KeyCode) -> Option<Option<bool>> {
+    match code {
+      KeyCode::Char(c) => self.input.push(c),
+      KeyCode::Backspace => {
+        self.input.pop();
+      }
+      KeyCode::Tab => {
+        if let Some(completed) = self.console.complete(vm, &self.input) {
+          self.input = completed;
+        }
+      }
+      KeyCode::Up if !self.history.is_empty() => {
+        let idx = match self.history_idx {
+          Some(idx) => idx.saturating_sub(1),
+          None => self.history.len() - 1,
+        };
+
+        self.history_idx = Some(idx);
+        self.input = self.history[idx].clone();
+      }
+      KeyCode::Down => match self.history_idx {
+        Some(idx) if idx + 1 < self.history.len() => {
+          self.history_idx = Some(idx + 1);
+          self.input = self.history[idx + 1].clone();
+        }
+        _ => {
+          self.history_idx = None;
+          self.input.clear();
+        }
+      },
+      KeyCode::Enter => {
+        let line = std::mem::take(&mut self.input);
+        let line = line.trim();
+        self.history_idx = None;
+
+        if line.is_empty() {
+          return Some(None);
+        }
+
+        if self.history.last().map(String::as_str) != Some(line) {
+          self.history.push(line.to_string());
+        }
+
+        self.log(format!("> {line}"));
+        let output = self.console.execute(vm, line);
+        return Some(self.log_output(output));
+      }
+      _ => return None,
+    }
+
+    Some(None)
+  }
+
+  /// Renders the log and command line.
+  pub fn render<B: Backend>(
+    &mut self,
+    theme: &Theme,
+    focused: bool,
+    area: Rect,
+    ui: &mut Frame<B>,
+  ) {
+    let rows = area.height.saturating_sub(3) as usize;
+    let skip = self.log.len().saturating_sub(rows);
+    let mut spans = self
+      .log
+      .iter()
+      .skip(skip)
+      .map(|(line, is_error)| {
+        let color = if *is_error { theme.error } else { theme.label };
+        Spans::from(Span::styled(line.clone(), Style::default().fg(color)))
+      })
+      .collect::<Vec<_>>();
+
+    spans.resize(rows, Spans::default());
+
+    let cursor = if focused { "_" } else { "" };
+    spans.push(Spans::from(vec![
+      Span::styled(": ", Style::default().fg(theme.accent)),
+      Span::from(format!("{}{cursor}", self.input)),
+    ]));
+
+    let border = if focused { theme.accent } else { theme.label };
+
+    ui.render_widget(
+      Paragraph::new(spans).block(
+        Block::default()
+          .title(" console ")
+          .title_alignment(Alignment::Center)
+          .borders(Borders::ALL)
+          .border_style(Style::default().fg(border)),
+      ),
+      area,
+    );
+  }
+
+  fn log_output(&mut self, output: eyre::Result<Output>) -> Option<bool> {
+    match output {
+      Ok(output) => {
+        for line in output.lines {
+          self.log(line);
+        }
+
+        output.paused
+      }
+      Err(err) => {
+        self.log_error(format!("{err:#}"));
+        None
+      }
+    }
+  }
+
+  fn push_log(&mut self, line: String, is_error: bool) {
+    if self.log.len() == LOG_LEN {
+      self.log.remove(0);
+    }
+
+    self.log.push((line, is_error));
+  }
+}
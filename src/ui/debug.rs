@@ -1,16 +1,17 @@
 //! Debug widgets.
 
-use crate::{insn::Insn, vm::Vm};
+use super::theme::Theme;
+use crate::{scheduler::Scheduler, vm::Vm};
 use tui::{
   backend::Backend,
   layout::{Alignment, Rect},
-  style::{Color, Style},
+  style::Style,
   text::{Span, Spans},
   widgets::{Block, Borders, Paragraph},
   Frame,
 };
 
-pub fn keys<B: Backend>(vm: &Vm, area: Rect, ui: &mut Frame<B>) {
+pub fn keys<B: Backend>(vm: &Vm, theme: &Theme, area: Rect, ui: &mut Frame<B>) {
   let mut spans = Vec::new();
   let mut keys = vm.keys.to_vec();
 
@@ -24,7 +25,10 @@ pub fn keys<B: Backend>(vm: &Vm, area: Rect, ui: &mut Frame<B>) {
 
       match keys.pop() {
         None => break,
-        Some(key) => line.push(Span::from(format!("{key:#x} "))),
+        Some(key) => line.push(Span::styled(
+          format!("{key:#x} "),
+          Style::default().fg(theme.accent),
+        )),
       };
     }
 
@@ -42,60 +46,26 @@ pub fn keys<B: Backend>(vm: &Vm, area: Rect, ui: &mut Frame<B>) {
   );
 }
 
-pub fn registers<B: Backend>(vm: &Vm, area: Rect, ui: &mut Frame<B>) {
-  let mut spans = Vec::new();
-
-  for i in 0..4 {
-    let mut line = Vec::new();
-
-    for j in i * 4..i * 4 + 4 {
-      let reg = format!("V{j:x}: ");
-      let val = format!("{:#04x} ", vm.reg8[j]);
-
-      line.push(Span::styled(reg, Style::default().fg(Color::Gray)));
-      line.push(Span::from(val));
-    }
-
-    let (reg, val) = match i {
-      0 => ("PC: ", format!("{:#04x}", vm.reg_pc)),
-      1 => (" I: ", format!("{:#04x}", vm.reg_i)),
-      2 => ("DT: ", format!("{:#04x}", vm.reg_dt)),
-      3 => ("ST: ", format!("{:#04x}", vm.reg_st)),
-      _ => unreachable!(),
-    };
-
-    line.push(Span::styled(reg, Style::default().fg(Color::Gray)));
-    line.push(Span::from(val));
-    spans.push(Spans::from(line));
-  }
-
-  ui.render_widget(
-    Paragraph::new(spans).block(
-      Block::default()
-        .title(" registers ")
-        .title_alignment(Alignment::Center)
-        .borders(Borders::ALL),
-    ),
-    area,
-  );
-}
-
-pub fn stack<B: Backend>(vm: &Vm, area: Rect, ui: &mut Frame<B>) {
+pub fn stack<B: Backend>(vm: &Vm, theme: &Theme, area: Rect, ui: &mut Frame<B>) {
   let mut spans = Vec::new();
 
   for i in 0..16 {
     let mut line = Vec::new();
 
-    if vm.reg_sp == i as u8 {
-      line.push(Span::styled("> ", Style::default().fg(Color::Blue)));
+    if i + 1 == vm.reg_sp as usize {
+      line.push(Span::styled("> ", Style::default().fg(theme.accent)));
     } else {
       line.push(Span::from("  "));
     }
 
     let add = format!("{i:#04x}");
-    let val = format!(": {:#06x}  ", vm.stack[i]);
+    let val = if i < vm.reg_sp as usize && !vm.symbols.is_empty() {
+      format!(": {}", vm.symbols.describe(vm.stack[i]))
+    } else {
+      format!(": {:#06x}  ", vm.stack[i])
+    };
 
-    line.push(Span::styled(add, Style::default().fg(Color::Gray)));
+    line.push(Span::styled(add, Style::default().fg(theme.label)));
     line.push(Span::from(val));
     spans.push(Spans::from(line));
   }
@@ -111,46 +81,48 @@ pub fn stack<B: Backend>(vm: &Vm, area: Rect, ui: &mut Frame<B>) {
   );
 }
 
-pub fn disassembly<B: Backend>(vm: &Vm, area: Rect, ui: &mut Frame<B>) {
-  let mut spans = Vec::new();
-  let beg = (vm.reg_pc as usize).saturating_sub(area.height as usize);
-  let beg = if beg % 2 == 0 { beg } else { beg + 1 };
-  let end = (vm.reg_pc as usize).saturating_add(area.height as usize);
-  let end = if end % 2 == 0 { end } else { end + 1 };
-
-  let insns = (beg..end).step_by(2).filter_map(|i| {
-    let (hi, lo) = vm.get_insn_bytes_at(i)?;
-    let insn = Insn::from_bytes(hi, lo)?;
-
-    Some((i, (hi as u16) << 8 | lo as u16, insn))
-  });
-
-  for (offs, byte, insn) in insns {
-    let mut line = Vec::new();
-
-    let offs_color = if offs == vm.reg_pc as usize {
-      Color::Blue
-    } else {
-      Color::Gray
-    };
-
-    let offs = format!("{offs:#06x} ");
-    let byte = format!("{byte:04x} ");
-    let insn = format!("{insn}");
+/// Renders a line showing whether the machine runs, whether it is parked and at what
+/// speed.
+pub fn status<B: Backend>(
+  vm: &Vm,
+  scheduler: &Scheduler,
+  paused: bool,
+  theme: &Theme,
+  area: Rect,
+  ui: &mut Frame<B>,
+) {
+  let state = match (paused, scheduler.fast_forward) {
+    (true, _) => "paused",
+    (false, true) => "fast-forward",
+    (false, false) => "running",
+  };
+
+  let mut line = vec![
+    Span::styled(format!(" {state} "), Style::default().fg(theme.accent)),
+    Span::styled(
+      format!(" {} ipf ", vm.tickrate),
+      Style::default().fg(theme.value),
+    ),
+    Span::styled(
+      format!(" {:.0}Hz ", scheduler.rate(vm)),
+      Style::default().fg(theme.label),
+    ),
+  ];
+
+  let activity = vm.activity();
+  if activity.is_parked() {
+    line.insert(
+      1,
+      Span::styled(format!(" {activity} "), Style::default().fg(theme.accent)),
+    );
+  }
 
-    line.push(Span::styled(offs, Style::default().fg(offs_color)));
-    line.push(Span::styled(byte, Style::default().fg(Color::Yellow)));
-    line.push(Span::from(insn));
-    spans.push(Spans::from(line));
+  if scheduler.slowdown > 1 {
+    line.push(Span::styled(
+      format!(" 1/{} speed ", scheduler.slowdown),
+      Style::default().fg(theme.value),
+    ));
   }
 
-  ui.render_widget(
-    Paragraph::new(spans).block(
-      Block::default()
-        .title(" disassembly ")
-        .title_alignment(Alignment::Center)
-        .borders(Borders::ALL),
-    ),
-    area,
-  );
+  ui.render_widget(Paragraph::new(Spans::from(line)), area);
 }
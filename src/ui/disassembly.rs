@@ -0,0 +1,127 @@
+//! Disassembly widget.
+
+use super::theme::Theme;
+use crate::{insn::Insn, vm::Vm};
+use crossterm::event::KeyCode;
+use tui::{
+  backend::Backend,
+  layout::{Alignment, Rect},
+  style::{Modifier, Style},
+  text::{Span, Spans},
+  widgets::{Block, Borders, Paragraph},
+  Frame,
+};
+
+/// Disassembly around the program counter, or around a cursor moved through it.
+#[derive(Default)]
+pub struct DisassemblyView {
+  /// Address of the selected instruction, follows the program counter when `None`.
+  pub cursor: Option<u16>,
+  /// Number of rows displayed by the last render.
+  rows: usize,
+}
+
+impl DisassemblyView {
+  /// Handles a key press, returns `true` if the key was consumed.
+  pub fn handle_key(&mut self, vm: &Vm, code: KeyCode) -> bool {
+    let max = vm.mem.len().saturating_sub(2) as u16;
+    let page = (self.rows.max(1) * 2) as u16;
+    let cursor = self.cursor.unwrap_or(vm.reg_pc);
+
+    match code {
+      KeyCode::Up => self.cursor = Some(cursor.saturating_sub(2)),
+      KeyCode::Down => self.cursor = Some((cursor + 2).min(max)),
+      KeyCode::PageUp => self.cursor = Some(cursor.saturating_sub(page)),
+      KeyCode::PageDown => self.cursor = Some((cursor + page).min(max)),
+      KeyCode::Char('p') | KeyCode::Char('P') => self.cursor = None,
+      _ => return false,
+    }
+
+    true
+  }
+
+  /// Renders instructions around the cursor, or the program counter.
+  pub fn render<B: Backend>(
+    &mut self,
+    vm: &Vm,
+    theme: &Theme,
+    focused: bool,
+    heatmap: bool,
+    area: Rect,
+    ui: &mut Frame<B>,
+  ) {
+    self.rows = area.height.saturating_sub(2) as usize;
+
+    let max = if heatmap { vm.profile.max() } else { 0 };
+    let center = self.cursor.unwrap_or(vm.reg_pc) as usize;
+    let mut spans = Vec::new();
+    let beg = center.saturating_sub(area.height as usize & !1);
+    let end = center.saturating_add(area.height as usize);
+
+    let insns = (beg..end).step_by(2).filter_map(|i| {
+      let (hi, lo) = vm.get_insn_bytes_at(i)?;
+      let insn = match vm.symbols.region(i as u16) {
+        Some(_) => format!("DB {hi:#04x}, {lo:#04x}"),
+        None => vm.symbols.format_insn(&Insn::from_bytes(hi, lo)?),
+      };
+
+      Some((i, (hi as u16) << 8 | lo as u16, insn))
+    });
+
+    for (offs, byte, insn) in insns {
+      let mut line = Vec::new();
+
+      let region = vm
+        .symbols
+        .region(offs as u16)
+        .filter(|region| region.range.start as usize == offs)
+        .and_then(|region| region.name.as_deref());
+
+      if let Some(label) = vm.symbols.label(offs as u16).or(region) {
+        spans.push(Spans::from(Span::styled(
+          format!("{label}:"),
+          Style::default().fg(theme.accent),
+        )));
+      }
+
+      let offs_color = if offs == vm.reg_pc as usize {
+        theme.accent
+      } else {
+        theme.label
+      };
+
+      let mut offs_style = Style::default().fg(offs_color);
+      if let Some(heat) = theme.heat_color(vm.profile.heat(offs, max)) {
+        offs_style = offs_style.fg(theme.bg).bg(heat);
+      }
+
+      let insn_style = match self.cursor {
+        Some(cursor) if cursor as usize == offs => {
+          Style::default().add_modifier(Modifier::REVERSED)
+        }
+        _ => Style::default(),
+      };
+
+      let offs = format!("{offs:#06x}");
+      let byte = format!(" {byte:04x} ");
+
+      line.push(Span::styled(offs, offs_style));
+      line.push(Span::styled(byte, Style::default().fg(theme.value)));
+      line.push(Span::styled(insn, insn_style));
+      spans.push(Spans::from(line));
+    }
+
+    let border = if focused { theme.accent } else { theme.label };
+
+    ui.render_widget(
+      Paragraph::new(spans).block(
+        Block::default()
+          .title(" disassembly ")
+          .title_alignment(Alignment::Center)
+          .borders(Borders::ALL)
+          .border_style(Style::default().fg(border)),
+      ),
+      area,
+    );
+  }
+}
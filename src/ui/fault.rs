@@ -0,0 +1,105 @@
+//! Fault panel widget.
+
+use super::theme::Theme;
+use crate::{
+  dump::StateDump,
+  vm::{fault::Fault, Vm},
+};
+use eyre::Result;
+use std::path::PathBuf;
+use tui::{
+  backend::Backend,
+  layout::{Alignment, Rect},
+  style::Style,
+  text::{Span, Spans},
+  widgets::{Block, Borders, Clear, Paragraph},
+  Frame,
+};
+
+/// Path crash dumps are written to unless configured otherwise.
+pub const DUMP_PATH: &str = "crash.json";
+
+/// Shows the fault the machine stopped with and writes it to a crash dump.
+pub struct FaultView {
+  /// Whether the widget is displayed.
+  pub visible: bool,
+  /// Path crash dumps are written to.
+  pub dump_path: PathBuf,
+  fault: Option<Fault>,
+}
+
+impl Default for FaultView {
+  fn default() -> Self {
+    Self {
+      visible: false,
+      dump_path: PathBuf::from(DUMP_PATH),
+      fault: None,
+    }
+  }
+}
+
+impl FaultView {
+  /// Displays `fault`.
+  pub fn show(&mut self, fault: Fault) {
+    self.fault = Some(fault);
+    self.visible = true;
+  }
+
+  /// Writes the state of `vm` with the displayed fault to [FaultView::dump_path].
+  pub fn write_dump(&self, vm: &Vm) -> Result<()> {
+    let mut dump = StateDump::full(vm)?;
+    dump.fault = self.fault.clone();
+    dump.write(&self.dump_path)
+  }
+
+  pub fn render<B: Backend>(
+    &self,
+    vm: &Vm,
+    theme: &Theme,
+    focused: bool,
+    area: Rect,
+    ui: &mut Frame<B>,
+  ) {
+    let Some(fault) = &self.fault else {
+      return;
+    };
+
+    // drop the oldest history when the panel is too short.
+    let report = fault.report(vm);
+    let rows = (area.height as usize).saturating_sub(3);
+    let excess = report.len().saturating_sub(rows).min(fault.history.len());
+    let oldest = report.len() - fault.history.len();
+
+    let mut lines = report
+      .iter()
+      .enumerate()
+      .filter(|(i, _)| !(oldest..oldest + excess).contains(i))
+      .map(|(i, line)| match i {
+        0 => Spans::from(Span::styled(
+          line.as_str(),
+          Style::default().fg(theme.error),
+        )),
+        _ if !line.starts_with(' ') => Spans::from(Span::styled(
+          line.as_str(),
+          Style::default().fg(theme.label),
+        )),
+        _ => Spans::from(line.as_str()),
+      })
+      .collect::<Vec<_>>();
+
+    lines.push(Spans::from(Span::styled(
+      format!("d: write `{}`  esc: close", self.dump_path.display()),
+      Style::default().fg(theme.value),
+    )));
+
+    let border = Style::default().fg(if focused { theme.accent } else { theme.label });
+    let block = Block::default()
+      .title(" fault ")
+      .title_alignment(Alignment::Center)
+      .borders(Borders::ALL)
+      .border_style(border);
+
+    ui.render_widget(Clear, area);
+    ui.render_widget(Paragraph::new(lines).block(block), area);
+  }
+}
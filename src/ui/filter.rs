@@ -0,0 +1,162 @@
+//! Display filters that reduce flicker.
+//!
+//! Chip-8 programs move sprites by erasing them with `DRW` and drawing them again, so
+//! sampling vram at an arbitrary point often catches a sprite while it is erased. Filters
+//! sit between vram and the game widget and are updated once per 60Hz frame, counted by
+//! [Vm::frames].
+
+use crate::{
+  render::Framebuffer,
+  vm::{Vm, VRAM_HEIGHT},
+};
+
+/// Brightness kept by a glowing pixel each frame.
+const DECAY: f32 = 0.6;
+/// Brightness below which a glowing pixel goes dark.
+const CUTOFF: f32 = 0.1;
+
+/// A way of presenting vram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+  /// Vram is displayed as is.
+  #[default]
+  None,
+  /// Pixels lit in the current or previous frame are displayed.
+  Blend,
+  /// Pixels that go dark fade out over a few frames.
+  Phosphor,
+  /// Vram is only displayed as it was at the start of each frame.
+  VBlank,
+}
+
+impl Filter {
+  /// Every filter in the order they are cycled through.
+  pub const ALL: [Filter; 4] = [Self::None, Self::Blend, Self::Phosphor, Self::VBlank];
+
+  /// Gets a filter by name.
+  pub fn named(name: &str) -> Option<Self> {
+    Self::ALL
+      .into_iter()
+      .find(|filter| filter.name().eq_ignore_ascii_case(name))
+  }
+
+  /// Gets the name of the filter.
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::None => "none",
+      Self::Blend => "blend",
+      Self::Phosphor => "phosphor",
+      Self::VBlank => "vblank",
+    }
+  }
+
+  /// Gets the filter after this one, wrapping around.
+  pub fn next(self) -> Self {
+    let idx = Self::ALL
+      .iter()
+      .position(|&filter| filter == self)
+      .unwrap_or(0);
+    Self::ALL[(idx + 1) % Self::ALL.len()]
+  }
+}
+
+/// Filter state carried between frames.
+pub struct Display {
+  /// The active filter.
+  pub filter: Filter,
+  /// Frame the state was last updated at.
+  frame: u64,
+  /// Vram at the start of the current frame.
+  prev: [u64; VRAM_HEIGHT],
+  /// Vram to display.
+  vram: [u64; VRAM_HEIGHT],
+  /// Brightness of each pixel from 0 to 1.
+  glow: [[f32; 64]; VRAM_HEIGHT],
+}
+
+impl Default for Display {
+  fn default() -> Self {
+    Self {
+      filter: Filter::default(),
+      frame: 0,
+      prev: [0; VRAM_HEIGHT],
+      vram: [0; VRAM_HEIGHT],
+      glow: [[0.; 64]; VRAM_HEIGHT],
+    }
+  }
+}
+
+impl Display {
+  /// Updates filter state from `vm`, advancing by however many frames passed since the
+  /// last update. Returns `true` if the framebuffer to display changed.
+  pub fn update(&mut self, vm: &Vm) -> bool {
+    if vm.frames < self.frame {
+      self.frame = vm.frames;
+    }
+
+    // Only the most recent frames are visible, skip any before them.
+    let frames = (vm.frames - self.frame).min(8);
+    self.frame = vm.frames;
+    let mut glowed = false;
+
+    for _ in 0..frames {
+      for (y, row) in self.glow.iter_mut().enumerate() {
+        for (x, level) in row.iter_mut().enumerate() {
+          let next = if self.prev[y] >> (63 - x) & 1 != 0 {
+            1.
+          } else if *level * DECAY < CUTOFF {
+            0.
+          } else {
+            *level * DECAY
+          };
+
+          glowed |= next != *level;
+          *level = next;
+        }
+      }
+    }
+
+    if frames > 0 {
+      self.prev = vm.vram;
+    }
+
+    let vram = match self.filter {
+      Filter::None | Filter::Phosphor => vm.vram,
+      Filter::Blend => {
+        let mut vram = vm.vram;
+        for (row, prev) in vram.iter_mut().zip(self.prev) {
+          *row |= prev;
+        }
+
+        vram
+      }
+      Filter::VBlank => self.prev,
+    };
+
+    let changed = vram != self.vram || glowed && self.glow().is_some();
+    self.vram = vram;
+    changed
+  }
+
+  /// Gets the vram to display.
+  pub fn vram(&self) -> &[u64] {
+    &self.vram
+  }
+
+  /// Gets the brightness of glowing pixels, if the filter makes pixels glow.
+  pub fn glow(&self) -> Option<&[[f32; 64]]> {
+    match self.filter {
+      Filter::Phosphor => Some(&self.glow),
+      _ => None,
+    }
+  }
+
+  /// Gets the framebuffer to display, glowing if the filter makes pixels glow.
+  pub fn framebuffer(&self) -> Framebuffer<'_> {
+    let framebuffer = Framebuffer::new(&self.vram);
+    match self.glow() {
+      Some(glow) => framebuffer.with_glow(glow),
+      None => framebuffer,
+    }
+  }
+}
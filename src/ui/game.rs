@@ -1,222 +1,121 @@
 //! Game widget.
 
+use super::theme::Theme;
+use crate::render::{Framebuffer, Renderer, Resolution, Shade};
+use eyre::Result;
 use tui::{
+  backend::Backend,
   buffer::Buffer,
   layout::Rect,
   style::{Color, Style},
   widgets::Widget,
+  Terminal,
 };
 
 pub struct Game<'vram> {
-  vram: &'vram [u64],
+  framebuffer: Framebuffer<'vram>,
+  theme: Theme,
 }
 
 impl<'vram> Game<'vram> {
   /// Creates a [Game].
   pub fn new(vram: &'vram [u64]) -> Self {
-    Self { vram }
+    Self::from_framebuffer(Framebuffer::new(vram))
   }
 
-  /// Gets vram width.
-  pub fn vram_width(&self) -> usize {
-    u64::BITS as _
+  /// Creates a [Game] displaying `framebuffer`.
+  pub fn from_framebuffer(framebuffer: Framebuffer<'vram>) -> Self {
+    Self {
+      framebuffer,
+      theme: Theme::default(),
+    }
   }
 
-  /// Gets vram height.
-  pub fn vram_height(&self) -> usize {
-    self.vram.len()
+  /// Sets the number of pixels displayed from the most significant bit of each row.
+  pub fn width(mut self, width: usize) -> Self {
+    self.framebuffer = self.framebuffer.with_width(width);
+    self
   }
 
-  fn render_blocks(&self, area: Rect, buf: &mut Buffer) {
-    let vram_mid_x = self.vram_width() / 2;
-    let vram_mid_y = self.vram_height() / 2;
-    let game_mid_x = area.width / 2;
-    let game_mid_y = area.height / 2;
-
-    let mid_x = game_mid_x - vram_mid_x as u16;
-    let mid_y = game_mid_y - vram_mid_y as u16;
-
-    for y in 0..self.vram_height() {
-      let byte = self.vram[y];
-
-      for x in 0..self.vram_width() {
-        let pixel = byte >> (63 - x) & 0x1 != 0;
-        let color = if pixel { Color::Green } else { Color::Black };
-
-        let x = mid_x + x as u16;
-        let y = mid_y + y as u16;
-
-        buf.get_mut(x, y).set_bg(color);
-      }
-    }
+  /// Sets the brightness of unlit pixels that are still glowing, from 0 to 1.
+  pub fn glow(mut self, glow: &'vram [[f32; 64]]) -> Self {
+    self.framebuffer = self.framebuffer.with_glow(glow);
+    self
   }
 
-  fn render_quarters(&self, area: Rect, buf: &mut Buffer) {
-    let vram_mid_x = self.vram_width() / 4;
-    let vram_mid_y = self.vram_height() / 4;
-    let game_mid_x = area.width / 2;
-    let game_mid_y = area.height / 2;
-
-    let mid_x = game_mid_x - vram_mid_x as u16;
-    let mid_y = game_mid_y - vram_mid_y as u16;
-
-    for y in (0..self.vram_height()).step_by(2) {
-      let n1 = self.vram[y];
-      let n2 = self.vram[y + 1];
-
-      for x in (0..self.vram_width()).step_by(2) {
-        // shifts two `n1` bits at x into higher bits of u4.
-        let n1 = n1 >> 60u64.saturating_sub(x as u64);
-        let n1 = n1 as u8 & 0b1100;
-
-        // shifts two `n2` bits at x into lower bits of u4.
-        let n2 = n2 >> 62u64.saturating_sub(x as u64);
-        let n2 = n2 as u8 & 0b0011;
-
-        // Collect 2x2 bits in u8 where trailing bits correspond to tl, tr, bl, br
-        let bits = n1 | n2;
-
-        let x = mid_x + (x / 2) as u16;
-        let y = mid_y + (y / 2) as u16;
-
-        match get_quarter_for_bits(bits) {
-          Some(quarter) => buf
-            .get_mut(x, y)
-            .set_char(quarter)
-            .set_fg(Color::Green)
-            .set_bg(Color::Black),
-          None => buf.get_mut(x, y).set_bg(Color::Green),
-        };
-      }
-    }
+  /// Sets the colour theme.
+  pub fn theme(mut self, theme: Theme) -> Self {
+    self.theme = theme;
+    self
   }
 
-  fn render_braille(&self, area: Rect, buf: &mut Buffer) {
-    let vram_mid_x = self.vram_width() / 4;
-    let vram_mid_y = self.vram_height() / 8;
-    let game_mid_x = area.width / 2;
-    let game_mid_y = area.height / 2;
-
-    let mid_x = game_mid_x - vram_mid_x as u16;
-    let mid_y = game_mid_y - vram_mid_y as u16;
-
-    for y in (0..self.vram_height()).step_by(4) {
-      let n1 = self.vram.get(y).copied().unwrap_or(0);
-      let n2 = self.vram.get(y + 1).copied().unwrap_or(0);
-      let n3 = self.vram.get(y + 2).copied().unwrap_or(0);
-      let n4 = self.vram.get(y + 3).copied().unwrap_or(0);
-
-      for x in (0..self.vram_width()).step_by(2) {
-        // Braille patterns are ordered as follows:
-        //  1 4
-        //  2 5
-        //  3 6
-        //  7 8
-
-        // shifts two `n1` bits at x into bits of u8.
-        let n1 = n1 >> 56u64.saturating_sub(x as u64);
-        // 0b00_00_00_01 - 1
-        let n1_hi = (n1 as u8 & 0b10_00_00_00) >> 7;
-        // 0b00_00_10_00 - 4
-        let n1_lo = (n1 as u8 & 0b01_00_00_00) >> 3;
-        let n1 = n1_hi | n1_lo;
-
-        // shifts two `n2` bits at x into bits of u8.
-        let n2 = n2 >> 58u64.saturating_sub(x as u64);
-        // 0b00_00_00_10 - 2
-        let n2_hi = (n2 as u8 & 0b00_10_00_00) >> 4;
-        // 0b00_01_00_00 - 5
-        let n2_lo = n2 as u8 & 0b00_01_00_00;
-        let n2 = n2_hi | n2_lo;
-
-        // shifts two `n1` bits at x into bits of u8.
-        let n3 = n3 >> 60u64.saturating_sub(x as u64);
-        // 0b00_00_01_00 - 3
-        let n3_hi = (n3 as u8 & 0b00_00_10_00) >> 1;
-        // 0b00_10_00_00 - 6
-        let n3_lo = (n3 as u8 & 0b00_00_01_00) << 3;
-        let n3 = n3_hi | n3_lo;
-
-        // shifts two `n2` bits at x into bits of u8.
-        let n4 = n4 >> 62u64.saturating_sub(x as u64);
-        // 0b01_00_00_00 - 7
-        let n4_hi = (n4 as u8 & 0b00_00_00_10) << 5;
-        // 0b10_00_00_00 - 8
-        let n4_lo = (n4 as u8 & 0b00_00_00_01) << 7;
-        let n4 = n4_hi | n4_lo;
-
-        // Collect 2x2 bits in u8 where trailing bits correspond to tl, tr, bl, br
-        let bits = n1 | n2 | n3 | n4;
-
-        let x = mid_x + (x / 2) as u16;
-        let y = mid_y + (y / 4) as u16;
-
-        match get_braille_for_bits(bits) {
-          Some(quarter) => buf
-            .get_mut(x, y)
-            .set_char(quarter)
-            .set_fg(Color::Green)
-            .set_bg(Color::Black),
-          None => buf.get_mut(x, y).set_bg(Color::Black),
-        };
-      }
+  fn color(&self, shade: Shade) -> Color {
+    match shade {
+      Shade::Off => self.theme.bg,
+      Shade::On => self.theme.fg,
+      Shade::Glow(level) => self.theme.fade(level),
     }
   }
 }
 
 impl<'vram> Widget for Game<'vram> {
   fn render(self, area: Rect, buf: &mut Buffer) {
-    let width = self.vram_width() as u16;
-    let height = self.vram_height() as u16;
-
-    if area.width >= width && area.height >= height {
-      return self.render_blocks(area, buf);
-    }
-
-    if area.width >= width / 2 && area.height >= height / 2 {
-      return self.render_quarters(area, buf);
+    let width = area.width as usize;
+    let height = area.height as usize;
+
+    let Some(resolution) = Resolution::fit(&self.framebuffer, width, height) else {
+      buf.set_string(
+        area.x,
+        area.y,
+        "Too small! Try resizing your terminal.",
+        Style::default().fg(self.theme.error),
+      );
+      return;
+    };
+
+    let cells = self.framebuffer.cells(resolution);
+
+    let (mid_x, mid_y) = cells.centre(width, height);
+    let mid_x = area.x + mid_x as u16;
+    let mid_y = area.y + mid_y as u16;
+
+    for (y, row) in cells.rows().enumerate() {
+      for (x, cell) in row.iter().enumerate() {
+        buf
+          .get_mut(mid_x + x as u16, mid_y + y as u16)
+          .set_char(cell.symbol)
+          .set_fg(self.color(cell.fg))
+          .set_bg(self.color(cell.bg));
+      }
     }
+  }
+}
 
-    if area.width >= width / 2 && area.height >= height / 4 {
-      return self.render_braille(area, buf);
-    }
+/// Draws frames across a whole terminal with the [Game] widget.
+pub struct TuiRenderer<B: Backend> {
+  terminal: Terminal<B>,
+  theme: Theme,
+}
 
-    buf.set_string(
-      0,
-      0,
-      "Too small! Try resizing your terminal.",
-      Style::default().fg(Color::Red),
-    );
+impl<B: Backend> TuiRenderer<B> {
+  /// Creates a [TuiRenderer] drawing to `backend`.
+  pub fn new(backend: B, theme: Theme) -> Result<Self> {
+    Ok(Self {
+      terminal: Terminal::new(backend)?,
+      theme,
+    })
   }
-}
 
-fn get_quarter_for_bits(bits: u8) -> Option<char> {
-  match bits {
-    0b0000 => Some(' '),
-    0b0001 => Some('▗'),
-    0b0010 => Some('▖'),
-    0b0011 => Some('▄'),
-    0b0100 => Some('▝'),
-    0b0101 => Some('▐'),
-    0b0110 => Some('▞'),
-    0b0111 => Some('▟'),
-    0b1000 => Some('▘'),
-    0b1001 => Some('▚'),
-    0b1010 => Some('▌'),
-    0b1011 => Some('▙'),
-    0b1100 => Some('▀'),
-    0b1101 => Some('▜'),
-    0b1110 => Some('▛'),
-    0b1111 => Some('█'),
-
-    _ => None,
+  /// Gets the terminal drawn to.
+  pub fn terminal(&self) -> &Terminal<B> {
+    &self.terminal
   }
 }
 
-fn get_braille_for_bits(bits: u8) -> Option<char> {
-  if bits == 0 {
-    Some(' ')
-  } else {
-    char::from_u32(0x2800u32 | bits as u32)
+impl<B: Backend> Renderer for TuiRenderer<B> {
+  fn render(&mut self, framebuffer: &Framebuffer) -> Result<()> {
+    let game = Game::from_framebuffer(*framebuffer).theme(self.theme);
+    self.terminal.draw(|ui| ui.render_widget(game, ui.size()))?;
+    Ok(())
   }
 }
@@ -1,6 +1,6 @@
 //! Terminal events.
 
-use crossterm::event::{self, Event, KeyEvent};
+use crossterm::event::{self, Event, KeyEvent, MouseEvent};
 use std::{
   ops::Deref,
   sync::mpsc,
@@ -12,18 +12,34 @@ use std::{
 pub struct UiKeys {
   rx: mpsc::Receiver<KeyEventWithState>,
   _tx: mpsc::Sender<KeyEventWithState>,
+  mouse_rx: mpsc::Receiver<MouseEvent>,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct KeyEventWithState {
   event: KeyEvent,
   pub is_pressed: bool,
+  /// Whether this is a repeated press of a key that is already held.
+  pub is_repeat: bool,
 }
 
 impl KeyEventWithState {
   /// Create a [KeyEventWithState] from [KeyEvent] and `is_pressed` state.
   pub fn new(event: KeyEvent, is_pressed: bool) -> Self {
-    Self { event, is_pressed }
+    Self {
+      event,
+      is_pressed,
+      is_repeat: false,
+    }
+  }
+
+  /// Create a repeated press [KeyEventWithState] from [KeyEvent].
+  pub fn repeat(event: KeyEvent) -> Self {
+    Self {
+      event,
+      is_pressed: true,
+      is_repeat: true,
+    }
   }
 }
 
@@ -47,6 +63,7 @@ impl UiKeys {
   #[allow(clippy::new_without_default)]
   pub fn new() -> Self {
     let (tx, rx) = mpsc::channel();
+    let (mouse_tx, mouse_rx) = mpsc::channel();
     let _tx = tx.clone();
 
     thread::spawn(move || {
@@ -56,11 +73,18 @@ impl UiKeys {
 
       loop {
         if let Ok(true) = event::poll(Duration::from_millis(50)) {
-          if let Ok(Event::Key(key)) = event::read() {
+          let event = event::read();
+          if let Ok(Event::Mouse(mouse)) = event {
+            mouse_tx.send(mouse).ok();
+          }
+
+          if let Ok(Event::Key(key)) = event {
             if let Some(last) = last_key {
               if last.code != key.code {
                 tx.send(KeyEventWithState::new(last, false)).ok();
                 tx.send(KeyEventWithState::new(key, true)).ok();
+              } else {
+                tx.send(KeyEventWithState::repeat(key)).ok();
               }
             } else {
               tx.send(KeyEventWithState::new(key, true)).ok();
@@ -82,7 +106,12 @@ impl UiKeys {
       }
     });
 
-    Self { rx, _tx }
+    Self { rx, _tx, mouse_rx }
+  }
+
+  /// Gets pending mouse events.
+  pub fn mouse(&mut self) -> impl Iterator<Item = MouseEvent> + '_ {
+    self.mouse_rx.try_iter()
   }
 }
 
@@ -0,0 +1,206 @@
+//! Memory viewer and hex editor widget.
+
+use super::theme::Theme;
+use crate::{
+  insn::{
+    insns::{DrwInsn, LdInsn},
+    Insn,
+  },
+  vm::{Vm, PROG_OFF},
+};
+use crossterm::event::KeyCode;
+use std::ops::Range;
+use tui::{
+  backend::Backend,
+  layout::{Alignment, Rect},
+  style::{Modifier, Style},
+  text::{Span, Spans},
+  widgets::{Block, Borders, Paragraph},
+  Frame,
+};
+
+/// Number of bytes displayed per row.
+pub const BYTES_PER_ROW: usize = 8;
+/// Width of the memory widget including borders.
+pub const WIDTH: u16 = 2 + 7 + BYTES_PER_ROW as u16 * 3 + BYTES_PER_ROW as u16;
+
+/// Size of the font area at the start of memory.
+const FONT_LEN: usize = 5 * 16;
+
+/// Scrollable hex/ascii view of [Vm] memory that supports editing while paused.
+#[derive(Default)]
+pub struct MemoryView {
+  /// Address of the selected byte.
+  pub cursor: usize,
+  /// Address of the first displayed row.
+  scroll: usize,
+  /// High nibble typed while editing the selected byte.
+  nibble: Option<u8>,
+  /// Number of rows displayed by the last render.
+  rows: usize,
+}
+
+impl MemoryView {
+  /// Handles a key press, returns `true` if the key was consumed.
+  ///
+  /// Navigation keys are always consumed, hex digits are only consumed when `editable`.
+  pub fn handle_key(&mut self, vm: &mut Vm, code: KeyCode, editable: bool) -> bool {
+    let len = vm.mem.len();
+    let page = self.rows.max(1) * BYTES_PER_ROW;
+
+    match code {
+      KeyCode::Left => self.move_to(self.cursor.saturating_sub(1)),
+      KeyCode::Right => self.move_to((self.cursor + 1).min(len - 1)),
+      KeyCode::Up => self.move_to(self.cursor.saturating_sub(BYTES_PER_ROW)),
+      KeyCode::Down => self.move_to((self.cursor + BYTES_PER_ROW).min(len - 1)),
+      KeyCode::PageUp => self.move_to(self.cursor.saturating_sub(page)),
+      KeyCode::PageDown => self.move_to((self.cursor + page).min(len - 1)),
+      KeyCode::Home => self.move_to(0),
+      KeyCode::End => self.move_to(len - 1),
+      KeyCode::Char('i') | KeyCode::Char('I') => self.move_to(vm.reg_i as usize % len),
+      KeyCode::Char('p') | KeyCode::Char('P') => self.move_to(vm.reg_pc as usize % len),
+      KeyCode::Char(c) if editable && c.is_ascii_hexdigit() => {
+        let digit = c.to_digit(16).unwrap_or(0) as u8;
+
+        match self.nibble.take() {
+          None => self.nibble = Some(digit),
+          Some(hi) => {
+            vm.mem[self.cursor] = hi << 4 | digit;
+            self.move_to((self.cursor + 1).min(len - 1));
+          }
+        }
+      }
+      KeyCode::Esc if self.nibble.is_some() => self.nibble = None,
+      _ => return false,
+    }
+
+    true
+  }
+
+  /// Moves the cursor to `addr`, discarding any partially typed byte.
+  pub fn move_to(&mut self, addr: usize) {
+    self.cursor = addr;
+    self.nibble = None;
+  }
+
+  /// Renders memory around the cursor.
+  pub fn render<B: Backend>(
+    &mut self,
+    vm: &Vm,
+    theme: &Theme,
+    focused: bool,
+    heatmap: bool,
+    area: Rect,
+    ui: &mut Frame<B>,
+  ) {
+    self.rows = area.height.saturating_sub(2) as usize;
+
+    let cursor_row = self.cursor / BYTES_PER_ROW;
+    let scroll_row = self.scroll / BYTES_PER_ROW;
+    let scroll_row = if cursor_row < scroll_row {
+      cursor_row
+    } else if self.rows > 0 && cursor_row >= scroll_row + self.rows {
+      cursor_row + 1 - self.rows
+    } else {
+      scroll_row
+    };
+
+    self.scroll = scroll_row * BYTES_PER_ROW;
+
+    let prog = PROG_OFF..PROG_OFF + vm.prog_len;
+    let deref_i = deref_i_range(vm);
+    let max = if heatmap { vm.profile.max() } else { 0 };
+    let mut spans = Vec::new();
+
+    for row in 0..self.rows {
+      let beg = self.scroll + row * BYTES_PER_ROW;
+      if beg >= vm.mem.len() {
+        break;
+      }
+
+      let end = (beg + BYTES_PER_ROW).min(vm.mem.len());
+      let mut line = vec![Span::styled(
+        format!("{beg:#06x} "),
+        Style::default().fg(theme.label),
+      )];
+
+      for addr in beg..end {
+        let text = if addr == self.cursor && self.nibble.is_some() {
+          format!("{:x}_", self.nibble.unwrap_or(0))
+        } else {
+          format!("{:02x}", vm.mem[addr])
+        };
+
+        let mut style = Style::default();
+
+        if addr < FONT_LEN {
+          style = style.fg(theme.label);
+        } else if prog.contains(&addr) {
+          style = style.fg(theme.fg);
+        }
+
+        if let Some(heat) = theme.heat_color(vm.profile.heat(addr, max)) {
+          style = style.fg(theme.bg).bg(heat);
+        }
+
+        if deref_i.contains(&addr) {
+          style = style.fg(theme.bg).bg(theme.accent);
+        }
+
+        if vm.recent_writes.contains(&(addr as u16)) {
+          style = style.fg(theme.value).add_modifier(Modifier::BOLD);
+        }
+
+        if focused && addr == self.cursor {
+          style = style.add_modifier(Modifier::REVERSED);
+        }
+
+        line.push(Span::styled(text, style));
+        line.push(Span::from(" "));
+      }
+
+      let ascii = vm.mem[beg..end]
+        .iter()
+        .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+        .collect::<String>();
+
+      line.push(Span::styled(ascii, Style::default().fg(theme.label)));
+      spans.push(Spans::from(line));
+    }
+
+    let title = if focused && vm.mem.get(self.cursor).is_some() {
+      format!(" memory {:#06x} ", self.cursor)
+    } else {
+      " memory ".to_string()
+    };
+
+    let border = if focused { theme.accent } else { theme.label };
+
+    ui.render_widget(
+      Paragraph::new(spans).block(
+        Block::default()
+          .title(title)
+          .title_alignment(Alignment::Center)
+          .borders(Borders::ALL)
+          .border_style(Style::default().fg(border)),
+      ),
+      area,
+    );
+  }
+}
+
+/// Gets the range of memory the instruction at the program counter reads or writes
+/// through `I`, or a single byte at `I` if it doesn't use `I`.
+fn deref_i_range(vm: &Vm) -> Range<usize> {
+  let i = vm.reg_i as usize;
+  let len = match vm.insn_at_pc() {
+    Some(Insn::Drw(DrwInsn { n, .. })) => n as usize,
+    Some(Insn::Ld(LdInsn::BcdReg(_))) => 3,
+    Some(Insn::Ld(LdInsn::PtrReg(x))) | Some(Insn::Ld(LdInsn::RegPtr(x))) => {
+      x as usize + 1
+    }
+    _ => 1,
+  };
+
+  i..i + len
+}
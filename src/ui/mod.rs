@@ -1,34 +1,117 @@
 //! Chip-8 terminal user interface.
 
+pub mod browser;
+pub mod console;
 pub mod debug;
+pub mod disassembly;
+pub mod fault;
+pub mod filter;
 pub mod game;
 pub mod keys;
+pub mod memory;
+pub mod registers;
+pub mod sprites;
+pub mod theme;
 
-use self::{game::Game, keys::UiKeys};
-use crate::vm::{flags::VmKey, Vm};
+use self::{
+  browser::BrowserView, console::ConsoleView, disassembly::DisassemblyView,
+  fault::FaultView, filter::Display, game::Game, keys::UiKeys, memory::MemoryView,
+  registers::RegistersView, sprites::SpritesView, theme::Theme,
+};
+use crate::{
+  rom::{watch::Watcher, KeyHints, Rom},
+  scheduler::{self, Scheduler, Step, SLOWDOWNS, TICKRATES},
+  vm::{debug::Break, flags::VmKey, symbols::Symbols, Vm},
+};
 use crossterm::{
   cursor::Show,
-  event::{DisableMouseCapture, EnableMouseCapture, KeyCode, KeyModifiers},
+  event::{
+    DisableMouseCapture, EnableMouseCapture, KeyCode, KeyModifiers, MouseButton,
+    MouseEventKind,
+  },
   execute,
   terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use eyre::Result;
+use eyre::{eyre, Result};
 use std::{
   io::{self, Stdout},
   panic,
+  path::PathBuf,
+  time::{Duration, Instant},
 };
 use tui::{
   backend::CrosstermBackend,
-  layout::{Constraint, Direction, Layout},
+  layout::{Constraint, Direction, Layout, Rect},
   Terminal,
 };
 
+/// Shortest time between redraws, 60 frames per second.
+const FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+/// The widget receiving keyboard input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+  /// Keys are sent to the virtual machine.
+  Game,
+  /// Keys navigate and edit the registers widget.
+  Registers,
+  /// Keys navigate and edit the memory widget.
+  Memory,
+  /// Keys navigate the disassembly widget.
+  Disassembly,
+  /// Keys navigate the sprite widget.
+  Sprites,
+  /// Keys are typed into the console command line.
+  Console,
+  /// Keys navigate the ROM browser.
+  Browser,
+  /// Keys close the fault panel or write a crash dump.
+  Fault,
+}
+
 pub struct Ui {
-  pub step: bool,
+  /// Step through the paused program requested by a key press.
+  pub step: Option<Step>,
   pub paused: bool,
+  pub theme: Theme,
+  pub focus: Focus,
+  /// Whether profiler execution counts are overlaid on disassembly and memory.
+  pub heatmap: bool,
+  pub memory: MemoryView,
+  pub registers: RegistersView,
+  pub sprites: SpritesView,
+  pub disassembly: DisassemblyView,
+  pub display: Display,
+  pub console: ConsoleView,
+  pub browser: BrowserView,
+  pub fault: FaultView,
+  /// Paces execution and holds the speed controls.
+  pub scheduler: Scheduler,
+  /// Keys of the running program that arrow keys, `z` and `x` are mapped to.
+  pub key_hints: KeyHints,
+  /// Whether colours of the loaded program replace the theme's.
+  pub rom_colors: bool,
 
+  /// Whether the program is reloaded when its file changes.
+  pub watch: bool,
+  /// Whether reloading the program keeps breakpoints and symbols.
+  pub keep_debug: bool,
+  /// Symbol file read again when the program is reloaded.
+  pub symbols_path: Option<PathBuf>,
+
+  /// Theme before colours of a program were applied.
+  base_theme: Option<Theme>,
+  /// The running program and the file it was loaded from.
+  program: Option<(PathBuf, Rom)>,
+  watcher: Option<Watcher>,
   keys: UiKeys,
   terminal: Terminal<CrosstermBackend<Stdout>>,
+  /// Whether the interface changed since it was last drawn, other than by the machine
+  /// changing or the terminal being resized.
+  redraw: bool,
+  /// Machine generation and terminal size the interface was last drawn with.
+  drawn: Option<(u64, Rect)>,
+  last_draw: Instant,
 }
 
 impl Ui {
@@ -46,63 +129,449 @@ impl Ui {
     }));
 
     Ok(Self {
-      step: false,
+      step: None,
       paused: false,
+      theme: Theme::default(),
+      focus: Focus::Game,
+      heatmap: false,
+      memory: MemoryView::default(),
+      registers: RegistersView::default(),
+      sprites: SpritesView::default(),
+      disassembly: DisassemblyView::default(),
+      display: Display::default(),
+      console: ConsoleView::default(),
+      browser: BrowserView::default(),
+      fault: FaultView::default(),
+      scheduler: Scheduler::new(),
+      key_hints: KeyHints::default(),
+      rom_colors: true,
+      watch: false,
+      keep_debug: false,
+      symbols_path: None,
+      base_theme: None,
+      program: None,
+      watcher: None,
       keys: UiKeys::new(),
       terminal,
+      redraw: true,
+      drawn: None,
+      last_draw: Instant::now(),
     })
   }
 
+  /// Handles breaks, faults and input, and redraws the interface if anything changed
+  /// since it was last drawn, at most 60 times per second.
   pub fn update(&mut self, vm: &mut Vm) -> Result<()> {
-    self.update_ui(vm)?;
+    if let Some(brk) = vm.take_break() {
+      self.step = None;
+      self.paused = true;
+      self.console.log(brk.to_string());
+      self.redraw = true;
+    }
+
+    if let Some(fault) = vm.take_fault() {
+      self.step = None;
+      self.paused = true;
+      self.console.log_error(fault.to_string());
+      self.fault.show(fault);
+      self.focus = Focus::Fault;
+      self.redraw = true;
+    }
+
+    if self.watcher.as_mut().is_some_and(Watcher::changed) {
+      if let Err(err) = self.reload(vm) {
+        self.console.log(format!("{err:#}"));
+      }
+
+      self.redraw = true;
+    }
+
+    self.redraw |= vm.take_dirty_rows() != 0;
+    self.redraw |= self.display.update(vm);
+
+    if self.last_draw.elapsed() >= FRAME_TIME {
+      let drawn = (vm.generation(), self.terminal.size()?);
+      if self.redraw || self.drawn != Some(drawn) {
+        self.update_ui(vm)?;
+        self.redraw = false;
+        self.drawn = Some(drawn);
+        self.last_draw = Instant::now();
+      }
+    }
+
     self.update_keys(vm)?;
 
     Ok(())
   }
 
+  /// Logs why a step stopped unless it simply finished.
+  pub fn stepped(&mut self, brk: Option<Break>) {
+    self.redraw = true;
+
+    match brk {
+      None | Some(Break::Until(_)) => {}
+      Some(brk) => self.console.log(brk.to_string()),
+    }
+  }
+
+  /// Sets the program running in the virtual machine, applying its key mapping and
+  /// colours and watching `path` for changes if [Ui::watch] is set.
+  pub fn set_program(&mut self, path: PathBuf, rom: Rom) {
+    self.apply_rom(&rom);
+    self.watcher = self.watch.then(|| Watcher::new(&path));
+    self.program = Some((path, rom));
+  }
+
+  /// Applies the key mapping and colours of `rom` and logs its title.
+  fn apply_rom(&mut self, rom: &Rom) {
+    self.key_hints = rom.config.keys;
+
+    if self.rom_colors {
+      let base = *self.base_theme.get_or_insert(self.theme);
+      let support = base.support;
+      let palette = rom.config.palette;
+
+      self.theme.fg = palette.fg.map_or(base.fg, |fg| support.convert(fg));
+      self.theme.bg = palette.bg.map_or(base.bg, |bg| support.convert(bg));
+    }
+
+    if let Some(describe) = rom.describe() {
+      self.console.log(format!("Loaded {describe}"));
+    }
+  }
+
+  /// Displays the ROM browser listing `dir` and focuses it.
+  pub fn browse(&mut self, dir: impl Into<PathBuf>) {
+    match self.browser.open(dir.into()) {
+      Ok(()) => {
+        self.browser.visible = true;
+        self.focus = Focus::Browser;
+      }
+      Err(err) => self.console.log(format!("{err:#}")),
+    }
+  }
+
+  /// Loads `rom` from `path` into `vm` and hides the browser.
+  fn load(&mut self, vm: &mut Vm, path: PathBuf, rom: Rom) -> Result<()> {
+    rom.load(vm)?;
+    self
+      .console
+      .log(format!("Loaded {} bytes", rom.program.len()));
+
+    self.set_program(path, rom);
+    self.step = None;
+    self.browser.visible = false;
+    self.fault.visible = false;
+    self.focus = Focus::Game;
+    Ok(())
+  }
+
+  /// Restarts the program, keeping breakpoints and symbols.
+  fn reset(&mut self, vm: &mut Vm) -> Result<()> {
+    let (_, rom) = self
+      .program
+      .as_ref()
+      .ok_or_else(|| eyre!("No program to reset"))?;
+
+    vm.load_program(&rom.program)?;
+    self.step = None;
+    self.close_fault();
+    self.console.log("Reset");
+    Ok(())
+  }
+
+  /// Loads the program from its file again, keeping breakpoints and symbols if
+  /// [Ui::keep_debug] is set.
+  fn reload(&mut self, vm: &mut Vm) -> Result<()> {
+    let (path, _) = self
+      .program
+      .as_ref()
+      .ok_or_else(|| eyre!("No program to reload"))?;
+
+    let path = path.clone();
+    let mut rom = Rom::from_file(&path)?;
+    rom.apply_db(self.browser.db());
+
+    let breakpoints = std::mem::take(&mut vm.breakpoints);
+    let symbols = std::mem::take(&mut vm.symbols);
+    rom.load(vm)?;
+
+    if self.keep_debug {
+      vm.breakpoints = breakpoints;
+      vm.symbols = symbols;
+    } else if let Some(symbols) = &self.symbols_path {
+      vm.symbols = Symbols::from_file(symbols)?;
+    }
+
+    self.console.log(format!("Reloaded `{}`", path.display()));
+    self.set_program(path, rom);
+    self.step = None;
+    self.close_fault();
+    Ok(())
+  }
+
+  /// Hides the fault panel, returning focus to the game if the panel had it.
+  fn close_fault(&mut self) {
+    self.fault.visible = false;
+    if self.focus == Focus::Fault {
+      self.focus = Focus::Game;
+    }
+  }
+
   fn update_ui(&mut self, vm: &mut Vm) -> Result<()> {
     self.terminal.draw(|ui| {
+      let screen = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+          [
+            Constraint::Min(0),
+            Constraint::Length(console::HEIGHT),
+            Constraint::Length(1),
+          ]
+          .as_ref(),
+        )
+        .split(ui.size());
+
+      let console_focused = self.focus == Focus::Console;
+      self
+        .console
+        .render(&self.theme, console_focused, screen[1], ui);
+
+      let paused = self.paused;
+      debug::status(vm, &self.scheduler, paused, &self.theme, screen[2], ui);
+
+      if self.browser.visible {
+        let browser_focused = self.focus == Focus::Browser;
+        self
+          .browser
+          .render(&self.theme, browser_focused, screen[0], ui);
+
+        if self.fault.visible {
+          let fault_focused = self.focus == Focus::Fault;
+          self
+            .fault
+            .render(vm, &self.theme, fault_focused, screen[0], ui);
+        }
+
+        return;
+      }
+
       let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-        .split(ui.size());
+        .split(screen[0]);
+
+      let game = if self.sprites.visible {
+        let game = Layout::default()
+          .direction(Direction::Vertical)
+          .constraints([Constraint::Min(0), Constraint::Length(sprites::HEIGHT)].as_ref())
+          .split(chunks[0]);
+
+        let sprites_focused = self.focus == Focus::Sprites;
+        self
+          .sprites
+          .render(vm, &self.theme, sprites_focused, game[1], ui);
 
-      ui.render_widget(Game::new(&vm.vram), chunks[0]);
+        game[0]
+      } else {
+        chunks[0]
+      };
+
+      let game_widget =
+        Game::from_framebuffer(self.display.framebuffer()).theme(self.theme);
+      ui.render_widget(game_widget, game);
 
       let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(6), Constraint::Percentage(100)].as_ref())
+        .constraints(
+          [
+            Constraint::Length(registers::HEIGHT),
+            Constraint::Percentage(100),
+          ]
+          .as_ref(),
+        )
         .split(chunks[1]);
 
       let top = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Length(48), Constraint::Min(0)].as_ref())
+        .constraints([Constraint::Length(registers::WIDTH), Constraint::Min(0)].as_ref())
         .split(chunks[0]);
 
-      debug::registers(vm, top[0], ui);
-      debug::keys(vm, top[1], ui);
+      let registers_focused = self.focus == Focus::Registers;
+
+      self
+        .registers
+        .render(vm, &self.theme, registers_focused, top[0], ui);
+      debug::keys(vm, &self.theme, top[1], ui);
 
       let bottom = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Min(0), Constraint::Length(20)].as_ref())
+        .constraints(
+          [
+            Constraint::Min(0),
+            Constraint::Length(memory::WIDTH),
+            Constraint::Length(24),
+          ]
+          .as_ref(),
+        )
         .split(chunks[1]);
 
-      debug::disassembly(vm, bottom[0], ui);
-      debug::stack(vm, bottom[1], ui);
+      let memory_focused = self.focus == Focus::Memory;
+
+      let disassembly_focused = self.focus == Focus::Disassembly;
+      self.disassembly.render(
+        vm,
+        &self.theme,
+        disassembly_focused,
+        self.heatmap,
+        bottom[0],
+        ui,
+      );
+      self
+        .memory
+        .render(vm, &self.theme, memory_focused, self.heatmap, bottom[1], ui);
+      debug::stack(vm, &self.theme, bottom[2], ui);
+
+      if self.fault.visible {
+        let fault_focused = self.focus == Focus::Fault;
+        self.fault.render(vm, &self.theme, fault_focused, game, ui);
+      }
     })?;
 
     Ok(())
   }
 
   fn update_keys(&mut self, vm: &mut Vm) -> Result<()> {
-    if self.step {
-      self.paused = true;
+    for mouse in self.keys.mouse() {
+      self.redraw = true;
+
+      if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
+        if self.registers.handle_click(mouse.column, mouse.row) {
+          self.focus = Focus::Registers;
+        }
+      }
     }
 
+    let mut chosen = None;
+    let mut step = None;
+    let mut reset = false;
+    let mut reload = false;
+
     for key in &mut self.keys {
+      self.redraw = true;
+
       let is_c = key.code == KeyCode::Char('c') || key.code == KeyCode::Char('C');
       let is_ctrl_c = is_c && key.modifiers.contains(KeyModifiers::CONTROL);
 
+      if key.is_pressed && self.focus == Focus::Console && !is_ctrl_c {
+        if key.code == KeyCode::Esc {
+          self.focus = Focus::Game;
+        } else if let Some(Some(paused)) = self.console.handle_key(vm, key.code) {
+          self.step = None;
+          self.paused = paused;
+        }
+
+        continue;
+      }
+
+      if key.is_pressed && self.focus == Focus::Browser && !is_ctrl_c {
+        if key.code == KeyCode::Esc {
+          self.browser.visible = false;
+          self.focus = Focus::Game;
+          continue;
+        }
+
+        match self.browser.handle_key(key.code) {
+          Ok(Some(program)) => chosen = Some(program),
+          Ok(None) => {}
+          Err(err) => self.console.log(format!("{err:#}")),
+        }
+
+        continue;
+      }
+
+      if key.is_pressed && self.focus == Focus::Fault {
+        match key.code {
+          KeyCode::Esc => {
+            self.fault.visible = false;
+            self.focus = Focus::Game;
+            continue;
+          }
+          KeyCode::Char('d') => {
+            match self.fault.write_dump(vm) {
+              Ok(()) => self.console.log(format!(
+                "Wrote crash dump `{}`",
+                self.fault.dump_path.display()
+              )),
+              Err(err) => self.console.log_error(format!("{err:#}")),
+            }
+
+            continue;
+          }
+          _ => {}
+        }
+      }
+
+      if key.is_pressed && key.code == KeyCode::Char(':') {
+        self.focus = Focus::Console;
+        continue;
+      }
+
+      if key.is_pressed && key.code == KeyCode::Tab {
+        self.focus = match self.focus {
+          Focus::Game => Focus::Registers,
+          Focus::Registers => Focus::Memory,
+          Focus::Memory => Focus::Disassembly,
+          Focus::Disassembly if self.sprites.visible => Focus::Sprites,
+          Focus::Disassembly
+          | Focus::Sprites
+          | Focus::Console
+          | Focus::Browser
+          | Focus::Fault => Focus::Game,
+        };
+
+        continue;
+      }
+
+      let consumed = key.is_pressed
+        && match self.focus {
+          Focus::Game | Focus::Console | Focus::Browser | Focus::Fault => false,
+          Focus::Registers => self.registers.handle_key(vm, key.code, self.paused),
+          Focus::Memory if key.code == KeyCode::Char('v') => {
+            self.sprites.show(self.memory.cursor as u16);
+            self.sprites.visible = true;
+            true
+          }
+          Focus::Memory => self.memory.handle_key(vm, key.code, self.paused),
+          Focus::Disassembly if key.code == KeyCode::Enter => {
+            if let Some(addr) = self.disassembly.cursor {
+              step = Some(Step::To(addr));
+            }
+
+            true
+          }
+          Focus::Disassembly => self.disassembly.handle_key(vm, key.code),
+          Focus::Sprites => self.sprites.handle_key(vm, key.code),
+        };
+
+      if consumed {
+        continue;
+      }
+
+      if key.is_repeat {
+        continue;
+      }
+
+      if let Some(hint) = key_hint(&self.key_hints, key.code) {
+        if key.is_pressed {
+          vm.signal_key_down(hint)
+        } else {
+          vm.signal_key_up(hint)
+        }
+
+        continue;
+      }
+
       match key.code {
         KeyCode::Char('0') => {
           if key.is_pressed {
@@ -218,12 +687,47 @@ impl Ui {
         }
 
         KeyCode::Char(' ') if key.is_pressed => {
-          self.step = false;
+          self.step = None;
           self.paused = !self.paused;
         }
-        KeyCode::Enter if key.is_pressed => {
-          self.step = true;
-          self.paused = false;
+        KeyCode::Enter if key.is_pressed => step = Some(Step::Insn),
+        KeyCode::F(8) if key.is_pressed => step = Some(Step::Frame),
+        KeyCode::F(9) if key.is_pressed => step = Some(Step::Over),
+        KeyCode::F(10) if key.is_pressed => step = Some(Step::Out),
+        KeyCode::F(2) if key.is_pressed => match self.browser.show() {
+          Ok(()) => self.focus = Focus::Browser,
+          Err(err) => self.console.log(format!("{err:#}")),
+        },
+        KeyCode::F(3) if key.is_pressed => self.heatmap = !self.heatmap,
+        KeyCode::F(5) if key.is_pressed => {
+          self.display.filter = self.display.filter.next();
+          self
+            .console
+            .log(format!("Display filter: {}", self.display.filter.name()));
+        }
+        KeyCode::Char('`') => self.scheduler.fast_forward = key.is_pressed,
+        KeyCode::Char('-') if key.is_pressed => {
+          vm.tickrate = scheduler::step(&TICKRATES, vm.tickrate, false);
+        }
+        KeyCode::Char('=') | KeyCode::Char('+') if key.is_pressed => {
+          vm.tickrate = scheduler::step(&TICKRATES, vm.tickrate, true);
+        }
+        KeyCode::Char(',') if key.is_pressed => {
+          let slowdown = self.scheduler.slowdown;
+          self.scheduler.slowdown = scheduler::step(&SLOWDOWNS, slowdown, false);
+        }
+        KeyCode::Char('.') if key.is_pressed => {
+          let slowdown = self.scheduler.slowdown;
+          self.scheduler.slowdown = scheduler::step(&SLOWDOWNS, slowdown, true);
+        }
+        KeyCode::F(6) if key.is_pressed => reset = true,
+        KeyCode::F(7) if key.is_pressed => reload = true,
+        KeyCode::F(4) if key.is_pressed => {
+          self.sprites.visible = !self.sprites.visible;
+
+          if !self.sprites.visible && self.focus == Focus::Sprites {
+            self.focus = Focus::Game;
+          }
         }
 
         code if code == KeyCode::Esc || is_ctrl_c => {
@@ -234,10 +738,44 @@ impl Ui {
       }
     }
 
+    if step.is_some() {
+      self.step = step;
+      self.paused = true;
+    }
+
+    let result = match (reset, reload) {
+      (_, true) => self.reload(vm),
+      (true, false) => self.reset(vm),
+      (false, false) => Ok(()),
+    };
+
+    if let Err(err) = result {
+      self.console.log(format!("{err:#}"));
+    }
+
+    if let Some((path, rom)) = chosen {
+      if let Err(err) = self.load(vm, path, rom) {
+        self.console.log(format!("{err:#}"));
+      }
+    }
+
     Ok(())
   }
 }
 
+/// Gets the program key mapped to `code` by `hints`.
+fn key_hint(hints: &KeyHints, code: KeyCode) -> Option<VmKey> {
+  match code {
+    KeyCode::Up => hints.up,
+    KeyCode::Down => hints.down,
+    KeyCode::Left => hints.left,
+    KeyCode::Right => hints.right,
+    KeyCode::Char('z') => hints.a,
+    KeyCode::Char('x') => hints.b,
+    _ => None,
+  }
+}
+
 fn cleanup() {
   let mut stdout = io::stdout();
   terminal::disable_raw_mode().ok();
@@ -0,0 +1,204 @@
+//! Editable registers widget.
+
+use super::theme::Theme;
+use crate::vm::{reg::VmReg, Vm};
+use crossterm::event::KeyCode;
+use tui::{
+  backend::Backend,
+  layout::{Alignment, Rect},
+  style::{Modifier, Style},
+  text::{Span, Spans},
+  widgets::{Block, Borders, Paragraph},
+  Frame,
+};
+
+/// Width of the registers widget including borders.
+pub const WIDTH: u16 = 48;
+/// Height of the registers widget including borders.
+pub const HEIGHT: u16 = 7;
+
+/// Registers displayed on each row of the widget.
+const ROWS: [&[VmReg]; 5] = [
+  &[
+    VmReg::V(0x0),
+    VmReg::V(0x1),
+    VmReg::V(0x2),
+    VmReg::V(0x3),
+    VmReg::Pc,
+  ],
+  &[
+    VmReg::V(0x4),
+    VmReg::V(0x5),
+    VmReg::V(0x6),
+    VmReg::V(0x7),
+    VmReg::I,
+  ],
+  &[
+    VmReg::V(0x8),
+    VmReg::V(0x9),
+    VmReg::V(0xa),
+    VmReg::V(0xb),
+    VmReg::Dt,
+  ],
+  &[
+    VmReg::V(0xc),
+    VmReg::V(0xd),
+    VmReg::V(0xe),
+    VmReg::V(0xf),
+    VmReg::St,
+  ],
+  &[VmReg::Sp, VmReg::Keys],
+];
+
+/// Register values that can be selected and edited while paused.
+#[derive(Default)]
+pub struct RegistersView {
+  /// Index of the selected register in [VmReg::ALL].
+  selected: usize,
+  /// Hex digits typed for the selected register.
+  input: Option<String>,
+  /// Screen position and width of each register rendered by the last render.
+  cells: Vec<(VmReg, u16, u16, u16)>,
+}
+
+impl RegistersView {
+  /// Gets the selected register.
+  pub fn selected(&self) -> VmReg {
+    VmReg::ALL[self.selected]
+  }
+
+  /// Selects `reg`, discarding any partially typed value.
+  pub fn select(&mut self, reg: VmReg) {
+    self.selected = VmReg::ALL.iter().position(|r| *r == reg).unwrap_or(0);
+    self.input = None;
+  }
+
+  /// Handles a key press, returns `true` if the key was consumed.
+  ///
+  /// Navigation keys are always consumed, value input is only consumed when `editable`.
+  pub fn handle_key(&mut self, vm: &mut Vm, code: KeyCode, editable: bool) -> bool {
+    let (row, col) = self.position();
+
+    match code {
+      KeyCode::Left => self.select_at(row, col.saturating_sub(1)),
+      KeyCode::Right => self.select_at(row, col + 1),
+      KeyCode::Up => self.select_at(row.saturating_sub(1), col),
+      KeyCode::Down => self.select_at((row + 1).min(ROWS.len() - 1), col),
+      KeyCode::Char(c) if editable && c.is_ascii_hexdigit() => {
+        let width = self.selected().width();
+        let input = self.input.get_or_insert_with(String::new);
+
+        if input.len() < width {
+          input.push(c);
+        }
+      }
+      KeyCode::Backspace if self.input.is_some() => {
+        if let Some(input) = &mut self.input {
+          input.pop();
+        }
+      }
+      KeyCode::Enter if self.input.is_some() => {
+        let input = self.input.take().unwrap_or_default();
+
+        if let Ok(val) = u16::from_str_radix(&input, 16) {
+          vm.write_reg(self.selected(), val);
+        }
+      }
+      KeyCode::Esc if self.input.is_some() => self.input = None,
+      _ => return false,
+    }
+
+    true
+  }
+
+  /// Handles a mouse click at screen position `x`, `y`, returns `true` if a register was
+  /// clicked.
+  pub fn handle_click(&mut self, x: u16, y: u16) -> bool {
+    let reg = self
+      .cells
+      .iter()
+      .find(|(_, cx, cy, w)| *cy == y && (*cx..*cx + *w).contains(&x))
+      .map(|(reg, ..)| *reg);
+
+    match reg {
+      Some(reg) => {
+        self.select(reg);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Renders register values.
+  pub fn render<B: Backend>(
+    &mut self,
+    vm: &Vm,
+    theme: &Theme,
+    focused: bool,
+    area: Rect,
+    ui: &mut Frame<B>,
+  ) {
+    let mut spans = Vec::new();
+    self.cells.clear();
+
+    for (y, regs) in ROWS.iter().enumerate() {
+      let mut line = Vec::new();
+      let mut x = area.x + 1;
+
+      for &reg in regs.iter() {
+        let name = format!("{:>2}: ", reg.name());
+        let is_selected = focused && reg == self.selected();
+        let val = match &self.input {
+          Some(input) if is_selected => format!("{input:_<0$}", reg.width()),
+          _ => format!("{:01$x}", vm.read_reg(reg), reg.width()),
+        };
+
+        let val_style = if is_selected {
+          Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+          Style::default()
+        };
+
+        let width = (name.len() + 2 + val.len()) as u16;
+        self.cells.push((reg, x, area.y + 1 + y as u16, width));
+        x += width + 1;
+
+        line.push(Span::styled(name, Style::default().fg(theme.label)));
+        line.push(Span::from("0x"));
+        line.push(Span::styled(val, val_style));
+        line.push(Span::from(" "));
+      }
+
+      spans.push(Spans::from(line));
+    }
+
+    let border = if focused { theme.accent } else { theme.label };
+
+    ui.render_widget(
+      Paragraph::new(spans).block(
+        Block::default()
+          .title(" registers ")
+          .title_alignment(Alignment::Center)
+          .borders(Borders::ALL)
+          .border_style(Style::default().fg(border)),
+      ),
+      area,
+    );
+  }
+
+  /// Gets the row and column of the selected register.
+  fn position(&self) -> (usize, usize) {
+    let reg = self.selected();
+
+    ROWS
+      .iter()
+      .enumerate()
+      .find_map(|(row, regs)| Some((row, regs.iter().position(|r| *r == reg)?)))
+      .unwrap_or((0, 0))
+  }
+
+  fn select_at(&mut self, row: usize, col: usize) {
+    let regs = ROWS[row];
+    self.select(regs[col.min(regs.len() - 1)]);
+  }
+}
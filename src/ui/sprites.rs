@@ -0,0 +1,177 @@
+//! Sprite viewer widget.
+
+use super::{game::Game, theme::Theme};
+use crate::{
+  insn::{insns::DrwInsn, Insn},
+  vm::Vm,
+};
+use crossterm::event::KeyCode;
+use tui::{
+  backend::Backend,
+  layout::{Alignment, Constraint, Direction, Layout, Rect},
+  style::Style,
+  widgets::{Block, Borders},
+  Frame,
+};
+
+/// Height of the sprite widget including borders.
+pub const HEIGHT: u16 = 12;
+/// Width of the sprite preview including borders.
+const PREVIEW_WIDTH: u16 = 12;
+/// Largest number of bytes drawn by a single `DRW`.
+const MAX_LEN: u8 = 15;
+/// Width of a sprite in pixels.
+const SPRITE_WIDTH: usize = 8;
+/// Pixels between sprites in the sheet.
+const GAP: usize = 1;
+
+/// Decodes memory as sprites, both a single sprite and a sheet of consecutive sprites.
+pub struct SpritesView {
+  /// Whether the widget is displayed.
+  pub visible: bool,
+  /// Address of the previewed sprite, follows `I` when `None`.
+  pub addr: Option<u16>,
+  /// Number of bytes per sprite when not drawing from `I`.
+  len: u8,
+  /// Address of the first sprite in the sheet.
+  sheet: u16,
+  /// Number of sprites per row and number of rows displayed by the last render.
+  sheet_size: (usize, usize),
+}
+
+impl Default for SpritesView {
+  fn default() -> Self {
+    Self {
+      visible: false,
+      addr: None,
+      len: 8,
+      sheet: 0,
+      sheet_size: (1, 1),
+    }
+  }
+}
+
+impl SpritesView {
+  /// Handles a key press, returns `true` if the key was consumed.
+  pub fn handle_key(&mut self, vm: &Vm, code: KeyCode) -> bool {
+    let max = vm.mem.len().saturating_sub(1) as u16;
+    let (cols, rows) = self.sheet_size;
+    let row = (cols * self.len as usize) as u16;
+
+    match code {
+      KeyCode::Left => self.addr = Some(self.addr(vm).saturating_sub(1)),
+      KeyCode::Right => self.addr = Some((self.addr(vm) + 1).min(max)),
+      KeyCode::Up => self.sheet = self.sheet.saturating_sub(row),
+      KeyCode::Down => self.sheet = (self.sheet + row).min(max),
+      KeyCode::PageUp => self.sheet = self.sheet.saturating_sub(row * rows as u16),
+      KeyCode::PageDown => self.sheet = (self.sheet + row * rows as u16).min(max),
+      KeyCode::Char('+') | KeyCode::Char('=') => self.len = (self.len + 1).min(MAX_LEN),
+      KeyCode::Char('-') => self.len = self.len.saturating_sub(1).max(1),
+      KeyCode::Char('i') | KeyCode::Char('I') => self.addr = None,
+      KeyCode::Char('s') | KeyCode::Char('S') => self.sheet = self.addr(vm),
+      _ => return false,
+    }
+
+    true
+  }
+
+  /// Previews the sprite at `addr` and starts the sheet at it.
+  pub fn show(&mut self, addr: u16) {
+    self.addr = Some(addr);
+    self.sheet = addr;
+  }
+
+  /// Gets the address of the previewed sprite.
+  pub fn addr(&self, vm: &Vm) -> u16 {
+    self.addr.unwrap_or(vm.reg_i)
+  }
+
+  /// Gets the number of bytes of the previewed sprite, the height of the `DRW` at the
+  /// program counter when following `I`.
+  pub fn len(&self, vm: &Vm) -> u8 {
+    match (self.addr, vm.insn_at_pc()) {
+      (None, Some(Insn::Drw(DrwInsn { n, .. }))) if n > 0 => n,
+      _ => self.len,
+    }
+  }
+
+  /// Renders the previewed sprite and the sheet.
+  pub fn render<B: Backend>(
+    &mut self,
+    vm: &Vm,
+    theme: &Theme,
+    focused: bool,
+    area: Rect,
+    ui: &mut Frame<B>,
+  ) {
+    let chunks = Layout::default()
+      .direction(Direction::Horizontal)
+      .constraints([Constraint::Length(PREVIEW_WIDTH), Constraint::Min(0)].as_ref())
+      .split(area);
+
+    let border = Style::default().fg(if focused { theme.accent } else { theme.label });
+    let addr = self.addr(vm);
+    let len = self.len(vm);
+
+    let block = Block::default()
+      .title(format!(" {addr:03x}:{len} "))
+      .title_alignment(Alignment::Center)
+      .borders(Borders::ALL)
+      .border_style(border);
+
+    let inner = block.inner(chunks[0]);
+    let preview = sprite_rows(vm, addr, len as usize);
+    ui.render_widget(block, chunks[0]);
+    ui.render_widget(Game::new(&preview).width(SPRITE_WIDTH).theme(*theme), inner);
+
+    let block = Block::default()
+      .title(format!(" sheet {:03x} ", self.sheet))
+      .title_alignment(Alignment::Center)
+      .borders(Borders::ALL)
+      .border_style(border);
+
+    let inner = block.inner(chunks[1]);
+    ui.render_widget(block, chunks[1]);
+
+    if inner.width > 0 && inner.height > 0 {
+      let (sheet, width) = self.sheet_rows(vm, inner);
+      ui.render_widget(Game::new(&sheet).width(width).theme(*theme), inner);
+    }
+  }
+
+  /// Lays out as many sprites of the preview length as fit in `area` at two pixels per
+  /// cell, returns the pixel rows and width of the sheet.
+  fn sheet_rows(&mut self, vm: &Vm, area: Rect) -> (Vec<u64>, usize) {
+    let len = self.len as usize;
+    let cell_width = SPRITE_WIDTH + GAP;
+    let cell_height = len + GAP;
+
+    let cols = (area.width as usize * 2 + GAP) / cell_width;
+    let cols = cols.clamp(1, (u64::BITS as usize + GAP) / cell_width);
+    let rows = ((area.height as usize * 2 + GAP) / cell_height).max(1);
+    self.sheet_size = (cols, rows);
+
+    let mut sheet = vec![0; rows * cell_height - GAP];
+    for row in 0..rows {
+      for col in 0..cols {
+        let addr = self.sheet as usize + (row * cols + col) * len;
+        let shift = col * cell_width;
+
+        for (y, byte) in sprite_rows(vm, addr as u16, len).into_iter().enumerate() {
+          sheet[row * cell_height + y] |= byte >> shift;
+        }
+      }
+    }
+
+    (sheet, cols * cell_width - GAP)
+  }
+}
+
+/// Gets `len` bytes at `addr` as pixel rows with the sprite in the most significant bits,
+/// bytes outside of memory are blank.
+fn sprite_rows(vm: &Vm, addr: u16, len: usize) -> Vec<u64> {
+  (0..len)
+    .map(|i| vm.mem.get(addr as usize + i).copied().unwrap_or(0))
+    .map(|byte| (byte as u64) << (u64::BITS as usize - SPRITE_WIDTH))
+    .collect()
+}
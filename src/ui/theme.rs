@@ -0,0 +1,308 @@
+//! Colour themes.
+
+use std::env;
+use tui::style::Color;
+
+/// A colour theme used by the game and debug widgets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+  /// Colour of lit pixels.
+  pub fg: Color,
+  /// Colour of unlit pixels.
+  pub bg: Color,
+  /// Colour of labels such as register names and addresses.
+  pub label: Color,
+  /// Colour of highlighted entries such as the program counter and stack pointer.
+  pub accent: Color,
+  /// Colour of raw values such as instruction bytes.
+  pub value: Color,
+  /// Colour of error messages.
+  pub error: Color,
+  /// Colours of the profiler heatmap, from coldest to hottest.
+  pub heat: [Color; 4],
+  /// Colours the terminal is able to display, used for colours mixed at runtime.
+  pub support: ColorSupport,
+}
+
+impl Theme {
+  /// Green on black, the default theme.
+  pub const CLASSIC: Theme = Theme {
+    fg: Color::Green,
+    bg: Color::Black,
+    label: Color::Gray,
+    accent: Color::Blue,
+    value: Color::Yellow,
+    error: Color::Red,
+    heat: [Color::Blue, Color::Cyan, Color::Yellow, Color::Red],
+    support: ColorSupport::TrueColor,
+  };
+
+  /// Amber phosphor on black.
+  pub const AMBER: Theme = Theme {
+    fg: Color::Rgb(0xff, 0xb0, 0x00),
+    bg: Color::Black,
+    label: Color::Rgb(0xa0, 0x70, 0x00),
+    accent: Color::Rgb(0xff, 0xd0, 0x60),
+    value: Color::Rgb(0xff, 0xb0, 0x00),
+    error: Color::Red,
+    heat: [
+      Color::Rgb(0x40, 0x20, 0x00),
+      Color::Rgb(0x80, 0x40, 0x00),
+      Color::Rgb(0xc0, 0x60, 0x00),
+      Color::Rgb(0xff, 0x80, 0x00),
+    ],
+    support: ColorSupport::TrueColor,
+  };
+
+  /// White on black.
+  pub const WHITE: Theme = Theme {
+    fg: Color::White,
+    bg: Color::Black,
+    label: Color::Gray,
+    accent: Color::White,
+    value: Color::Gray,
+    error: Color::Red,
+    heat: [Color::DarkGray, Color::Gray, Color::White, Color::Red],
+    support: ColorSupport::TrueColor,
+  };
+
+  /// Maximum contrast between every element.
+  pub const HIGH_CONTRAST: Theme = Theme {
+    fg: Color::Rgb(0xff, 0xff, 0xff),
+    bg: Color::Rgb(0x00, 0x00, 0x00),
+    label: Color::Rgb(0xff, 0xff, 0xff),
+    accent: Color::Rgb(0xff, 0xff, 0x00),
+    value: Color::Rgb(0x00, 0xff, 0xff),
+    error: Color::Rgb(0xff, 0x00, 0x00),
+    heat: [
+      Color::Rgb(0x00, 0x00, 0xff),
+      Color::Rgb(0x00, 0xff, 0x00),
+      Color::Rgb(0xff, 0xff, 0x00),
+      Color::Rgb(0xff, 0x00, 0x00),
+    ],
+    support: ColorSupport::TrueColor,
+  };
+
+  /// Colours from the Okabe-Ito palette, distinguishable with common colour blindness.
+  pub const COLOUR_BLIND: Theme = Theme {
+    fg: Color::Rgb(0x56, 0xb4, 0xe9),
+    bg: Color::Black,
+    label: Color::Gray,
+    accent: Color::Rgb(0xe6, 0x9f, 0x00),
+    value: Color::Rgb(0xf0, 0xe4, 0x42),
+    error: Color::Rgb(0xd5, 0x5e, 0x00),
+    heat: [
+      Color::Rgb(0x00, 0x72, 0xb2),
+      Color::Rgb(0x00, 0x9e, 0x73),
+      Color::Rgb(0xf0, 0xe4, 0x42),
+      Color::Rgb(0xe6, 0x9f, 0x00),
+    ],
+    support: ColorSupport::TrueColor,
+  };
+
+  /// Gets a theme by name.
+  pub fn named(name: &str) -> Option<Self> {
+    match name.to_ascii_lowercase().as_str() {
+      "classic" | "green" => Some(Self::CLASSIC),
+      "amber" => Some(Self::AMBER),
+      "white" => Some(Self::WHITE),
+      "high-contrast" | "contrast" => Some(Self::HIGH_CONTRAST),
+      "colour-blind" | "color-blind" | "cb" => Some(Self::COLOUR_BLIND),
+      _ => None,
+    }
+  }
+
+  /// Gets the heatmap colour for `heat` from 0 to 1, or `None` when `heat` is 0.
+  pub fn heat_color(&self, heat: f64) -> Option<Color> {
+    if heat <= 0. {
+      return None;
+    }
+
+    let idx = (heat * self.heat.len() as f64).ceil() as usize;
+    Some(self.heat[idx.clamp(1, self.heat.len()) - 1])
+  }
+
+  /// Converts every colour in the theme to one the terminal can display.
+  pub fn fallback(self, support: ColorSupport) -> Self {
+    Self {
+      fg: support.convert(self.fg),
+      bg: support.convert(self.bg),
+      label: support.convert(self.label),
+      accent: support.convert(self.accent),
+      value: support.convert(self.value),
+      error: support.convert(self.error),
+      heat: self.heat.map(|color| support.convert(color)),
+      support,
+    }
+  }
+
+  /// Mixes the background and pixel colours, `level` is 0 for the background and 1 for
+  /// the pixel colour.
+  pub fn fade(&self, level: f32) -> Color {
+    let (Some(fg), Some(bg)) = (to_rgb(self.fg), to_rgb(self.bg)) else {
+      return if level >= 0.5 { self.fg } else { self.bg };
+    };
+
+    let mix =
+      |fg: u8, bg: u8| (bg as f32 + (fg as f32 - bg as f32) * level.clamp(0., 1.)) as u8;
+    let color = Color::Rgb(mix(fg.0, bg.0), mix(fg.1, bg.1), mix(fg.2, bg.2));
+    self.support.convert(color)
+  }
+}
+
+impl Default for Theme {
+  fn default() -> Self {
+    Self::CLASSIC
+  }
+}
+
+/// The colours a terminal is able to display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSupport {
+  /// 24-bit colour.
+  TrueColor,
+  /// The xterm 256 colour palette.
+  Ansi256,
+  /// The 16 standard ansi colours.
+  Ansi16,
+}
+
+impl ColorSupport {
+  /// Detects colour support from the `COLORTERM` and `TERM` environment variables.
+  pub fn detect() -> Self {
+    let colorterm = env::var("COLORTERM").unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+      return Self::TrueColor;
+    }
+
+    let term = env::var("TERM").unwrap_or_default();
+    if term.contains("256color") {
+      Self::Ansi256
+    } else {
+      Self::Ansi16
+    }
+  }
+
+  /// Converts a colour to the closest colour the terminal can display.
+  pub fn convert(self, color: Color) -> Color {
+    let (r, g, b) = match (self, color) {
+      (Self::TrueColor, _) => return color,
+      (_, Color::Rgb(r, g, b)) => (r, g, b),
+      (Self::Ansi16, Color::Indexed(i)) => indexed_to_rgb(i),
+      _ => return color,
+    };
+
+    match self {
+      Self::TrueColor => color,
+      Self::Ansi256 => Color::Indexed(rgb_to_indexed(r, g, b)),
+      Self::Ansi16 => rgb_to_ansi16(r, g, b),
+    }
+  }
+}
+
+/// Parses a colour as `#rrggbb`, `rrggbb` or a colour name such as `green`.
+pub fn parse_color(s: &str) -> Option<Color> {
+  let hex = s.strip_prefix('#').unwrap_or(s);
+  if hex.len() == 6 {
+    if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+      return Some(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+    }
+  }
+
+  ANSI16
+    .iter()
+    .map(|(color, _)| *color)
+    .find(|color| format!("{color:?}").eq_ignore_ascii_case(s))
+}
+
+/// Steps of the xterm 6x6x6 colour cube.
+const CUBE: [u8; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+/// Approximate rgb values of the 16 standard ansi colours.
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+  (Color::Black, (0x00, 0x00, 0x00)),
+  (Color::Red, (0x80, 0x00, 0x00)),
+  (Color::Green, (0x00, 0x80, 0x00)),
+  (Color::Yellow, (0x80, 0x80, 0x00)),
+  (Color::Blue, (0x00, 0x00, 0x80)),
+  (Color::Magenta, (0x80, 0x00, 0x80)),
+  (Color::Cyan, (0x00, 0x80, 0x80)),
+  (Color::Gray, (0xc0, 0xc0, 0xc0)),
+  (Color::DarkGray, (0x80, 0x80, 0x80)),
+  (Color::LightRed, (0xff, 0x00, 0x00)),
+  (Color::LightGreen, (0x00, 0xff, 0x00)),
+  (Color::LightYellow, (0xff, 0xff, 0x00)),
+  (Color::LightBlue, (0x00, 0x00, 0xff)),
+  (Color::LightMagenta, (0xff, 0x00, 0xff)),
+  (Color::LightCyan, (0x00, 0xff, 0xff)),
+  (Color::White, (0xff, 0xff, 0xff)),
+];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+  let dr = r1 as i32 - r2 as i32;
+  let dg = g1 as i32 - g2 as i32;
+  let db = b1 as i32 - b2 as i32;
+
+  (dr * dr + dg * dg + db * db) as u32
+}
+
+fn nearest_cube_step(v: u8) -> usize {
+  (0..CUBE.len())
+    .min_by_key(|&i| (CUBE[i] as i32 - v as i32).abs())
+    .unwrap_or(0)
+}
+
+fn rgb_to_indexed(r: u8, g: u8, b: u8) -> u8 {
+  let (ri, gi, bi) = (
+    nearest_cube_step(r),
+    nearest_cube_step(g),
+    nearest_cube_step(b),
+  );
+  let cube = (CUBE[ri], CUBE[gi], CUBE[bi]);
+  let cube_idx = 16 + 36 * ri + 6 * gi + bi;
+
+  // The grayscale ramp is often closer for desaturated colours.
+  let avg = (r as u32 + g as u32 + b as u32) / 3;
+  let gray_step = (avg.saturating_sub(8) / 10).min(23) as u8;
+  let gray_val = 8 + gray_step * 10;
+  let gray = (gray_val, gray_val, gray_val);
+
+  if distance((r, g, b), gray) < distance((r, g, b), cube) {
+    232 + gray_step
+  } else {
+    cube_idx as u8
+  }
+}
+
+fn indexed_to_rgb(i: u8) -> (u8, u8, u8) {
+  match i {
+    0..=15 => ANSI16[i as usize].1,
+    16..=231 => {
+      let i = i as usize - 16;
+      (CUBE[i / 36], CUBE[(i / 6) % 6], CUBE[i % 6])
+    }
+    _ => {
+      let v = 8 + (i - 232) * 10;
+      (v, v, v)
+    }
+  }
+}
+
+fn to_rgb(color: Color) -> Option<(u8, u8, u8)> {
+  match color {
+    Color::Rgb(r, g, b) => Some((r, g, b)),
+    Color::Indexed(i) => Some(indexed_to_rgb(i)),
+    color => ANSI16
+      .iter()
+      .find(|(ansi, _)| *ansi == color)
+      .map(|(_, rgb)| *rgb),
+  }
+}
+
+fn rgb_to_ansi16(r: u8, g: u8, b: u8) -> Color {
+  ANSI16
+    .iter()
+    .min_by_key(|(_, rgb)| distance((r, g, b), *rgb))
+    .map(|(color, _)| *color)
+    .unwrap_or(Color::White)
+}
@@ -0,0 +1,294 @@
+//! Virtual machine code and data coverage.
+
+use super::{Vm, PROG_OFF};
+use crate::insn::Insn;
+use std::{collections::BTreeMap, fmt::Write};
+
+bitflags::bitflags! {
+  /// Ways a memory address was accessed.
+  #[derive(Default)]
+  pub struct Access: u8 {
+    /// An instruction starting at the address was executed.
+    const EXEC = 0b001;
+    /// The address was read as data.
+    const READ = 0b010;
+    /// The address was written to.
+    const WRITE = 0b100;
+  }
+}
+
+/// Outcomes of a conditional skip instruction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Branch {
+  /// Number of times the next instruction was skipped.
+  pub taken: u64,
+  /// Number of times the next instruction was not skipped.
+  pub not_taken: u64,
+}
+
+/// Per address accesses and per branch outcomes.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+  /// Accesses of each address.
+  pub access: Vec<Access>,
+  /// Outcomes of each executed conditional skip instruction by address.
+  pub branches: BTreeMap<u16, Branch>,
+}
+
+/// A line of a coverage report.
+enum Line {
+  /// An instruction that may or may not have been executed.
+  Insn(u16),
+  /// A byte of data.
+  Data(u16),
+}
+
+impl Coverage {
+  /// Creates an empty [Coverage] for a memory of `len` bytes.
+  pub fn new(len: usize) -> Self {
+    Self {
+      access: vec![Access::empty(); len],
+      branches: BTreeMap::new(),
+    }
+  }
+
+  /// Clears every access and branch.
+  pub fn reset(&mut self) {
+    *self = Self::new(self.access.len());
+  }
+
+  /// Gets the accesses of `addr`.
+  pub fn get(&self, addr: usize) -> Access {
+    self.access.get(addr).copied().unwrap_or_default()
+  }
+
+  /// Records execution of the instruction `hi`, `lo` at `pc`, `skipped` is whether the
+  /// following instruction was skipped.
+  pub(crate) fn record_exec(&mut self, pc: u16, hi: u8, lo: u8, skipped: bool) {
+    self.record(pc as usize, Access::EXEC);
+
+    if is_conditional(hi, lo) {
+      let branch = self.branches.entry(pc).or_default();
+      if skipped {
+        branch.taken += 1;
+      } else {
+        branch.not_taken += 1;
+      }
+    }
+  }
+
+  /// Records an access of `addr`.
+  pub(crate) fn record(&mut self, addr: usize, access: Access) {
+    if let Some(flags) = self.access.get_mut(addr) {
+      flags.insert(access);
+    }
+  }
+}
+
+impl Vm {
+  /// Formats coverage of the loaded program in the lcov tracefile format.
+  ///
+  /// Line numbers are instruction addresses, functions are the targets of `CALL`
+  /// instructions and branches are the outcomes of conditional skips.
+  pub fn coverage_lcov(&self, source: &str) -> String {
+    let mut report = format!("TN:\nSF:{source}\n");
+
+    let routines = self
+      .profile
+      .routines
+      .iter()
+      .filter(|(_, stats)| stats.calls > 0)
+      .collect::<Vec<_>>();
+
+    for (&addr, _) in &routines {
+      let _ = writeln!(report, "FN:{addr},{}", self.symbols.describe(addr));
+    }
+
+    for (&addr, stats) in &routines {
+      let _ = writeln!(
+        report,
+        "FNDA:{},{}",
+        stats.calls,
+        self.symbols.describe(addr)
+      );
+    }
+
+    let _ = writeln!(report, "FNF:{}\nFNH:{}", routines.len(), routines.len());
+
+    let (mut found, mut hit) = (0, 0);
+    let (mut branches_found, mut branches_hit) = (0, 0);
+
+    for line in self.coverage_lines() {
+      let Line::Insn(addr) = line else {
+        continue;
+      };
+
+      let count = self.profile.counts[addr as usize];
+      found += 1;
+      hit += (count > 0) as u32;
+
+      let _ = writeln!(report, "DA:{addr},{count}");
+
+      if let Some(branch) = self.coverage.branches.get(&addr) {
+        let _ = writeln!(report, "BRDA:{addr},0,0,{}", branch.taken);
+        let _ = writeln!(report, "BRDA:{addr},0,1,{}", branch.not_taken);
+        branches_found += 2;
+        branches_hit += (branch.taken > 0) as u32 + (branch.not_taken > 0) as u32;
+      } else if self
+        .get_insn_bytes_at(addr as usize)
+        .is_some_and(|(hi, lo)| is_conditional(hi, lo))
+      {
+        let _ = writeln!(report, "BRDA:{addr},0,0,-");
+        let _ = writeln!(report, "BRDA:{addr},0,1,-");
+        branches_found += 2;
+      }
+    }
+
+    let _ = writeln!(report, "BRF:{branches_found}\nBRH:{branches_hit}");
+    let _ = writeln!(report, "LF:{found}\nLH:{hit}");
+    report.push_str("end_of_record\n");
+    report
+  }
+
+  /// Formats coverage of the loaded program as annotated disassembly.
+  ///
+  /// Each instruction is prefixed with its execution count, or `#####` if it never ran,
+  /// conditional skips are annotated with how often they skipped and data bytes with
+  /// whether they were read or written.
+  pub fn coverage_report(&self) -> String {
+    let mut body = String::new();
+    let (mut found, mut hit) = (0, 0);
+    let (mut branches_found, mut branches_hit) = (0, 0);
+    let (mut data, mut read, mut written) = (0, 0, 0);
+
+    for line in self.coverage_lines() {
+      let addr = match line {
+        Line::Insn(addr) | Line::Data(addr) => addr,
+      };
+
+      if let Some(label) = self.symbols.label(addr) {
+        let _ = writeln!(body, "{label}:");
+      }
+
+      let access = self.coverage.get(addr as usize);
+
+      match line {
+        Line::Insn(addr) => {
+          let count = self.profile.counts[addr as usize];
+          let (hi, lo) = self.get_insn_bytes_at(addr as usize).unwrap_or_default();
+          let insn = Insn::from_bytes(hi, lo)
+            .map(|insn| self.symbols.format_insn(&insn))
+            .unwrap_or_else(|| "???".to_string());
+
+          found += 1;
+          hit += (count > 0) as u32;
+
+          let count = match count {
+            0 => "#####".to_string(),
+            count => count.to_string(),
+          };
+
+          let _ = write!(
+            body,
+            "{count:>9}  {addr:#06x}  {hi:02x}{lo:02x}  {insn:<20}"
+          );
+
+          if let Some(branch) = self.coverage.branches.get(&addr) {
+            let _ = write!(
+              body,
+              " ; skipped {} of {}",
+              branch.taken,
+              branch.taken + branch.not_taken
+            );
+
+            branches_found += 2;
+            branches_hit += (branch.taken > 0) as u32 + (branch.not_taken > 0) as u32;
+          } else if is_conditional(hi, lo) {
+            branches_found += 2;
+          }
+        }
+        Line::Data(addr) => {
+          let byte = self.mem[addr as usize];
+          let db = format!("DB {byte:#04x}");
+          let _ = write!(body, "{:>9}  {addr:#06x}  {byte:02x}    {db:<20}", "-");
+
+          data += 1;
+          read += access.contains(Access::READ) as u32;
+          written += access.contains(Access::WRITE) as u32;
+
+          match (
+            access.contains(Access::READ),
+            access.contains(Access::WRITE),
+          ) {
+            (true, true) => body.push_str(" ; read, written"),
+            (true, false) => body.push_str(" ; read"),
+            (false, true) => body.push_str(" ; written"),
+            (false, false) => {}
+          }
+        }
+      }
+
+      let body_len = body.trim_end_matches(' ').len();
+      body.truncate(body_len);
+      body.push('\n');
+    }
+
+    let percent = |hit: u32, found: u32| hit as f64 * 100. / found.max(1) as f64;
+    let mut report = String::new();
+    let _ = writeln!(
+      report,
+      "Instructions executed: {hit} of {found} ({:.2}%)",
+      percent(hit, found)
+    );
+    let _ = writeln!(
+      report,
+      "Branches taken: {branches_hit} of {branches_found} ({:.2}%)",
+      percent(branches_hit, branches_found)
+    );
+    let _ = writeln!(
+      report,
+      "Data bytes read: {read} of {data}, written: {written} of {data}"
+    );
+    report.push('\n');
+    report.push_str(&body);
+    report
+  }
+
+  /// Splits the loaded program into instructions and data.
+  ///
+  /// Executed addresses are always instructions, addresses that were read, written or
+  /// are in a data region are data, anything else is assumed to be an instruction.
+  fn coverage_lines(&self) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut addr = PROG_OFF;
+    let end = PROG_OFF + self.prog_len;
+
+    while addr < end {
+      let access = self.coverage.get(addr);
+      let is_data = !access.contains(Access::EXEC)
+        && (access.intersects(Access::READ | Access::WRITE)
+          || self.symbols.region(addr as u16).is_some()
+          || addr + 1 >= end
+          || self.coverage.get(addr + 1).contains(Access::EXEC));
+
+      if is_data {
+        lines.push(Line::Data(addr as u16));
+        addr += 1;
+      } else {
+        lines.push(Line::Insn(addr as u16));
+        addr += 2;
+      }
+    }
+
+    lines
+  }
+}
+
+/// Returns `true` if the instruction `hi`, `lo` conditionally skips the next instruction.
+fn is_conditional(hi: u8, lo: u8) -> bool {
+  match hi >> 4 {
+    0x3 | 0x4 | 0x5 | 0x9 => true,
+    0xe => lo == 0x9e || lo == 0xa1,
+    _ => false,
+  }
+}
@@ -0,0 +1,262 @@
+//! Virtual machine debugging.
+
+use super::{error::VmError, Vm};
+use crate::insn::{insns::JpInsn, Insn};
+use std::{fmt::Display, ops::Range};
+
+/// Default number of instructions executed per timer tick, see [Vm::tickrate].
+///
+/// Close to the classic 500Hz cpu with 60Hz timers.
+pub const CYCLES_PER_TICK: u32 = 8;
+
+/// A reason the virtual machine stopped running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Break {
+  /// The program counter reached a breakpoint.
+  Breakpoint(u16),
+  /// A watched memory address was written to.
+  Watch(u16, u8),
+  /// A run condition was met.
+  Until(u16),
+  /// The virtual machine is waiting for a key press.
+  WaitKey,
+  /// The instruction budget of a run was exhausted.
+  Budget(u32),
+}
+
+impl Display for Break {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Breakpoint(addr) => write!(f, "Breakpoint at {addr:#06x}"),
+      Self::Watch(addr, val) => write!(f, "Watch {addr:#06x} written with {val:#04x}"),
+      Self::Until(addr) => write!(f, "Stopped at {addr:#06x}"),
+      Self::WaitKey => write!(f, "Waiting for key press"),
+      Self::Budget(n) => write!(f, "Stopped after {n} instructions"),
+    }
+  }
+}
+
+/// What the machine is doing, see [Vm::activity].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+  /// Executing instructions.
+  Running,
+  /// Waiting for a key press.
+  WaitKey,
+  /// Jumping to itself while the delay or sound timer counts down.
+  Idle,
+  /// Jumping to itself with the timers stopped, nothing changes until it is reset.
+  Halted,
+}
+
+impl Activity {
+  /// Returns `true` if executing instructions changes nothing until the timers tick.
+  pub fn is_parked(self) -> bool {
+    self != Self::Running
+  }
+}
+
+impl Display for Activity {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Running => write!(f, "running"),
+      Self::WaitKey => write!(f, "waiting for key"),
+      Self::Idle => write!(f, "idle"),
+      Self::Halted => write!(f, "halted"),
+    }
+  }
+}
+
+/// A condition that stops [Vm::run_until].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Until {
+  /// Stop when the program counter reaches an address.
+  Addr(u16),
+  /// Stop after returning from the current subroutine.
+  Ret,
+}
+
+impl Vm {
+  /// Adds a breakpoint at `addr`, returns `false` if it already existed.
+  pub fn add_breakpoint(&mut self, addr: u16) -> bool {
+    self.breakpoints.insert(addr)
+  }
+
+  /// Removes a breakpoint at `addr`, returns `false` if it didn't exist.
+  pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+    self.breakpoints.remove(&addr)
+  }
+
+  /// Watches writes to the memory addresses in `range`.
+  pub fn add_watch(&mut self, range: Range<u16>) {
+    if !self.watches.contains(&range) {
+      self.watches.push(range);
+    }
+  }
+
+  /// Stops watching writes to memory addresses in `range`, returns `false` if it wasn't
+  /// watched.
+  pub fn remove_watch(&mut self, range: &Range<u16>) -> bool {
+    let len = self.watches.len();
+    self.watches.retain(|watch| watch != range);
+    self.watches.len() != len
+  }
+
+  /// Returns `true` if the last update stopped on a breakpoint or watch.
+  pub fn has_break(&self) -> bool {
+    self.brk.is_some()
+  }
+
+  /// Takes the reason the last update stopped, if any.
+  pub fn take_break(&mut self) -> Option<Break> {
+    self.brk.take()
+  }
+
+  /// Executes `n` instructions, ticking timers every [Vm::tickrate] instructions.
+  ///
+  /// Stops early on breakpoints, watches and key waits. Instructions waiting for the
+  /// next timer tick because of [Quirks::vblank] run without waiting.
+  ///
+  /// [Quirks::vblank]: super::quirks::Quirks::vblank
+  pub fn step_n(&mut self, n: u32) -> Result<Option<Break>, VmError> {
+    for i in 0..n {
+      if self.wait_key {
+        return Ok(Some(Break::WaitKey));
+      }
+
+      self.wait_vblank = false;
+
+      self.update()?;
+
+      if let Some(brk) = self.take_break() {
+        return Ok(Some(brk));
+      }
+
+      if (i + 1) % self.tickrate.max(1) == 0 {
+        self.update_timers();
+      }
+    }
+
+    Ok(None)
+  }
+
+  /// Executes instructions until `until` is met, ticking timers every
+  /// [Vm::tickrate] instructions.
+  ///
+  /// Stops early on breakpoints, watches, key waits and after `budget` instructions.
+  pub fn run_until(&mut self, until: Until, budget: u32) -> Result<Break, VmError> {
+    let depth = self.reg_sp;
+
+    for i in 0..budget {
+      if self.wait_key {
+        return Ok(Break::WaitKey);
+      }
+
+      self.update()?;
+
+      if let Some(brk) = self.take_break() {
+        return Ok(brk);
+      }
+
+      if (i + 1) % self.tickrate.max(1) == 0 {
+        self.update_timers();
+      }
+
+      let done = match until {
+        Until::Addr(addr) => self.reg_pc == addr,
+        Until::Ret => self.reg_sp < depth,
+      };
+
+      if done {
+        return Ok(Break::Until(self.reg_pc));
+      }
+    }
+
+    Ok(Break::Budget(budget))
+  }
+
+  /// Restores machine state from `other`, keeping breakpoints, watches and symbols.
+  pub fn restore(&mut self, other: &Vm) {
+    let breakpoints = std::mem::take(&mut self.breakpoints);
+    let watches = std::mem::take(&mut self.watches);
+    let symbols = std::mem::take(&mut self.symbols);
+    let generation = self.generation;
+
+    *self = other.clone();
+    self.breakpoints = breakpoints;
+    self.watches = watches;
+    self.symbols = symbols;
+    self.brk = None;
+    self.fault = None;
+    self.dirty_rows = u32::MAX;
+    self.generation = generation + 1;
+  }
+
+  /// Stops waiting for a key press or the next frame, for when registers are replaced.
+  pub fn cancel_wait(&mut self) {
+    self.wait_key = false;
+    self.wait_vblank = false;
+  }
+
+  /// Executes one frame of [Vm::tickrate] instructions and ticks the timers once,
+  /// ignoring breakpoints and watches.
+  ///
+  /// Instructions waiting for a key press do not advance until a key is signalled, and
+  /// the rest of the frame is skipped after an instruction of a parked machine runs, see
+  /// [Vm::activity].
+  pub fn run_frame(&mut self) -> Result<(), VmError> {
+    for _ in 0..self.tickrate {
+      let parked = self.activity().is_parked();
+      self.update()?;
+      self.brk = None;
+
+      if parked {
+        break;
+      }
+    }
+
+    self.update_timers();
+    Ok(())
+  }
+
+  /// Executes one frame of [Vm::tickrate] instructions and ticks the timers once,
+  /// stopping early on breakpoints and watches.
+  ///
+  /// Timers only tick when the whole frame was executed.
+  pub fn step_frame(&mut self) -> Result<Option<Break>, VmError> {
+    for _ in 0..self.tickrate {
+      self.update()?;
+
+      if let Some(brk) = self.take_break() {
+        return Ok(Some(brk));
+      }
+    }
+
+    self.update_timers();
+    Ok(None)
+  }
+
+  /// Gets what the machine is doing, detecting key waits and jumps to the same
+  /// instruction.
+  pub fn activity(&self) -> Activity {
+    if self.wait_key {
+      return Activity::WaitKey;
+    }
+
+    match self.insn_at_pc() {
+      Some(Insn::Jp(JpInsn::Addr(addr))) if addr == self.reg_pc => {
+        match self.reg_dt > 0 || self.reg_st > 0 {
+          true => Activity::Idle,
+          false => Activity::Halted,
+        }
+      }
+      _ => Activity::Running,
+    }
+  }
+
+  /// Gets the instruction at the program counter.
+  pub fn insn_at_pc(&self) -> Option<Insn> {
+    let (hi, lo) = self.get_insn_bytes_at(self.reg_pc as usize)?;
+    Insn::from_bytes(hi, lo)
+  }
+}
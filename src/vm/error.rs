@@ -6,6 +6,8 @@ pub enum VmError {
   Io(std::io::Error),
   /// An instruction attempted to access an invalid register.
   BadReg(u8),
+  /// An instruction attempted to access an invalid memory address.
+  BadAddr(usize),
   /// An instruction invalid instruction was found.
   BadInsn(u16),
   /// An instruction attempted to overflow the stack.
@@ -27,6 +29,7 @@ impl std::fmt::Debug for VmError {
     match self {
       Self::Io(err) => write!(f, "{}", err),
       Self::BadReg(reg) => write!(f, "Bad register `{reg}`"),
+      Self::BadAddr(addr) => write!(f, "Bad address `{addr:#x}`"),
       Self::BadInsn(insn) => write!(f, "Bad instruction `{insn:#x}`"),
       Self::StackOverflow => write!(f, "Stack overflow"),
       Self::StackUnderflow => write!(f, "Stack underflow"),
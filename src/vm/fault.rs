@@ -0,0 +1,109 @@
+//! Virtual machine faults.
+
+use super::{error::VmError, Vm};
+use crate::insn::Insn;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+/// An error and the machine state it happened in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fault {
+  /// What went wrong.
+  pub error: String,
+  /// Address of the faulting instruction.
+  pub pc: u16,
+  /// The faulting instruction, `None` if it couldn't be fetched.
+  pub opcode: Option<u16>,
+  /// Disassembly of the faulting instruction, `None` if it isn't valid.
+  pub insn: Option<String>,
+  /// Return addresses on the stack, oldest first.
+  pub stack: Vec<u16>,
+  /// Addresses of the instructions executed before it, oldest first.
+  pub history: Vec<u16>,
+  /// Number of 60Hz frames since the program was loaded.
+  pub frames: u64,
+}
+
+impl Fault {
+  /// Captures the state of `vm` after `err` occurred running the instruction at `pc`.
+  pub fn new(vm: &Vm, pc: u16, err: &VmError) -> Self {
+    let bytes = vm.get_insn_bytes_at(pc as usize);
+    let depth = (vm.reg_sp as usize).min(vm.stack.len());
+
+    Self {
+      error: err.to_string(),
+      pc,
+      opcode: bytes.map(|(hi, lo)| u16::from_be_bytes([hi, lo])),
+      insn: bytes
+        .and_then(|(hi, lo)| Insn::from_bytes(hi, lo))
+        .map(|insn| vm.symbols.format_insn(&insn)),
+      stack: vm.stack[..depth].to_vec(),
+      history: vm.history.iter().copied().collect(),
+      frames: vm.frames,
+    }
+  }
+
+  /// Describes the fault over several lines, naming addresses with the symbols of `vm`
+  /// and disassembling the history from its memory.
+  pub fn report(&self, vm: &Vm) -> Vec<String> {
+    let symbols = &vm.symbols;
+    let insn = match (self.opcode, &self.insn) {
+      (Some(opcode), Some(insn)) => format!("{opcode:04x}  {insn}"),
+      (Some(opcode), None) => format!("{opcode:04x}  invalid"),
+      (None, _) => "outside memory".to_string(),
+    };
+
+    let mut lines = vec![
+      format!("{self} in frame {}", self.frames),
+      format!("  {}  {insn}", symbols.describe(self.pc)),
+      "Call stack, innermost first:".to_string(),
+    ];
+
+    match self.stack.is_empty() {
+      true => lines.push("  empty".to_string()),
+      false => lines.extend(
+        self
+          .stack
+          .iter()
+          .rev()
+          .map(|&addr| format!("  {}", symbols.describe(addr))),
+      ),
+    }
+
+    lines.push("Recent instructions, newest last:".to_string());
+    lines.extend(self.history.iter().map(|&addr| {
+      let insn = vm
+        .get_insn_bytes_at(addr as usize)
+        .and_then(|(hi, lo)| Insn::from_bytes(hi, lo))
+        .map_or_else(|| "invalid".to_string(), |insn| symbols.format_insn(&insn));
+
+      format!("  {}  {insn}", symbols.describe(addr))
+    }));
+
+    lines
+  }
+}
+
+impl Display for Fault {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} at {:#06x}", self.error, self.pc)
+  }
+}
+
+impl Vm {
+  /// Gets the fault the last update stopped with, if any.
+  pub fn fault(&self) -> Option<&Fault> {
+    self.fault.as_ref()
+  }
+
+  /// Takes the fault the last update stopped with, if any.
+  pub fn take_fault(&mut self) -> Option<Fault> {
+    self.fault.take()
+  }
+
+  /// Sets the fault reported as the last update stopping with, such as one loaded from
+  /// a crash dump.
+  pub fn set_fault(&mut self, fault: Option<Fault>) {
+    self.fault = fault;
+  }
+}
@@ -1,13 +1,30 @@
 //! Virtual machine.
 
-use self::{error::VmError, flags::VmKey, visit::Step};
+use self::{
+  coverage::Coverage, debug::Break, error::VmError, fault::Fault, flags::VmKey,
+  profile::Profile, quirks::Quirks, symbols::Symbols, visit::Step,
+};
 use crate::insn::visit::InsnVisit;
+use std::{
+  collections::{BTreeSet, VecDeque},
+  ops::Range,
+};
 
+pub mod coverage;
+pub mod debug;
 pub mod error;
+pub mod fault;
 pub mod flags;
+pub mod profile;
+pub mod quirks;
+pub mod reg;
 pub mod sprites;
+pub mod symbols;
 pub mod visit;
 
+#[cfg(test)]
+mod tests;
+
 /// Offset of program space in memory.
 pub const PROG_OFF: usize = 0x200;
 /// Size of an instruction in bytes.
@@ -16,8 +33,13 @@ pub const INSN_SIZE: u16 = 2;
 pub const VRAM_WIDTH: usize = std::mem::size_of::<u64>();
 /// Height of vram buffer.
 pub const VRAM_HEIGHT: usize = 32;
+/// Number of memory writes remembered in [Vm::recent_writes].
+pub const RECENT_WRITES: usize = 32;
+/// Number of executed instructions remembered in [Vm::history].
+pub const HISTORY: usize = 32;
 
 /// A chip-8 virtual machine.
+#[derive(Clone)]
 pub struct Vm {
   pub mem: Vec<u8>,
   pub reg8: [u8; 16],
@@ -25,13 +47,45 @@ pub struct Vm {
   pub reg_dt: u8,
   pub reg_st: u8,
   pub reg_pc: u16,
+  /// Number of return addresses on the stack.
   pub reg_sp: u8,
   pub stack: [u16; 16],
   pub vram: [u64; VRAM_HEIGHT],
 
+  /// Length of the loaded program in bytes.
+  pub prog_len: usize,
+  /// Number of timer ticks since the program was loaded.
+  pub frames: u64,
+  /// Most recently written memory addresses, newest last.
+  pub recent_writes: VecDeque<u16>,
+  /// Addresses of the most recently executed instructions, newest last.
+  pub history: VecDeque<u16>,
+
+  /// Addresses that stop execution before the instruction at them runs.
+  pub breakpoints: BTreeSet<u16>,
+  /// Memory ranges that stop execution when written to.
+  pub watches: Vec<Range<u16>>,
+  /// Labels and data regions of the loaded program.
+  pub symbols: Symbols,
+  /// Execution counts of the loaded program.
+  pub profile: Profile,
+  /// Executed, read and written addresses of the loaded program.
+  pub coverage: Coverage,
+  /// Behaviours that differ between interpreters, kept when a program is loaded.
+  pub quirks: Quirks,
+  /// Instructions executed per 60Hz frame, kept when a program is loaded.
+  pub tickrate: u32,
+
   pub keys: VmKey,
   wait_key: bool,
   wait_key_reg: u8,
+  wait_vblank: bool,
+  brk: Option<Break>,
+  fault: Option<Fault>,
+  /// Rows of vram changed since they were last taken, bit `y` for row `y`.
+  dirty_rows: u32,
+  /// Incremented whenever the machine changes itself.
+  generation: u64,
 }
 
 impl Vm {
@@ -68,20 +122,59 @@ impl Vm {
     self.vram = [0; VRAM_HEIGHT];
     self.wait_key = false;
     self.wait_key_reg = 0;
+    self.wait_vblank = false;
+    self.prog_len = program.len();
+    self.frames = 0;
+    self.recent_writes.clear();
+    self.history.clear();
+    self.profile.reset();
+    self.coverage.reset();
+    self.brk = None;
+    self.fault = None;
+    self.dirty_rows = u32::MAX;
+    self.generation += 1;
 
     Ok(())
   }
 
   /// Updates the virtual machine.
   ///
-  /// Ideally this should be executed at 500Hz.
+  /// Ideally this should be executed at 500Hz. Errors are also kept as a [Fault] with
+  /// the state they happened in, see [Vm::take_fault].
   pub fn update(&mut self) -> Result<(), VmError> {
-    if self.wait_key {
+    if self.wait_key || self.wait_vblank {
       return Ok(());
     }
 
-    let (hi, lo) = self.get_insn_bytes().unwrap_or((0, 0));
+    let pc = self.reg_pc;
+    let result = self.execute(pc);
+    if let Err(err) = &result {
+      self.fault = Some(Fault::new(self, pc, err));
+    }
+
+    result
+  }
+
+  /// Executes the instruction at `pc`.
+  fn execute(&mut self, pc: u16) -> Result<(), VmError> {
+    let (hi, lo) = self.get_insn_bytes().ok_or(VmError::BadAddr(pc as usize))?;
+    self.profile.record(pc, hi, lo);
+
     let step = self.visit_insn(hi, lo)?;
+    self
+      .coverage
+      .record_exec(pc, hi, lo, matches!(step, Step::Skip));
+
+    // a jump to itself changes nothing but execution counts.
+    if !matches!(step, Step::Jump(addr) if addr == pc) {
+      self.generation += 1;
+    }
+
+    if self.history.len() == HISTORY {
+      self.history.pop_front();
+    }
+
+    self.history.push_back(pc);
 
     match step {
       Step::Next => self.reg_pc += INSN_SIZE,
@@ -93,6 +186,10 @@ impl Vm {
       }
     }
 
+    if self.breakpoints.contains(&self.reg_pc) {
+      self.brk = Some(Break::Breakpoint(self.reg_pc));
+    }
+
     Ok(())
   }
 
@@ -100,17 +197,36 @@ impl Vm {
   ///
   /// Ideally this should be executed at 60Hz.
   pub fn update_timers(&mut self) {
+    if self.reg_dt > 0 || self.reg_st > 0 {
+      self.generation += 1;
+    }
+
     self.reg_dt = self.reg_dt.saturating_sub(1);
     self.reg_st = self.reg_st.saturating_sub(1);
+    self.frames += 1;
+    self.wait_vblank = false;
+  }
+
+  /// Returns `true` if execution is blocked until a key is pressed.
+  pub fn is_waiting_key(&self) -> bool {
+    self.wait_key
   }
 
   /// Sends a signal to the virtual machine that a key has been released.
   pub fn signal_key_up(&mut self, key: VmKey) {
+    if self.keys.contains(key) {
+      self.generation += 1;
+    }
+
     self.keys.remove(key);
   }
 
   /// Sends a signal to the virtual machine that a key has been pressed.
   pub fn signal_key_down(&mut self, key: VmKey) {
+    if !self.keys.contains(key) || self.wait_key {
+      self.generation += 1;
+    }
+
     self.keys.insert(key);
 
     if self.wait_key {
@@ -124,6 +240,30 @@ impl Vm {
     }
   }
 
+  /// Takes the rows of vram changed since the last call, bit `y` for row `y`.
+  pub fn take_dirty_rows(&mut self) -> u32 {
+    std::mem::take(&mut self.dirty_rows)
+  }
+
+  /// Gets a counter incremented whenever instructions, timers or key signals change the
+  /// machine, so that a change can be detected by comparing it to an earlier value.
+  ///
+  /// Writes to public fields, such as edits from the debugger, are not counted.
+  pub fn generation(&self) -> u64 {
+    self.generation
+  }
+
+  /// Sends signals to the virtual machine so that exactly `keys` are pressed.
+  pub fn signal_keys(&mut self, keys: VmKey) {
+    for key in (self.keys - keys).to_vec() {
+      self.signal_key_up(key.into());
+    }
+
+    for key in (keys - self.keys).to_vec() {
+      self.signal_key_down(key.into());
+    }
+  }
+
   #[inline]
   fn get_insn_bytes(&self) -> Option<(u8, u8)> {
     self.get_insn_bytes_at(self.reg_pc as usize)
@@ -151,9 +291,27 @@ impl Default for Vm {
       stack: [0; 16],
       vram: [0; VRAM_HEIGHT],
 
+      prog_len: 0,
+      frames: 0,
+      recent_writes: VecDeque::with_capacity(RECENT_WRITES),
+      history: VecDeque::with_capacity(HISTORY),
+
+      breakpoints: BTreeSet::new(),
+      watches: Vec::new(),
+      symbols: Symbols::new(),
+      profile: Profile::new(4096),
+      coverage: Coverage::new(4096),
+      quirks: Quirks::default(),
+      tickrate: debug::CYCLES_PER_TICK,
+
       keys: VmKey::empty(),
       wait_key: false,
       wait_key_reg: 0,
+      wait_vblank: false,
+      brk: None,
+      fault: None,
+      dirty_rows: 0,
+      generation: 0,
     }
   }
 }
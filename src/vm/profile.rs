@@ -0,0 +1,171 @@
+//! Virtual machine instruction profiler.
+
+use super::{Vm, PROG_OFF};
+use crate::insn::Insn;
+use std::{collections::BTreeMap, fmt::Write};
+
+/// Execution statistics of a subroutine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RoutineStats {
+  /// Number of times the subroutine was called.
+  pub calls: u64,
+  /// Instructions executed in the subroutine itself.
+  pub exclusive: u64,
+  /// Instructions executed in the subroutine and the subroutines it called.
+  pub inclusive: u64,
+}
+
+/// Per address and per subroutine execution counts.
+///
+/// Subroutines are identified by the targets of `CALL` instructions, instructions
+/// executed outside of any call are attributed to the program entry point.
+#[derive(Debug, Clone)]
+pub struct Profile {
+  /// Number of times the instruction at each address was executed.
+  pub counts: Vec<u64>,
+  /// Statistics of each subroutine by entry address.
+  pub routines: BTreeMap<u16, RoutineStats>,
+  /// Total number of instructions executed.
+  pub total: u64,
+  /// Entry addresses of the subroutines currently being executed.
+  frames: Vec<u16>,
+}
+
+impl Profile {
+  /// Creates an empty [Profile] for a memory of `len` bytes.
+  pub fn new(len: usize) -> Self {
+    Self {
+      counts: vec![0; len],
+      routines: BTreeMap::new(),
+      total: 0,
+      frames: Vec::new(),
+    }
+  }
+
+  /// Clears every count.
+  pub fn reset(&mut self) {
+    *self = Self::new(self.counts.len());
+  }
+
+  /// Gets the highest execution count of any address.
+  pub fn max(&self) -> u64 {
+    self.counts.iter().copied().max().unwrap_or(0)
+  }
+
+  /// Gets the execution count at `addr` relative to `max` on a logarithmic scale, from 0
+  /// to 1.
+  pub fn heat(&self, addr: usize, max: u64) -> f64 {
+    match self.counts.get(addr) {
+      Some(&count) if max > 0 => (count as f64).ln_1p() / (max as f64).ln_1p(),
+      _ => 0.,
+    }
+  }
+
+  /// Gets the entry address of the subroutine currently being executed.
+  pub fn routine(&self) -> u16 {
+    self.frames.last().copied().unwrap_or(PROG_OFF as u16)
+  }
+
+  /// Gets addresses sorted by execution count, hottest first.
+  pub fn hottest(&self) -> Vec<(u16, u64)> {
+    let mut hottest = self
+      .counts
+      .iter()
+      .enumerate()
+      .filter(|(_, &count)| count > 0)
+      .map(|(addr, &count)| (addr as u16, count))
+      .collect::<Vec<_>>();
+
+    hottest.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    hottest
+  }
+
+  /// Gets subroutines sorted by inclusive instruction count, hottest first.
+  pub fn hottest_routines(&self) -> Vec<(u16, RoutineStats)> {
+    let mut hottest = self
+      .routines
+      .iter()
+      .map(|(&addr, &stats)| (addr, stats))
+      .collect::<Vec<_>>();
+
+    hottest.sort_by(|a, b| b.1.inclusive.cmp(&a.1.inclusive).then(a.0.cmp(&b.0)));
+    hottest
+  }
+
+  /// Records execution of the instruction `hi`, `lo` at `pc`.
+  pub(crate) fn record(&mut self, pc: u16, hi: u8, lo: u8) {
+    if let Some(count) = self.counts.get_mut(pc as usize) {
+      *count += 1;
+    }
+
+    self.total += 1;
+
+    let routine = self.routine();
+    self.routines.entry(routine).or_default().exclusive += 1;
+
+    let mut seen = Vec::with_capacity(self.frames.len() + 1);
+    for &frame in self
+      .frames
+      .iter()
+      .chain(std::iter::once(&(PROG_OFF as u16)))
+    {
+      if !seen.contains(&frame) {
+        seen.push(frame);
+        self.routines.entry(frame).or_default().inclusive += 1;
+      }
+    }
+
+    match (hi >> 4, hi, lo) {
+      (0x2, _, _) => {
+        let target = ((hi as u16 & 0xf) << 8) | lo as u16;
+        self.frames.push(target);
+        self.routines.entry(target).or_default().calls += 1;
+      }
+      (_, 0x00, 0xee) => {
+        self.frames.pop();
+      }
+      _ => {}
+    }
+  }
+}
+
+impl Vm {
+  /// Formats the `top` hottest addresses and subroutines as a text report.
+  pub fn profile_report(&self, top: usize) -> String {
+    let profile = &self.profile;
+    let total = profile.total.max(1) as f64;
+    let mut report = format!("{} instructions executed\n", profile.total);
+
+    report.push_str("\nHottest addresses\n");
+    report.push_str("  addr         count       %  instruction\n");
+    for (addr, count) in profile.hottest().into_iter().take(top) {
+      let insn = self
+        .get_insn_bytes_at(addr as usize)
+        .and_then(|(hi, lo)| Insn::from_bytes(hi, lo))
+        .map(|insn| self.symbols.format_insn(&insn))
+        .unwrap_or_else(|| "???".to_string());
+
+      let _ = writeln!(
+        report,
+        "  {addr:#06x} {count:>12} {:>6.2}%  {insn}",
+        count as f64 * 100. / total,
+      );
+    }
+
+    report.push_str("\nHottest subroutines\n");
+    report.push_str("  routine             calls    exclusive    inclusive       %\n");
+    for (addr, stats) in profile.hottest_routines().into_iter().take(top) {
+      let _ = writeln!(
+        report,
+        "  {:<16} {:>8} {:>12} {:>12} {:>6.2}%",
+        self.symbols.describe(addr),
+        stats.calls,
+        stats.exclusive,
+        stats.inclusive,
+        stats.inclusive as f64 * 100. / total,
+      );
+    }
+
+    report
+  }
+}
@@ -0,0 +1,42 @@
+//! Behaviours that differ between chip-8 interpreters.
+
+use serde::{Deserialize, Serialize};
+
+/// Behaviours that differ between chip-8 interpreters, named after the Octo options that
+/// enable them.
+///
+/// The default matches how this virtual machine has always behaved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quirks {
+  /// `8xy6` and `8xyE` shift `Vx` in place instead of shifting `Vy` into `Vx`.
+  pub shift: bool,
+  /// `Fx55` and `Fx65` leave `I` unchanged instead of incrementing it past the last
+  /// register.
+  pub load_store: bool,
+  /// `8xy4` to `8xyE` write the result after `VF`, so the result wins when `x` is `F`.
+  pub vf_order: bool,
+  /// `8xy1`, `8xy2` and `8xy3` reset `VF` to 0.
+  pub logic: bool,
+  /// `DRW` clips sprites at the screen edges instead of wrapping them.
+  pub clip: bool,
+  /// `Bnnn` jumps to `nnn` plus `Vx`, where `x` is the high digit of `nnn`, instead of
+  /// `V0`.
+  pub jump: bool,
+  /// `DRW` waits for the next timer tick before execution continues.
+  pub vblank: bool,
+}
+
+impl Default for Quirks {
+  fn default() -> Self {
+    Self {
+      shift: true,
+      load_store: true,
+      vf_order: false,
+      logic: false,
+      clip: false,
+      jump: false,
+      vblank: false,
+    }
+  }
+}
@@ -0,0 +1,119 @@
+//! Virtual machine register access.
+
+use super::{flags::VmKey, Vm};
+
+/// A register that can be inspected or modified from outside of a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmReg {
+  /// General purpose register Vx.
+  V(u8),
+  /// Program counter.
+  Pc,
+  /// Address register I.
+  I,
+  /// Delay timer.
+  Dt,
+  /// Sound timer.
+  St,
+  /// Stack pointer.
+  Sp,
+  /// Pressed keys mask.
+  Keys,
+}
+
+impl VmReg {
+  /// Every register in display order.
+  pub const ALL: [VmReg; 22] = [
+    VmReg::V(0x0),
+    VmReg::V(0x1),
+    VmReg::V(0x2),
+    VmReg::V(0x3),
+    VmReg::V(0x4),
+    VmReg::V(0x5),
+    VmReg::V(0x6),
+    VmReg::V(0x7),
+    VmReg::V(0x8),
+    VmReg::V(0x9),
+    VmReg::V(0xa),
+    VmReg::V(0xb),
+    VmReg::V(0xc),
+    VmReg::V(0xd),
+    VmReg::V(0xe),
+    VmReg::V(0xf),
+    VmReg::Pc,
+    VmReg::I,
+    VmReg::Dt,
+    VmReg::St,
+    VmReg::Sp,
+    VmReg::Keys,
+  ];
+
+  /// Parses a register name such as `v3`, `pc` or `keys`, ignoring case.
+  pub fn parse(name: &str) -> Option<Self> {
+    match name.to_ascii_lowercase().as_str() {
+      "pc" => Some(Self::Pc),
+      "i" => Some(Self::I),
+      "dt" => Some(Self::Dt),
+      "st" => Some(Self::St),
+      "sp" => Some(Self::Sp),
+      "keys" | "k" => Some(Self::Keys),
+      name => {
+        let x = name.strip_prefix('v')?;
+        let x = u8::from_str_radix(x, 16).ok()?;
+
+        (x < 16).then_some(Self::V(x))
+      }
+    }
+  }
+
+  /// Gets the display name of the register.
+  pub fn name(self) -> String {
+    match self {
+      Self::V(x) => format!("V{x:x}"),
+      Self::Pc => "PC".to_string(),
+      Self::I => "I".to_string(),
+      Self::Dt => "DT".to_string(),
+      Self::St => "ST".to_string(),
+      Self::Sp => "SP".to_string(),
+      Self::Keys => "KEYS".to_string(),
+    }
+  }
+
+  /// Gets the number of hex digits needed to display the register value.
+  pub fn width(self) -> usize {
+    match self {
+      Self::Pc | Self::I | Self::Keys => 4,
+      _ => 2,
+    }
+  }
+}
+
+impl Vm {
+  /// Reads the value of `reg`.
+  pub fn read_reg(&self, reg: VmReg) -> u16 {
+    match reg {
+      VmReg::V(x) => self.reg8[x as usize & 0xf] as u16,
+      VmReg::Pc => self.reg_pc,
+      VmReg::I => self.reg_i,
+      VmReg::Dt => self.reg_dt as u16,
+      VmReg::St => self.reg_st as u16,
+      VmReg::Sp => self.reg_sp as u16,
+      VmReg::Keys => self.keys.bits(),
+    }
+  }
+
+  /// Writes `val` to `reg`, truncating it to the size of the register.
+  ///
+  /// The stack pointer is clamped to the depth of the stack.
+  pub fn write_reg(&mut self, reg: VmReg, val: u16) {
+    match reg {
+      VmReg::V(x) => self.reg8[x as usize & 0xf] = val as u8,
+      VmReg::Pc => self.reg_pc = val,
+      VmReg::I => self.reg_i = val,
+      VmReg::Dt => self.reg_dt = val as u8,
+      VmReg::St => self.reg_st = val as u8,
+      VmReg::Sp => self.reg_sp = val.min(self.stack.len() as u16) as u8,
+      VmReg::Keys => self.keys = VmKey::from_bits_truncate(val),
+    }
+  }
+}
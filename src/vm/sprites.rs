@@ -2,20 +2,20 @@
 
 const SPRITE_0: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
 const SPRITE_1: [u8; 5] = [0x20, 0x60, 0x20, 0x20, 0x70];
-const SPRITE_2: [u8; 5] = [0xF0, 0x10, 0xF0, 0x80, 0xF0];
-const SPRITE_3: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_4: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_5: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_6: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_7: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_8: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_9: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_A: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_B: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_C: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_D: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_E: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
-const SPRITE_F: [u8; 5] = [0xf0, 0x90, 0x90, 0x90, 0xf0];
+const SPRITE_2: [u8; 5] = [0xf0, 0x10, 0xf0, 0x80, 0xf0];
+const SPRITE_3: [u8; 5] = [0xf0, 0x10, 0xf0, 0x10, 0xf0];
+const SPRITE_4: [u8; 5] = [0x90, 0x90, 0xf0, 0x10, 0x10];
+const SPRITE_5: [u8; 5] = [0xf0, 0x80, 0xf0, 0x10, 0xf0];
+const SPRITE_6: [u8; 5] = [0xf0, 0x80, 0xf0, 0x90, 0xf0];
+const SPRITE_7: [u8; 5] = [0xf0, 0x10, 0x20, 0x40, 0x40];
+const SPRITE_8: [u8; 5] = [0xf0, 0x90, 0xf0, 0x90, 0xf0];
+const SPRITE_9: [u8; 5] = [0xf0, 0x90, 0xf0, 0x10, 0xf0];
+const SPRITE_A: [u8; 5] = [0xf0, 0x90, 0xf0, 0x90, 0x90];
+const SPRITE_B: [u8; 5] = [0xe0, 0x90, 0xe0, 0x90, 0xe0];
+const SPRITE_C: [u8; 5] = [0xf0, 0x80, 0x80, 0x80, 0xf0];
+const SPRITE_D: [u8; 5] = [0xe0, 0x90, 0x90, 0x90, 0xe0];
+const SPRITE_E: [u8; 5] = [0xf0, 0x80, 0xf0, 0x80, 0xf0];
+const SPRITE_F: [u8; 5] = [0xf0, 0x80, 0xf0, 0x80, 0x80];
 
 /// Copies sprites to supplied slice.
 pub fn copy_to(to: &mut [u8]) {
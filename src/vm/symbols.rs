@@ -0,0 +1,189 @@
+//! Symbol maps for labelled disassembly.
+//!
+//! Symbol files are plain text with one entry per line, `#` and `;` start comments. The
+//! following forms are accepted so that the output of most assemblers can be used as is:
+//!
+//! * `name = 0x200`, `name 0x200`, `name: 0x200` - A label.
+//! * `0x200 name`, `0x200: name` - A label.
+//! * `:const name 0x200` - A label, as written in Octo source.
+//! * `data 0x300..0x310 name` - A data region, `name` is optional.
+//! * `0x300..0x310 name` - A data region.
+//!
+//! Addresses are hex when prefixed with `0x` or `$`, otherwise decimal or hex as long as
+//! they start with a digit.
+
+use crate::insn::{
+  insns::{CallInsn, JpInsn, LdInsn, SysInsn},
+  Insn,
+};
+use eyre::{bail, Result, WrapErr};
+use std::{
+  collections::{BTreeMap, HashMap},
+  fs,
+  ops::Range,
+  path::Path,
+};
+
+/// Maximum distance from a label for an address to be described relative to it.
+const MAX_OFFSET: u16 = 0x100;
+
+/// An annotated region of memory containing data rather than instructions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataRegion {
+  pub range: Range<u16>,
+  pub name: Option<String>,
+}
+
+/// Labels and data regions of a program.
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+  labels: BTreeMap<u16, String>,
+  addrs: HashMap<String, u16>,
+  regions: Vec<DataRegion>,
+}
+
+impl Symbols {
+  /// Creates an empty [Symbols].
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Reads symbols from a file.
+  pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+      .wrap_err_with(|| format!("Failed to read symbols `{}`", path.display()))?;
+
+    Self::parse(&text).wrap_err_with(|| format!("Bad symbols `{}`", path.display()))
+  }
+
+  /// Parses symbols from text.
+  pub fn parse(text: &str) -> Result<Self> {
+    let mut symbols = Self::new();
+
+    for (i, line) in text.lines().enumerate() {
+      let line = line.split(['#', ';']).next().unwrap_or_default();
+      let words = line
+        .split(|c: char| c.is_whitespace() || c == '=' || c == ',')
+        .map(|word| word.trim_end_matches(':'))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+
+      let parsed = match words[..] {
+        [] => continue,
+        ["data", range] => {
+          parse_range(range).map(|range| symbols.add_region(range, None))
+        }
+        ["data", range, name] | [range, name] if parse_range(range).is_some() => {
+          parse_range(range)
+            .map(|range| symbols.add_region(range, Some(name.to_string())))
+        }
+        [addr, name] if parse_addr(addr).is_some() => {
+          parse_addr(addr).map(|addr| symbols.add_label(name, addr))
+        }
+        [":const", name, addr] | [name, addr] => {
+          parse_addr(addr).map(|addr| symbols.add_label(name, addr))
+        }
+        _ => None,
+      };
+
+      if parsed.is_none() {
+        bail!("Bad symbol on line {}: `{}`", i + 1, line.trim());
+      }
+    }
+
+    Ok(symbols)
+  }
+
+  /// Adds a label, replacing any label with the same name or address.
+  pub fn add_label(&mut self, name: &str, addr: u16) {
+    if let Some(old) = self.addrs.insert(name.to_string(), addr) {
+      self.labels.remove(&old);
+    }
+
+    if let Some(old) = self.labels.insert(addr, name.to_string()) {
+      if old != name {
+        self.addrs.remove(&old);
+      }
+    }
+  }
+
+  /// Adds a data region.
+  pub fn add_region(&mut self, range: Range<u16>, name: Option<String>) {
+    self.regions.push(DataRegion { range, name });
+  }
+
+  /// Returns `true` if there are no labels or data regions.
+  pub fn is_empty(&self) -> bool {
+    self.labels.is_empty() && self.regions.is_empty()
+  }
+
+  /// Gets the label at `addr`.
+  pub fn label(&self, addr: u16) -> Option<&str> {
+    self.labels.get(&addr).map(String::as_str)
+  }
+
+  /// Gets the address of label `name`.
+  pub fn addr(&self, name: &str) -> Option<u16> {
+    self.addrs.get(name).copied()
+  }
+
+  /// Gets every label name.
+  pub fn names(&self) -> impl Iterator<Item = &str> {
+    self.labels.values().map(String::as_str)
+  }
+
+  /// Gets the data region containing `addr`.
+  pub fn region(&self, addr: u16) -> Option<&DataRegion> {
+    self
+      .regions
+      .iter()
+      .find(|region| region.range.contains(&addr))
+  }
+
+  /// Describes `addr` as a label, a label and offset such as `draw+0x4`, or a hex address
+  /// when no label is close.
+  pub fn describe(&self, addr: u16) -> String {
+    match self.labels.range(..=addr).next_back() {
+      Some((&label_addr, name)) if label_addr == addr => name.clone(),
+      Some((&label_addr, name)) if addr - label_addr <= MAX_OFFSET => {
+        format!("{name}+{:#x}", addr - label_addr)
+      }
+      _ => format!("{addr:#06x}"),
+    }
+  }
+
+  /// Formats an instruction, replacing addresses of labels with their names.
+  pub fn format_insn(&self, insn: &Insn) -> String {
+    let name = |addr: u16| self.label(addr).map(str::to_string);
+
+    let named = match *insn {
+      Insn::Sys(SysInsn { addr }) => name(addr).map(|name| format!("SYS {name}")),
+      Insn::Jp(JpInsn::Addr(addr)) => name(addr).map(|name| format!("JP {name}")),
+      Insn::Jp(JpInsn::AddrReg(addr)) => name(addr).map(|name| format!("JP V0, {name}")),
+      Insn::Call(CallInsn { addr }) => name(addr).map(|name| format!("CALL {name}")),
+      Insn::Ld(LdInsn::MemAddr(addr)) => name(addr).map(|name| format!("LD I, {name}")),
+      _ => None,
+    };
+
+    named.unwrap_or_else(|| insn.to_string())
+  }
+}
+
+/// Parses an address as hex when prefixed with `0x` or `$`, otherwise as decimal or hex
+/// when it starts with a digit.
+pub fn parse_addr(s: &str) -> Option<u16> {
+  match s.strip_prefix("0x").or_else(|| s.strip_prefix('$')) {
+    Some(hex) => u16::from_str_radix(hex, 16).ok(),
+    None if s.starts_with(|c: char| c.is_ascii_digit()) => {
+      s.parse().ok().or_else(|| u16::from_str_radix(s, 16).ok())
+    }
+    None => None,
+  }
+}
+
+/// Parses a range of addresses written as `beg..end`.
+pub fn parse_range(s: &str) -> Option<Range<u16>> {
+  let (beg, end) = s.split_once("..")?;
+  Some(parse_addr(beg)?..parse_addr(end)?)
+}
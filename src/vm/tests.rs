@@ -0,0 +1,500 @@
+//! Conformance of every instruction to its documented semantics.
+
+use super::{
+  debug::Activity, error::VmError, flags::VmKey, quirks::Quirks, Vm, PROG_OFF,
+};
+
+const PC: u16 = PROG_OFF as u16;
+
+/// Creates a machine with `insns` loaded.
+fn load(insns: &[u16]) -> Vm {
+  let program = insns
+    .iter()
+    .flat_map(|insn| insn.to_be_bytes())
+    .collect::<Vec<_>>();
+
+  let mut vm = Vm::new();
+  vm.load_program(&program).unwrap();
+  vm
+}
+
+/// Executes `n` instructions.
+fn step(vm: &mut Vm, n: usize) {
+  for _ in 0..n {
+    vm.update().unwrap();
+  }
+}
+
+/// Creates a machine with `insns` loaded and executes each of them once.
+fn run(insns: &[u16]) -> Vm {
+  let mut vm = load(insns);
+  step(&mut vm, insns.len());
+  vm
+}
+
+/// Like [run], under `quirks`.
+fn run_quirks(quirks: Quirks, insns: &[u16]) -> Vm {
+  let mut vm = load(insns);
+  vm.quirks = quirks;
+  step(&mut vm, insns.len());
+  vm
+}
+
+#[test]
+fn cls() {
+  let mut vm = load(&[0x00e0]);
+  vm.vram = [u64::MAX; 32];
+  step(&mut vm, 1);
+  assert_eq!(vm.vram, [0; 32]);
+}
+
+#[test]
+fn sys_is_ignored() {
+  let vm = run(&[0x0300]);
+  assert_eq!(vm.reg_pc, PC + 2);
+}
+
+#[test]
+fn jp() {
+  let mut vm = load(&[0x1234]);
+  step(&mut vm, 1);
+  assert_eq!(vm.reg_pc, 0x234);
+}
+
+#[test]
+fn jp_v0() {
+  let vm = run(&[0x6004, 0xb300]);
+  assert_eq!(vm.reg_pc, 0x304);
+
+  let jump = Quirks {
+    jump: true,
+    ..Quirks::default()
+  };
+  let vm = run_quirks(jump, &[0x6004, 0x6308, 0xb300]);
+  assert_eq!(vm.reg_pc, 0x308);
+}
+
+#[test]
+fn call_ret() {
+  let mut vm = load(&[0x2206, 0x0000, 0x0000, 0x00ee]);
+  step(&mut vm, 1);
+  assert_eq!((vm.reg_pc, vm.reg_sp), (PC + 6, 1));
+  assert_eq!(vm.stack[0], PC + 2);
+
+  step(&mut vm, 1);
+  assert_eq!((vm.reg_pc, vm.reg_sp), (PC + 2, 0));
+}
+
+#[test]
+fn stack_depth_16() {
+  // Each call pushes the address after itself and calls the next one.
+  let calls = (0..17).map(|i| 0x2202 + i * 2).collect::<Vec<_>>();
+  let mut vm = load(&calls);
+  step(&mut vm, 16);
+  assert_eq!(vm.reg_sp, 16);
+  assert_eq!(vm.stack[15], PC + 32);
+
+  assert!(matches!(vm.update(), Err(VmError::StackOverflow)));
+  assert_eq!(vm.reg_sp, 16);
+}
+
+#[test]
+fn stack_unwinds_from_depth_16() {
+  // Subroutines at 0x204 onwards each call the next and return, the last one returns
+  // straight away.
+  let mut insns = vec![0x2204, 0x1202];
+  for sub in 0..16 {
+    let call = match sub {
+      15 => 0x00ee,
+      _ => 0x2208 + sub * 4,
+    };
+    insns.extend([call, 0x00ee]);
+  }
+
+  let mut vm = load(&insns);
+  step(&mut vm, 16);
+  assert_eq!(vm.reg_sp, 16);
+
+  step(&mut vm, 16);
+  assert_eq!((vm.reg_pc, vm.reg_sp), (PC + 2, 0));
+}
+
+#[test]
+fn ret_underflow() {
+  let mut vm = load(&[0x00ee]);
+  assert!(matches!(vm.update(), Err(VmError::StackUnderflow)));
+}
+
+#[test]
+fn se_sne_kk() {
+  assert_eq!(run(&[0x6142, 0x3142]).reg_pc, PC + 6);
+  assert_eq!(run(&[0x6142, 0x3143]).reg_pc, PC + 4);
+  assert_eq!(run(&[0x6142, 0x4142]).reg_pc, PC + 4);
+  assert_eq!(run(&[0x6142, 0x4143]).reg_pc, PC + 6);
+}
+
+#[test]
+fn se_sne_xy() {
+  assert_eq!(run(&[0x6142, 0x6242, 0x5120]).reg_pc, PC + 8);
+  assert_eq!(run(&[0x6142, 0x6243, 0x5120]).reg_pc, PC + 6);
+  assert_eq!(run(&[0x6142, 0x6242, 0x9120]).reg_pc, PC + 6);
+  assert_eq!(run(&[0x6142, 0x6243, 0x9120]).reg_pc, PC + 8);
+}
+
+#[test]
+fn ld_add_kk() {
+  let vm = run(&[0x61fe, 0x7103, 0x6f00, 0x7fff]);
+  assert_eq!(vm.reg8[1], 0x01);
+  // Adding a byte never touches the flag, even when it wraps.
+  assert_eq!(vm.reg8[0xf], 0xff);
+}
+
+#[test]
+fn ld_xy() {
+  let vm = run(&[0x6242, 0x8120]);
+  assert_eq!(vm.reg8[1], 0x42);
+}
+
+#[test]
+fn logic() {
+  let vm = run(&[0x610c, 0x620a, 0x6f05, 0x8121]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0x0e, 0x05));
+  let vm = run(&[0x610c, 0x620a, 0x8122]);
+  assert_eq!(vm.reg8[1], 0x08);
+  let vm = run(&[0x610c, 0x620a, 0x8123]);
+  assert_eq!(vm.reg8[1], 0x06);
+
+  let logic = Quirks {
+    logic: true,
+    ..Quirks::default()
+  };
+  for insn in [0x8121, 0x8122, 0x8123] {
+    let vm = run_quirks(logic, &[0x610c, 0x620a, 0x6f05, insn]);
+    assert_eq!(vm.reg8[0xf], 0);
+  }
+}
+
+#[test]
+fn add_xy_carry() {
+  let vm = run(&[0x61ff, 0x6202, 0x8124]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0x01, 1));
+  let vm = run(&[0x61fd, 0x6202, 0x6f07, 0x8124]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0xff, 0));
+}
+
+#[test]
+fn sub_xy_borrow() {
+  let vm = run(&[0x6105, 0x6203, 0x8125]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0x02, 1));
+  let vm = run(&[0x6103, 0x6205, 0x8125]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0xfe, 0));
+  // Equal operands don't borrow.
+  let vm = run(&[0x6105, 0x6205, 0x8125]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0x00, 1));
+}
+
+#[test]
+fn subn_xy_borrow() {
+  let vm = run(&[0x6103, 0x6205, 0x8127]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0x02, 1));
+  let vm = run(&[0x6105, 0x6203, 0x8127]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0xfe, 0));
+}
+
+#[test]
+fn arithmetic_into_vf() {
+  // The flag is written last, so it wins over the result.
+  assert_eq!(run(&[0x6fff, 0x6e02, 0x8fe4]).reg8[0xf], 1);
+  assert_eq!(run(&[0x6f01, 0x6e02, 0x8fe4]).reg8[0xf], 0);
+  assert_eq!(run(&[0x6f05, 0x6e03, 0x8fe5]).reg8[0xf], 1);
+  assert_eq!(run(&[0x6f03, 0x6e05, 0x8fe5]).reg8[0xf], 0);
+  assert_eq!(run(&[0x6f03, 0x6e05, 0x8fe7]).reg8[0xf], 1);
+  assert_eq!(run(&[0x6f05, 0x6e03, 0x8fe7]).reg8[0xf], 0);
+
+  // Unless the result is written last.
+  let vf_order = Quirks {
+    vf_order: true,
+    ..Quirks::default()
+  };
+  assert_eq!(
+    run_quirks(vf_order, &[0x6fff, 0x6e02, 0x8fe4]).reg8[0xf],
+    0x01
+  );
+  assert_eq!(
+    run_quirks(vf_order, &[0x6f03, 0x6e05, 0x8fe5]).reg8[0xf],
+    0xfe
+  );
+  assert_eq!(
+    run_quirks(vf_order, &[0x6f05, 0x6e03, 0x8fe7]).reg8[0xf],
+    0xfe
+  );
+}
+
+#[test]
+fn shifts() {
+  let vm = run(&[0x6181, 0x8106]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0x40, 1));
+  let vm = run(&[0x6181, 0x810e]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0x02, 1));
+  let vm = run(&[0x6142, 0x810e]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0x84, 0));
+
+  let vy = Quirks {
+    shift: false,
+    ..Quirks::default()
+  };
+  let vm = run_quirks(vy, &[0x6181, 0x6202, 0x8126]);
+  assert_eq!((vm.reg8[1], vm.reg8[0xf]), (0x01, 0));
+
+  assert_eq!(run(&[0x6f03, 0x8f06]).reg8[0xf], 1);
+  assert_eq!(run(&[0x6f40, 0x8f0e]).reg8[0xf], 0);
+}
+
+#[test]
+fn ld_add_i() {
+  let vm = run(&[0xa123, 0x6110, 0xf11e]);
+  assert_eq!(vm.reg_i, 0x133);
+}
+
+#[test]
+fn rnd_masks() {
+  for _ in 0..32 {
+    assert_eq!(run(&[0xc100]).reg8[1], 0);
+    assert_eq!(run(&[0xc10f]).reg8[1] & 0xf0, 0);
+  }
+}
+
+#[test]
+fn drw() {
+  // The digit 0 in the top left corner.
+  let vm = run(&[0x6000, 0xf029, 0xd005]);
+  assert_eq!(vm.vram[0] >> 56, 0xf0);
+  assert_eq!(vm.vram[1] >> 56, 0x90);
+  assert_eq!(vm.vram[4] >> 56, 0xf0);
+  assert_eq!(vm.vram[5], 0);
+  assert_eq!(vm.reg8[0xf], 0);
+}
+
+#[test]
+fn drw_collision() {
+  // Drawing the same sprite twice erases it and reports the collision.
+  let vm = run(&[0x6000, 0xf029, 0xd005, 0xd005]);
+  assert_eq!(vm.vram, [0; 32]);
+  assert_eq!(vm.reg8[0xf], 1);
+
+  // The flag is cleared when nothing collides, and is 1 however many pixels collide.
+  let vm = run(&[0x6000, 0xf029, 0xd005, 0x6108, 0xd105]);
+  assert_eq!(vm.reg8[0xf], 0);
+  let vm = run(&[0x6f07, 0x6000, 0xf029, 0xd005]);
+  assert_eq!(vm.reg8[0xf], 0);
+}
+
+#[test]
+fn drw_wraps() {
+  // The digit 0 at the bottom right, wrapping around both edges.
+  let vm = run(&[0x603e, 0x611f, 0x6200, 0xf229, 0xd015]);
+  assert_eq!(vm.vram[31], 0xc000_0000_0000_0003);
+  assert_eq!(vm.vram[0], 0x4000_0000_0000_0002);
+  assert_eq!(vm.vram[3], 0xc000_0000_0000_0003);
+
+  // Coordinates past the edges wrap before drawing.
+  let vm = run(&[0x6040, 0x6120, 0x6200, 0xf229, 0xd015]);
+  assert_eq!(vm.vram[0] >> 56, 0xf0);
+}
+
+#[test]
+fn drw_clips() {
+  let clip = Quirks {
+    clip: true,
+    ..Quirks::default()
+  };
+  let vm = run_quirks(clip, &[0x603e, 0x611f, 0x6200, 0xf229, 0xd015]);
+  assert_eq!(vm.vram[31], 0x3);
+  assert_eq!(vm.vram[0], 0);
+}
+
+#[test]
+fn skp_sknp() {
+  let mut vm = load(&[0x6107, 0xe19e]);
+  vm.keys = VmKey::KEY_7;
+  step(&mut vm, 2);
+  assert_eq!(vm.reg_pc, PC + 6);
+
+  assert_eq!(run(&[0x6107, 0xe19e]).reg_pc, PC + 4);
+  assert_eq!(run(&[0x6107, 0xe1a1]).reg_pc, PC + 6);
+
+  // Only the low digit of the register names a key.
+  let mut vm = load(&[0x6117, 0xe19e]);
+  vm.keys = VmKey::KEY_7;
+  step(&mut vm, 2);
+  assert_eq!(vm.reg_pc, PC + 6);
+}
+
+#[test]
+fn ld_k_waits() {
+  let mut vm = load(&[0xf30a]);
+  step(&mut vm, 3);
+  assert!(vm.is_waiting_key());
+  assert_eq!(vm.reg_pc, PC);
+
+  vm.signal_key_down(VmKey::KEY_A);
+  assert!(!vm.is_waiting_key());
+  assert_eq!((vm.reg_pc, vm.reg8[3]), (PC + 2, 0xa));
+}
+
+#[test]
+fn timers() {
+  let mut vm = run(&[0x6105, 0xf115, 0xf118]);
+  assert_eq!((vm.reg_dt, vm.reg_st), (5, 5));
+  vm.update_timers();
+  assert_eq!((vm.reg_dt, vm.reg_st), (4, 4));
+
+  let mut vm = load(&[0x6102, 0xf115, 0xf207]);
+  step(&mut vm, 2);
+  vm.update_timers();
+  step(&mut vm, 1);
+  assert_eq!(vm.reg8[2], 1);
+}
+
+#[test]
+fn ld_f_points_at_digits() {
+  for digit in 0..16u16 {
+    let vm = run(&[0x6100 | digit, 0xf129]);
+    assert_eq!(vm.reg_i, digit * 5);
+  }
+
+  // Only the low digit of the register is used.
+  assert_eq!(run(&[0x611a, 0xf129]).reg_i, 0xa * 5);
+
+  let vm = run(&[0x6108, 0xf129]);
+  assert_eq!(vm.mem[40..45], [0xf0, 0x90, 0xf0, 0x90, 0xf0]);
+}
+
+#[test]
+fn ld_b() {
+  for (val, digits) in [
+    (0, [0, 0, 0]),
+    (9, [0, 0, 9]),
+    (99, [0, 9, 9]),
+    (255, [2, 5, 5]),
+  ] {
+    let vm = run(&[0xa300, 0x6100 | val, 0xf133]);
+    assert_eq!(vm.mem[0x300..0x303], digits, "{val}");
+  }
+}
+
+#[test]
+fn ld_deref_i_includes_x() {
+  let vm = run(&[0x6011, 0x6122, 0x6233, 0x6344, 0xa300, 0xf255]);
+  assert_eq!(vm.mem[0x300..0x304], [0x11, 0x22, 0x33, 0x00]);
+  assert_eq!(vm.reg_i, 0x300);
+
+  let vm = run(&[0x6f42, 0xa300, 0xff55]);
+  assert_eq!(vm.mem[0x30f], 0x42);
+  let vm = run(&[0x6042, 0xa300, 0xf055]);
+  assert_eq!(vm.mem[0x300], 0x42);
+}
+
+#[test]
+fn ld_x_deref_i_includes_x() {
+  let mut vm = load(&[0xa300, 0xf265]);
+  vm.mem[0x300..0x304].copy_from_slice(&[0x11, 0x22, 0x33, 0x44]);
+  step(&mut vm, 2);
+  assert_eq!(vm.reg8[..4], [0x11, 0x22, 0x33, 0x00]);
+  assert_eq!(vm.reg_i, 0x300);
+
+  let mut vm = load(&[0xa300, 0xff65]);
+  vm.mem[0x30f] = 0x42;
+  step(&mut vm, 2);
+  assert_eq!(vm.reg8[0xf], 0x42);
+}
+
+#[test]
+fn load_store_advances_i() {
+  let advance = Quirks {
+    load_store: false,
+    ..Quirks::default()
+  };
+  assert_eq!(run_quirks(advance, &[0xa300, 0xf255]).reg_i, 0x303);
+  assert_eq!(run_quirks(advance, &[0xa300, 0xf065]).reg_i, 0x301);
+}
+
+#[test]
+fn faults() {
+  let mut vm = load(&[0xffff]);
+  assert!(matches!(vm.update(), Err(VmError::BadInsn(0xffff))));
+
+  let mut vm = load(&[0xafff, 0xf165]);
+  step(&mut vm, 1);
+  assert!(matches!(vm.update(), Err(VmError::BadAddr(0x1000))));
+
+  let mut vm = load(&[0x1ffe]);
+  step(&mut vm, 2);
+  assert!(matches!(vm.update(), Err(VmError::BadAddr(0x1000))));
+}
+
+#[test]
+fn fault_keeps_state() {
+  let mut vm = load(&[0x2206, 0x1202, 0x0000, 0x6001, 0xffff]);
+  step(&mut vm, 2);
+  assert!(vm.update().is_err());
+
+  let fault = vm.take_fault().unwrap();
+  assert_eq!(fault.pc, PC + 8);
+  assert_eq!(fault.opcode, Some(0xffff));
+  assert_eq!(fault.insn, None);
+  assert_eq!(fault.stack, [PC + 2]);
+  assert_eq!(fault.history, [PC, PC + 6]);
+  assert!(vm.fault().is_none());
+}
+
+#[test]
+fn activity() {
+  let mut vm = load(&[0x6001, 0x1202]);
+  assert_eq!(vm.activity(), Activity::Running);
+  step(&mut vm, 1);
+  assert_eq!(vm.activity(), Activity::Halted);
+  vm.reg_st = 1;
+  assert_eq!(vm.activity(), Activity::Idle);
+
+  let mut vm = load(&[0xf00a]);
+  step(&mut vm, 1);
+  assert_eq!(vm.activity(), Activity::WaitKey);
+}
+
+#[test]
+fn parked_frame_ticks_timers() {
+  let mut vm = load(&[0x6005, 0xf015, 0x1204]);
+  vm.tickrate = 1000;
+  vm.run_frame().unwrap();
+  assert_eq!(vm.reg_pc, PC + 4);
+  assert_eq!(vm.reg_dt, 4);
+  assert_eq!(vm.profile.counts[PC as usize + 4], 1);
+}
+
+#[test]
+fn drw_marks_dirty_rows() {
+  let mut vm = load(&[0x6003, 0xf029, 0xd015, 0x00e0]);
+  assert_eq!(vm.take_dirty_rows(), u32::MAX);
+
+  step(&mut vm, 3);
+  assert_eq!(vm.take_dirty_rows(), 0b11111);
+  assert_eq!(vm.take_dirty_rows(), 0);
+
+  step(&mut vm, 1);
+  assert_eq!(vm.take_dirty_rows(), u32::MAX);
+}
+
+#[test]
+fn generation_ignores_halt() {
+  let mut vm = load(&[0x6001, 0x1202]);
+  step(&mut vm, 1);
+  let generation = vm.generation();
+
+  step(&mut vm, 1);
+  vm.update_timers();
+  assert_eq!(vm.generation(), generation);
+
+  vm.signal_key_down(VmKey::KEY_1);
+  assert_ne!(vm.generation(), generation);
+}
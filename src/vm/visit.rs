@@ -1,6 +1,6 @@
 //! Virtual machine instruction visitor.
 
-use super::{error::VmError, Vm};
+use super::{coverage::Access, debug::Break, error::VmError, Vm, RECENT_WRITES};
 use crate::insn::visit::InsnVisit;
 
 /// A virtual machine step.
@@ -50,6 +50,72 @@ impl Vm {
     Ok(())
   }
 
+  /// Gets value of memory at `addr`.
+  #[inline]
+  fn get_mem(&mut self, addr: usize) -> Result<u8, VmError> {
+    let val = self.mem.get(addr).copied().ok_or(VmError::BadAddr(addr))?;
+    self.coverage.record(addr, Access::READ);
+    Ok(val)
+  }
+
+  /// Sets value of memory at `addr` and records the write.
+  #[inline]
+  fn set_mem(&mut self, addr: usize, val: u8) -> Result<(), VmError> {
+    *self.mem.get_mut(addr).ok_or(VmError::BadAddr(addr))? = val;
+    self.coverage.record(addr, Access::WRITE);
+
+    if self.recent_writes.len() == RECENT_WRITES {
+      self.recent_writes.pop_front();
+    }
+
+    self.recent_writes.push_back(addr as u16);
+
+    if self
+      .watches
+      .iter()
+      .any(|watch| watch.contains(&(addr as u16)))
+    {
+      self.brk = Some(Break::Watch(addr as u16, val));
+    }
+
+    Ok(())
+  }
+
+  /// Sets register `reg` to `val` and `VF` to `flag`, in the order given by
+  /// [Quirks::vf_order](super::quirks::Quirks::vf_order).
+  #[inline]
+  fn set_reg_flag(&mut self, reg: u8, val: u8, flag: u8) -> Result<Step, VmError> {
+    if self.quirks.vf_order {
+      self.set_reg(0xf, flag)?;
+      self.set_reg(reg, val)?;
+    } else {
+      self.set_reg(reg, val)?;
+      self.set_reg(0xf, flag)?;
+    }
+
+    Ok(Step::Next)
+  }
+
+  /// Resets `VF` after a logic instruction if
+  /// [Quirks::logic](super::quirks::Quirks::logic) is set.
+  #[inline]
+  fn reset_flag(&mut self) -> Result<Step, VmError> {
+    if self.quirks.logic {
+      self.reg8[0xf] = 0;
+    }
+
+    Ok(Step::Next)
+  }
+
+  /// Moves `I` past the registers `Fx55` and `Fx65` accessed unless
+  /// [Quirks::load_store](super::quirks::Quirks::load_store) is set.
+  #[inline]
+  fn advance_i(&mut self, x: u8) {
+    if !self.quirks.load_store {
+      self.reg_i = self.reg_i.wrapping_add(x as u16 + 1);
+    }
+  }
+
   // Pops value from top of stack.
   #[inline]
   fn stack_pop(&mut self) -> Result<u16, VmError> {
@@ -57,21 +123,20 @@ impl Vm {
       return Err(VmError::StackUnderflow);
     }
 
-    let val = self.stack[self.reg_sp as usize];
     self.reg_sp -= 1;
 
-    Ok(val)
+    Ok(self.stack[self.reg_sp as usize])
   }
 
   // Pushes value to top of stack.
   #[inline]
   fn stack_push(&mut self, val: u16) -> Result<(), VmError> {
-    if self.reg_sp as usize == self.stack.len() {
-      return Err(VmError::StackOverflow);
-    }
+    *self
+      .stack
+      .get_mut(self.reg_sp as usize)
+      .ok_or(VmError::StackOverflow)? = val;
 
     self.reg_sp += 1;
-    self.stack[self.reg_sp as usize] = val;
 
     Ok(())
   }
@@ -88,6 +153,7 @@ impl InsnVisit for Vm {
   #[inline]
   fn cls(&mut self) -> Self::Result {
     self.vram = [0; 32];
+    self.dirty_rows = u32::MAX;
     Ok(Step::Next)
   }
 
@@ -96,14 +162,11 @@ impl InsnVisit for Vm {
     Ok(Step::Jump(self.stack_pop()?))
   }
 
+  /// Machine code routines only ran on the original computers, modern interpreters
+  /// ignore them.
   #[inline]
   fn sys_nnn(&mut self, _: u16) -> Self::Result {
-    todo!(
-      r"Jump to a machine code routine at nnn.
-    
-      This instruction is only used on the old computers on which Chip-8 was originally 
-      implemented. It is ignored by modern interpreters."
-    )
+    Ok(Step::Next)
   }
 
   #[inline]
@@ -153,61 +216,49 @@ impl InsnVisit for Vm {
   #[inline]
   fn or_x_y(&mut self, x: u8, y: u8) -> Self::Result {
     self.set_reg(x, self.get_reg(x)? | self.get_reg(y)?)?;
-    Ok(Step::Next)
+    self.reset_flag()
   }
 
   #[inline]
   fn and_x_y(&mut self, x: u8, y: u8) -> Self::Result {
     self.set_reg(x, self.get_reg(x)? & self.get_reg(y)?)?;
-    Ok(Step::Next)
+    self.reset_flag()
   }
 
   #[inline]
   fn xor_x_y(&mut self, x: u8, y: u8) -> Self::Result {
     self.set_reg(x, self.get_reg(x)? ^ self.get_reg(y)?)?;
-    Ok(Step::Next)
+    self.reset_flag()
   }
 
   #[inline]
   fn add_x_y(&mut self, x: u8, y: u8) -> Self::Result {
     let (val, carry) = self.get_reg(x)?.overflowing_add(self.get_reg(y)?);
-    self.set_reg(x, val)?;
-    self.set_reg(0xf, carry as u8)?;
-    Ok(Step::Next)
+    self.set_reg_flag(x, val, carry as u8)
   }
 
   #[inline]
   fn sub_x_y(&mut self, x: u8, y: u8) -> Self::Result {
     let (val, carry) = self.get_reg(x)?.overflowing_sub(self.get_reg(y)?);
-    self.set_reg(x, val)?;
-    self.set_reg(0xf, !carry as u8)?;
-    Ok(Step::Next)
+    self.set_reg_flag(x, val, !carry as u8)
   }
 
   #[inline]
-  fn shr_x_y(&mut self, x: u8, _: u8) -> Self::Result {
-    let val = self.get_reg(x)?;
-
-    self.set_reg(x, val >> 1)?;
-    self.set_reg(0xf, val & 0x1)?;
-
-    Ok(Step::Next)
+  fn shr_x_y(&mut self, x: u8, y: u8) -> Self::Result {
+    let val = self.get_reg(if self.quirks.shift { x } else { y })?;
+    self.set_reg_flag(x, val >> 1, val & 0x1)
   }
 
   #[inline]
   fn subn_x_y(&mut self, x: u8, y: u8) -> Self::Result {
     let (val, carry) = self.get_reg(y)?.overflowing_sub(self.get_reg(x)?);
-    self.set_reg(x, val)?;
-    self.set_reg(0xf, !carry as u8)?;
-    Ok(Step::Next)
+    self.set_reg_flag(x, val, !carry as u8)
   }
 
   #[inline]
-  fn shl_x_y(&mut self, x: u8, _: u8) -> Self::Result {
-    let val = self.get_reg(x)?;
-    self.set_reg(x, val << 1)?;
-    self.set_reg(0xf, val >> 7)?;
-    Ok(Step::Next)
+  fn shl_x_y(&mut self, x: u8, y: u8) -> Self::Result {
+    let val = self.get_reg(if self.quirks.shift { x } else { y })?;
+    self.set_reg_flag(x, val << 1, val >> 7)
   }
 
   #[inline]
@@ -227,7 +278,12 @@ impl InsnVisit for Vm {
 
   #[inline]
   fn jp_0_nnn(&mut self, nnn: u16) -> Self::Result {
-    Ok(Step::Jump(self.get_reg(0)? as u16 + nnn))
+    let x = if self.quirks.jump {
+      (nnn >> 8) as u8
+    } else {
+      0
+    };
+    Ok(Step::Jump(self.get_reg(x)? as u16 + nnn))
   }
 
   #[inline]
@@ -245,25 +301,41 @@ impl InsnVisit for Vm {
     let n = n as usize;
     let x = self.get_reg(x)? as u32;
     let y = self.get_reg(y)? as usize;
+    let mut collision = false;
 
     for i in 0..n {
-      let byte = self.mem[self.reg_i as usize + i];
+      if self.quirks.clip && y % self.vram.len() + i >= self.vram.len() {
+        break;
+      }
+
+      let byte = self.get_mem(self.reg_i as usize + i)?;
       let vram_y = (y + i) % self.vram.len();
       let vram = self.vram[vram_y];
 
       let mask = (byte as u64) << (64 - 8);
-      let mask = mask.rotate_right(x);
+      let mask = match self.quirks.clip {
+        true => mask >> (x % 64),
+        false => mask.rotate_right(x),
+      };
 
-      self.reg8[0xf] |= (mask & vram).rotate_left(x).rotate_right(64 - 8) as u8;
+      collision |= mask & vram != 0;
       self.vram[vram_y] ^= mask;
+
+      if mask != 0 {
+        self.dirty_rows |= 1 << vram_y;
+      }
     }
 
+    self.reg8[0xf] = collision as u8;
+
+    self.wait_vblank = self.quirks.vblank;
+
     Ok(Step::Next)
   }
 
   #[inline]
   fn skp_x(&mut self, x: u8) -> Self::Result {
-    let key = self.get_reg(x)?.into();
+    let key = (self.get_reg(x)? & 0xf).into();
     if self.keys.contains(key) {
       Ok(Step::Skip)
     } else {
@@ -273,7 +345,7 @@ impl InsnVisit for Vm {
 
   #[inline]
   fn sknp_x(&mut self, x: u8) -> Self::Result {
-    let key = self.get_reg(x)?.into();
+    let key = (self.get_reg(x)? & 0xf).into();
     if !self.keys.contains(key) {
       Ok(Step::Skip)
     } else {
@@ -312,7 +384,7 @@ impl InsnVisit for Vm {
 
   #[inline]
   fn ld_f_x(&mut self, x: u8) -> Self::Result {
-    self.reg_i = self.get_reg(x)? as u16 * 5;
+    self.reg_i = (self.get_reg(x)? & 0xf) as u16 * 5;
 
     Ok(Step::Next)
   }
@@ -321,28 +393,31 @@ impl InsnVisit for Vm {
   fn ld_b_x(&mut self, x: u8) -> Self::Result {
     let x = self.get_reg(x)?;
 
-    self.mem[self.reg_i as usize] = x / 100;
-    self.mem[self.reg_i as usize + 1] = (x % 100) / 10;
-    self.mem[self.reg_i as usize + 2] = x % 10;
+    self.set_mem(self.reg_i as usize, x / 100)?;
+    self.set_mem(self.reg_i as usize + 1, (x % 100) / 10)?;
+    self.set_mem(self.reg_i as usize + 2, x % 10)?;
 
     Ok(Step::Next)
   }
 
   #[inline]
   fn ld_deref_i_x(&mut self, x: u8) -> Self::Result {
-    for i in 0..x {
-      self.mem[self.reg_i as usize + i as usize] = self.get_reg(i)?;
+    for i in 0..=x {
+      self.set_mem(self.reg_i as usize + i as usize, self.get_reg(i)?)?;
     }
 
+    self.advance_i(x);
     Ok(Step::Next)
   }
 
   #[inline]
   fn ld_x_deref_i(&mut self, x: u8) -> Self::Result {
-    for i in 0..x {
-      self.set_reg(i, self.mem[self.reg_i as usize + i as usize])?;
+    for i in 0..=x {
+      let val = self.get_mem(self.reg_i as usize + i as usize)?;
+      self.set_reg(i, val)?;
     }
 
+    self.advance_i(x);
     Ok(Step::Next)
   }
 
@@ -0,0 +1,48 @@
+//! Golden frames of the programs in `tests/golden`, see [chip8::golden].
+
+use chip8::golden;
+
+#[test]
+fn golden_frames() {
+  let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+  let reports = golden::run_dir(dir, None, false).unwrap();
+  assert!(!reports.is_empty());
+
+  let failed = reports
+    .iter()
+    .filter(|report| !report.outcome.is_ok())
+    .map(ToString::to_string)
+    .collect::<Vec<_>>();
+
+  assert!(failed.is_empty(), "{}", failed.join("\n"));
+}
+
+#[test]
+fn hash_reports() {
+  let actual = vec![0; 32];
+  let hash = golden::vram_sha1(&actual);
+
+  let missing = golden::Report {
+    name: "a.ch8".to_string(),
+    outcome: golden::Outcome::Missing(actual.clone()),
+  };
+  assert_eq!(
+    missing.to_string(),
+    format!("missing a.ch8: got {hash}, run with `--update` to write it")
+  );
+
+  let failed = golden::Report {
+    name: "a.ch8".to_string(),
+    outcome: golden::Outcome::Fail {
+      expected: golden::Golden::Hash("0".repeat(40)),
+      actual,
+    },
+  };
+  assert_eq!(
+    failed.to_string(),
+    format!(
+      "FAIL    a.ch8: expected hash {}, got {hash}, no reference image is stored",
+      "0".repeat(40)
+    )
+  );
+}